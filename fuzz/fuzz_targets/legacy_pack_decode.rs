@@ -0,0 +1,24 @@
+//! 旧版协议解码：任意输入都不应导致崩溃
+//!
+//! 运行：`cargo +nightly fuzz run legacy_pack_decode`
+
+#![no_main]
+
+use bigherox_robocup::data_legacy::{LegacyPackFromCoach, LegacyPackFromRobot};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // 解码成功时，重新编码再解码应得到相同的字节（按字节比较，避免NaN不相等）
+    if let Ok(pack) = LegacyPackFromRobot::try_from_bytes(data) {
+        let bytes = pack.to_bytes();
+        let round_pack = LegacyPackFromRobot::try_from_bytes(&bytes)
+            .expect("Failed to decode re-encoded robot pack!");
+        assert_eq!(round_pack.to_bytes(), bytes);
+    }
+    if let Ok(pack) = LegacyPackFromCoach::try_from_bytes(data) {
+        let bytes = pack.to_bytes();
+        let round_pack = LegacyPackFromCoach::try_from_bytes(&bytes)
+            .expect("Failed to decode re-encoded coach pack!");
+        assert_eq!(round_pack.to_bytes(), bytes);
+    }
+});
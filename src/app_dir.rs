@@ -0,0 +1,155 @@
+//! 程序目录查找：配置目录与数据目录
+//!
+//! 查找顺序：
+//! 1. 命令行参数`--config-dir <目录>`，解析后由`set_cli_config_dir`设置
+//! 2. 环境变量`BIGHEROX_HOME`
+//! 3. XDG目录：`$XDG_CONFIG_HOME/bigherox-robocup`（配置）与`$XDG_DATA_HOME/bigherox-robocup`（数据）
+//! 4. 可执行文件所在目录
+//!
+//! 前两项为用户明确指定，目录不存在时直接报错，不再继续往下查找。
+
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use static_init::dynamic;
+
+use crate::error::{BigHeroXError, BigHeroXResult};
+
+/// XDG目录下的子目录名
+pub const APP_DIR_NAME: &str = "bigherox-robocup";
+/// 指定程序目录的环境变量
+pub const HOME_ENV_VAR: &str = "BIGHEROX_HOME";
+/// 指定程序目录的命令行参数
+pub const CONFIG_DIR_ARG: &str = "--config-dir";
+
+/// 程序目录的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppDirSource {
+    /// 命令行参数
+    CliArg,
+    /// 环境变量`BIGHEROX_HOME`
+    EnvVar,
+    /// XDG配置/数据目录
+    Xdg,
+    /// 可执行文件所在目录
+    ExeDir,
+}
+
+impl std::fmt::Display for AppDirSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let display_str = match self {
+            AppDirSource::CliArg => CONFIG_DIR_ARG,
+            AppDirSource::EnvVar => HOME_ENV_VAR,
+            AppDirSource::Xdg => "XDG",
+            AppDirSource::ExeDir => "exe dir",
+        };
+        f.write_str(display_str)
+    }
+}
+
+/// 查找到的程序目录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppDirs {
+    /// 配置目录，其下存放`robot_config`等
+    pub config_dir: PathBuf,
+    /// 数据目录，其下存放`fonts`等
+    pub data_dir: PathBuf,
+    /// 来源
+    pub source: AppDirSource,
+}
+
+impl AppDirs {
+    /// 按顺序查找程序目录
+    pub fn resolve(cli_dir: Option<&Path>) -> BigHeroXResult<Self> {
+        // 命令行参数
+        if let Some(dir) = cli_dir {
+            return Self::from_home(dir, AppDirSource::CliArg);
+        }
+        // 环境变量
+        if let Some(dir) = std::env::var_os(HOME_ENV_VAR).filter(|dir| !dir.is_empty()) {
+            return Self::from_home(Path::new(&dir), AppDirSource::EnvVar);
+        }
+        // XDG目录
+        if let Some(dirs) = Self::from_xdg() {
+            return Ok(dirs);
+        }
+        // 可执行文件所在目录
+        Self::from_exe_dir()
+    }
+
+    /// 配置与数据放在同一目录下
+    fn from_home(dir: &Path, source: AppDirSource) -> BigHeroXResult<Self> {
+        if !dir.is_dir() {
+            return Err(BigHeroXError::AppDirNotFound(source, dir.to_path_buf()));
+        }
+        Ok(Self {
+            config_dir: dir.to_path_buf(),
+            data_dir: dir.to_path_buf(),
+            source,
+        })
+    }
+
+    /// 仅当XDG配置目录下已有本程序的目录时采用；数据目录不存在时与配置目录相同。
+    fn from_xdg() -> Option<Self> {
+        let home_dir = std::env::var_os("HOME").map(PathBuf::from);
+        let xdg_dir = |var: &str, fallback: &[&str]| {
+            std::env::var_os(var)
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| {
+                    home_dir
+                        .as_ref()
+                        .map(|home| fallback.iter().fold(home.clone(), |dir, sub| dir.join(sub)))
+                })
+                .map(|dir| dir.join(APP_DIR_NAME))
+        };
+        let config_dir = xdg_dir("XDG_CONFIG_HOME", &[".config"]).filter(|dir| dir.is_dir())?;
+        let data_dir = xdg_dir("XDG_DATA_HOME", &[".local", "share"])
+            .filter(|dir| dir.is_dir())
+            .unwrap_or_else(|| config_dir.clone());
+        Some(Self {
+            config_dir,
+            data_dir,
+            source: AppDirSource::Xdg,
+        })
+    }
+
+    /// 可执行文件所在目录。
+    /// 直接`cargo run`时可执行文件位于`target/<profile>/`下，因此向上找到第一个带有`fonts`或`robot_config`的目录。
+    fn from_exe_dir() -> BigHeroXResult<Self> {
+        let exe_path = std::env::current_exe()?;
+        let exe_dir = exe_path
+            .parent()
+            .ok_or(BigHeroXError::AppDirNotFound(
+                AppDirSource::ExeDir,
+                exe_path.clone(),
+            ))?
+            .to_path_buf();
+        let dir = exe_dir
+            .ancestors()
+            .find(|dir| dir.join("fonts").is_dir() || dir.join("robot_config").is_dir())
+            .map(Path::to_path_buf)
+            .unwrap_or(exe_dir);
+        Self::from_home(&dir, AppDirSource::ExeDir)
+    }
+}
+
+/// 命令行指定的程序目录
+static CLI_CONFIG_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// 设置命令行指定的程序目录。需在首次使用`APP_DIRS`之前调用，之后调用无效。
+pub fn set_cli_config_dir(dir: PathBuf) {
+    let _ = CLI_CONFIG_DIR.set(dir);
+}
+
+/// 程序目录，首次使用时查找（须为lazy，以便先读取命令行参数）
+#[dynamic(lazy)]
+pub static APP_DIRS: BigHeroXResult<AppDirs> =
+    AppDirs::resolve(CLI_CONFIG_DIR.get().map(PathBuf::as_path));
+
+/// 获取程序目录
+pub fn app_dirs() -> BigHeroXResult<&'static AppDirs> {
+    APP_DIRS.as_ref().map_err(Clone::clone)
+}
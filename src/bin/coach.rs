@@ -1,12 +1,17 @@
 use bevy::prelude::*;
-use bigherox_robocup::{coach::CoachMode, MainPlugin, Mode};
+use bigherox_robocup::{coach::CoachMode, launch_args::LaunchArgs, MainPlugin, Mode};
+use clap::Parser;
 
 fn main() {
+    let launch_args = LaunchArgs::parse();
+    if let Err(err) = launch_args.init_app_dirs() {
+        eprintln!("Failed to locate config dir: {err:?}");
+        std::process::exit(1);
+    }
+    let default_mode = Mode::Coach {
+        mode: CoachMode::Normal,
+    };
     App::new()
-        .add_plugins(MainPlugin {
-            mode: Mode::Coach {
-                mode: CoachMode::Normal,
-            },
-        })
+        .add_plugins(MainPlugin::from_launch_args(default_mode, launch_args))
         .run();
 }
@@ -1,12 +1,17 @@
 use bevy::prelude::*;
-use bigherox_robocup::{robot::RobotRole, MainPlugin, Mode};
+use bigherox_robocup::{launch_args::LaunchArgs, robot::RobotRole, MainPlugin, Mode};
+use clap::Parser;
 
 fn main() {
+    let launch_args = LaunchArgs::parse();
+    if let Err(err) = launch_args.init_app_dirs() {
+        eprintln!("Failed to locate config dir: {err:?}");
+        std::process::exit(1);
+    }
+    let default_mode = Mode::Robot {
+        role: RobotRole::GoalKeeper,
+    };
     App::new()
-        .add_plugins(MainPlugin {
-            mode: Mode::Robot {
-                role: RobotRole::GoalKeeper,
-            },
-        })
+        .add_plugins(MainPlugin::from_launch_args(default_mode, launch_args))
         .run();
 }
@@ -1,4 +1,19 @@
+pub mod network;
+
+use std::{
+    fs::create_dir_all,
+    net::{Ipv4Addr, SocketAddrV4},
+    path::PathBuf,
+};
+
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use static_init::dynamic;
+
+use crate::{
+    app_dir::app_dirs, data_legacy::convert::LegacyFrame, error::BigHeroXResult,
+    launch_args::LaunchArgs, traits::FastAccessData,
+};
 
 pub struct CoachPlugin {
     pub mode: CoachMode,
@@ -6,9 +21,18 @@ pub struct CoachPlugin {
 
 impl Plugin for CoachPlugin {
     fn build(&self, app: &mut App) {
+        // 读取配置文件，再用命令行参数覆盖
+        let mut config = CoachConfig::load_or_default();
+        if let Some(launch_args) = app.world.get_resource::<LaunchArgs>() {
+            config.override_with(launch_args);
+        }
         app
             // 添加模式
-            .insert_resource(self.mode);
+            .insert_resource(self.mode)
+            // 添加配置
+            .insert_resource(config)
+            // 添加机器人通信组件
+            .add_plugins(network::CoachNetworkPlugin);
     }
 }
 
@@ -31,3 +55,92 @@ impl std::fmt::Display for CoachMode {
         f.write_str(display_str)
     }
 }
+
+#[dynamic(lazy)]
+pub static COACH_CONFIG_DIR: BigHeroXResult<PathBuf> = app_dirs().and_then(|dirs| {
+    let config_dir = dirs.config_dir.join("coach_config");
+    if !config_dir.is_dir() {
+        create_dir_all(&config_dir)?;
+    }
+    Ok(config_dir)
+});
+
+/// 教练机设置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, Resource)]
+#[serde(default)]
+pub struct CoachConfig {
+    pub network: CoachNetworkConfig,
+}
+
+impl CoachConfig {
+    /// 用命令行参数覆盖配置
+    pub fn override_with(&mut self, launch_args: &LaunchArgs) {
+        if let Some(coach_ip) = launch_args.coach_ip {
+            self.network.bind_ip = coach_ip;
+        }
+        if let Some(coach_port) = launch_args.coach_port {
+            self.network.bind_port = coach_port;
+        }
+    }
+}
+
+/// 教练机设置：网络
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoachNetworkConfig {
+    /// 本机绑定的IP地址，默认监听所有网卡
+    pub bind_ip: Ipv4Addr,
+    /// 本机绑定的端口，即机器人设置中的教练机端口
+    pub bind_port: u16,
+    /// 向机器人发送数据的间隔，单位：毫秒
+    pub send_interval_ms: u64,
+    /// 超过该时间未收到数据的机器人视为离线，单位：毫秒
+    pub robot_timeout_ms: u64,
+    /// 合并障碍物的距离阈值：不同机器人看到的障碍物距离小于该值时视为同一个，单位：厘米
+    pub barrier_merge_dist_cm: u16,
+    /// 旧协议坐标系，用于新旧协议之间的转换，应与机器人设置中的相同
+    pub legacy_frame: LegacyFrame,
+    /// 旧版数据包的发送方式
+    pub legacy_send: LegacySendMode,
+}
+
+/// 旧版数据包的发送方式。新版机器人总是单播
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LegacySendMode {
+    /// 发往广播或组播地址，与原C++教练机相同：编号1~5的数据包都发出，机器人按数据包中的编号接收。
+    /// 使用组播时机器人需设置`multicast_group`
+    Broadcast(SocketAddrV4),
+    /// 只发给在线的机器人，发往其最近一次发来数据的地址
+    Unicast,
+}
+
+impl Default for LegacySendMode {
+    fn default() -> Self {
+        Self::Broadcast(SocketAddrV4::new(Ipv4Addr::BROADCAST, 20091))
+    }
+}
+
+impl Default for CoachNetworkConfig {
+    fn default() -> Self {
+        Self {
+            bind_ip: Ipv4Addr::UNSPECIFIED,
+            bind_port: 20090,
+            send_interval_ms: 30,
+            robot_timeout_ms: 1000,
+            barrier_merge_dist_cm: 50,
+            legacy_frame: Default::default(),
+            legacy_send: Default::default(),
+        }
+    }
+}
+
+impl FastAccessData<'_> for CoachConfig {
+    fn file_path() -> BigHeroXResult<&'static str> {
+        #[dynamic(lazy)]
+        static FILE_PATH_STRING: BigHeroXResult<String> = COACH_CONFIG_DIR
+            .as_ref()
+            .map(|dir| dir.join("config.toml").to_string_lossy().to_string())
+            .map_err(Clone::clone);
+        FILE_PATH_STRING.as_deref().map_err(Clone::clone)
+    }
+}
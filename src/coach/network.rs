@@ -0,0 +1,647 @@
+//! 与机器人通信：旧版UDP协议与新版协议，教练机一侧
+//!
+//! 接收各机器人发来的`LegacyPackFromRobot`或新版`ModernRobotState`，按编号保存各机器人的最新状态；
+//! 按固定间隔发送`LegacyPackFromCoach`，其中球员、障碍物与球的位置由各机器人的数据合并而来。
+//! 旧版数据包默认与原C++教练机一样广播给编号1~5的机器人，也可设置为单播给在线的机器人（见`LegacySendMode`）；
+//! 新版机器人总是单播，收到的是`ModernCoachCommand`。
+
+use std::{
+    collections::HashMap,
+    io,
+    net::{SocketAddr, SocketAddrV4},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bevy::prelude::*;
+use glam::I16Vec2;
+use mio::{net::UdpSocket, Events, Interest, Poll, Token};
+
+use crate::{
+    data_legacy::{
+        convert::LegacyFrame, LegacyCtrl, LegacyDecodeStats, LegacyMsgType, LegacyPackBarrier,
+        LegacyPackFromCoach, LegacyPackFromCoachPlayer, LegacyPackFromRobot,
+    },
+    data_modern::{
+        is_newer_packet, negotiate_version, ModernBody, ModernCoachCommand, ModernHeader,
+        ModernPacket, ModernRobotState, PeerProtocol, MODERN_PROTOCOL_VERSION,
+    },
+    field::world_state::ObstacleList,
+};
+
+use super::{CoachConfig, LegacySendMode};
+
+/*
+ * Part: Plugin
+ */
+
+pub(super) struct CoachNetworkPlugin;
+
+impl Plugin for CoachNetworkPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<RobotPackReceiveEvent>()
+            .init_resource::<LegacyDecodeStats>()
+            .init_resource::<RobotsLegacyData>()
+            .init_resource::<CoachLegacyCommands>()
+            .add_systems(Startup, network_bind_system)
+            .add_systems(FixedPreUpdate, network_receive_system)
+            .add_systems(FixedPostUpdate, network_send_system);
+    }
+}
+
+/*
+ * Part: Event & Resource
+ */
+
+/// 旧协议中的机器人数量，编号为1~5
+pub const LEGACY_ROBOT_COUNT: usize = 5;
+
+/// 收到机器人数据包。新版数据包也会转为旧版格式发送此事件
+#[derive(Debug, Clone, Copy, Event)]
+pub struct RobotPackReceiveEvent {
+    pub pack: LegacyPackFromRobot,
+    pub from: SocketAddr,
+}
+
+/// 单个机器人最近一次发来的数据
+#[derive(Debug, Clone)]
+pub struct RobotLegacyState {
+    /// 新版数据包也会转为旧版格式记录于此
+    pub pack: LegacyPackFromRobot,
+    /// 发送地址，教练机向该地址回复
+    pub addr: SocketAddr,
+    pub receive_time: SystemTime,
+    /// 该机器人使用的协议，教练机按此格式回复
+    pub protocol: PeerProtocol,
+    /// 新版协议：完整的机器人状态
+    pub modern: Option<ModernRobotState>,
+    /// 新版协议：最近一次收到的包头，用于丢弃乱序的数据包
+    pub last_header: Option<ModernHeader>,
+}
+
+/// 各机器人的数据，以机器人编号为键
+#[derive(Debug, Default, Resource)]
+pub struct RobotsLegacyData {
+    pub robots: HashMap<u8, RobotLegacyState>,
+}
+
+impl RobotsLegacyData {
+    /// 在`timeout`内发来过数据的机器人
+    pub fn online(
+        &self,
+        now: SystemTime,
+        timeout: Duration,
+    ) -> impl Iterator<Item = &RobotLegacyState> {
+        self.robots.values().filter(move |state| {
+            now.duration_since(state.receive_time)
+                .map_or(true, |duration| duration <= timeout)
+        })
+    }
+}
+
+/// 下发给单个机器人的指令
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoachLegacyCommand {
+    pub ctrl: LegacyCtrl,
+    pub setup_pos: I16Vec2,
+    /// MoveTo
+    pub target_pos: I16Vec2,
+    pub target_angle: i16,
+    pub speed: u8,
+    /// Defence
+    pub def_angle: i16,
+    pub def_dist: i16,
+    /// Pass
+    pub pass_target_pos: I16Vec2,
+    /// Catch
+    pub catch_from_pos: I16Vec2,
+}
+
+impl Default for CoachLegacyCommand {
+    fn default() -> Self {
+        Self {
+            ctrl: LegacyCtrl::Stop,
+            setup_pos: I16Vec2::ZERO,
+            target_pos: I16Vec2::ZERO,
+            target_angle: 0,
+            speed: 0,
+            def_angle: 0,
+            def_dist: 0,
+            pass_target_pos: I16Vec2::ZERO,
+            catch_from_pos: I16Vec2::ZERO,
+        }
+    }
+}
+
+/// 下发给各机器人的指令，以机器人编号为键；没有指令的机器人收到`Stop`
+#[derive(Debug, Default, Resource)]
+pub struct CoachLegacyCommands {
+    pub commands: HashMap<u8, CoachLegacyCommand>,
+}
+
+#[derive(Resource)]
+pub(super) struct CoachNetworkModule {
+    poll: Poll,
+    events: Events,
+    socket: UdpSocket,
+    send_interval: Duration,
+    robot_timeout: Duration,
+    barrier_merge_dist: i16,
+    legacy_frame: LegacyFrame,
+    legacy_send: LegacySendMode,
+    last_send_time: Option<SystemTime>,
+    /// 新版协议的发送序号
+    seq: u32,
+}
+
+const UDP_TOKEN: Token = Token(0);
+
+impl CoachNetworkModule {
+    /// 生成下一个发给`robot_id`的新版数据包
+    fn modern_packet(&mut self, robot_id: u8, version: u16, body: ModernBody) -> ModernPacket {
+        self.seq = self.seq.wrapping_add(1);
+        ModernPacket {
+            header: ModernHeader {
+                version,
+                robot_id,
+                seq: self.seq,
+                send_time_ms: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |duration| duration.as_millis() as u64),
+            },
+            body,
+        }
+    }
+
+    fn send_bytes(&self, bytes: &[u8], addr: SocketAddr) {
+        match self.socket.send_to(bytes, addr) {
+            Ok(_) => {}
+            // 发送缓冲区满：丢弃这一帧，下次再发
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {}
+            Err(err) => warn!("Failed to send to robot {addr}: {err}"),
+        }
+    }
+}
+
+/*
+ * Part: System
+ */
+
+fn network_bind_system(mut commands: Commands, config: Res<CoachConfig>) {
+    let network = config.network;
+    // Create a poll instance.
+    let poll = match Poll::new() {
+        Ok(poll) => poll,
+        Err(err) => {
+            warn!("Failed to create poll using mio! {err:?}");
+            return;
+        }
+    };
+    let bind_addr = SocketAddr::V4(SocketAddrV4::new(network.bind_ip, network.bind_port));
+    let mut socket = match UdpSocket::bind(bind_addr) {
+        Ok(socket) => socket,
+        Err(err) => {
+            warn!("Failed to bind {bind_addr}! {err:?}");
+            return;
+        }
+    };
+    if let Err(err) = poll
+        .registry()
+        .register(&mut socket, UDP_TOKEN, Interest::READABLE)
+    {
+        warn!("Failed to register udp socket on mio! {err:?}");
+        return;
+    }
+    if let LegacySendMode::Broadcast(_) = network.legacy_send {
+        if let Err(err) = socket.set_broadcast(true) {
+            warn!("Failed to enable broadcast on udp socket! {err:?}");
+        }
+    }
+    info!(
+        "Robot network: bind {bind_addr}, legacy send {:?}",
+        network.legacy_send
+    );
+    commands.insert_resource(CoachNetworkModule {
+        poll,
+        events: Events::with_capacity(8),
+        socket,
+        send_interval: Duration::from_millis(network.send_interval_ms),
+        robot_timeout: Duration::from_millis(network.robot_timeout_ms),
+        barrier_merge_dist: network.barrier_merge_dist_cm.min(i16::MAX as u16) as i16,
+        legacy_frame: network.legacy_frame,
+        legacy_send: network.legacy_send,
+        last_send_time: None,
+        seq: 0,
+    });
+}
+
+fn network_receive_system(
+    module: Option<ResMut<CoachNetworkModule>>,
+    mut robots_data: ResMut<RobotsLegacyData>,
+    mut decode_stats: ResMut<LegacyDecodeStats>,
+    mut receive_event_writer: EventWriter<RobotPackReceiveEvent>,
+) {
+    let Some(mut module) = module else {
+        return;
+    };
+    let module = module.as_mut();
+    // 不阻塞固定时间系统
+    if let Err(err) = module.poll.poll(&mut module.events, Some(Duration::ZERO)) {
+        warn!("Failed to poll events on robot network mio! {err:?}");
+        return;
+    }
+    if !module.events.iter().any(|event| event.token() == UDP_TOKEN) {
+        return;
+    }
+    let mut buf = [0u8; 2048];
+    // 读取所有已到达的数据包
+    loop {
+        let (count, from) = match module.socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+            Err(err) => {
+                warn!("Failed to receive from robot: {err}");
+                break;
+            }
+        };
+        let bytes = &buf[..count];
+        let state = if ModernPacket::is_modern(bytes) {
+            let packet = match ModernPacket::try_from_bytes(bytes) {
+                Ok(packet) => packet,
+                Err(err) => {
+                    warn!("Drop malformed modern packet from {from}: {err}");
+                    continue;
+                }
+            };
+            match receive_modern(module, &mut robots_data, packet, from) {
+                Some(state) => state,
+                None => continue,
+            }
+        } else {
+            let decode_result = LegacyPackFromRobot::try_from_bytes(bytes);
+            decode_stats.record(&decode_result);
+            let pack = match decode_result {
+                Ok(pack) => pack,
+                Err(err) => {
+                    warn!("Drop malformed pack from {from}: {err}");
+                    continue;
+                }
+            };
+            RobotLegacyState {
+                pack,
+                addr: from,
+                receive_time: SystemTime::now(),
+                protocol: PeerProtocol::Legacy,
+                modern: None,
+                last_header: None,
+            }
+        };
+        let pack = state.pack;
+        if !(1..=LEGACY_ROBOT_COUNT as u8).contains(&pack.id) {
+            warn!("Drop pack from {from}: invalid robot id {}", pack.id);
+            continue;
+        }
+        robots_data.robots.insert(pack.id, state);
+        receive_event_writer.send(RobotPackReceiveEvent { pack, from });
+    }
+}
+
+/// 处理新版数据包：回复协商，收到机器人状态时返回
+fn receive_modern(
+    module: &mut CoachNetworkModule,
+    robots_data: &mut RobotsLegacyData,
+    packet: ModernPacket,
+    from: SocketAddr,
+) -> Option<RobotLegacyState> {
+    let ModernPacket { header, body } = packet;
+    match body {
+        ModernBody::Hello {
+            min_version,
+            max_version,
+        } => {
+            let version = negotiate_version(min_version, max_version);
+            info!(
+                "Robot {} from {from} supports modern protocol v{min_version}~v{max_version}, use {version:?}",
+                header.robot_id
+            );
+            let ack = module.modern_packet(
+                header.robot_id,
+                version.unwrap_or(MODERN_PROTOCOL_VERSION),
+                ModernBody::HelloAck { version },
+            );
+            module.send_bytes(&ack.to_bytes(), from);
+            // 机器人（重新）发起协商，通常是刚刚启动，序号从头开始
+            if let Some(robot) = robots_data.robots.get_mut(&header.robot_id) {
+                robot.last_header = None;
+            }
+            None
+        }
+        ModernBody::RobotState(state) => {
+            // 丢弃乱序的数据包
+            let last_header = robots_data
+                .robots
+                .get(&header.robot_id)
+                .and_then(|robot| robot.last_header.as_ref());
+            if !is_newer_packet(&header, last_header) {
+                return None;
+            }
+            Some(RobotLegacyState {
+                pack: state.to_legacy_pack(header.robot_id, &module.legacy_frame),
+                addr: from,
+                receive_time: SystemTime::now(),
+                protocol: PeerProtocol::Modern {
+                    version: header.version,
+                },
+                modern: Some(state),
+                last_header: Some(header),
+            })
+        }
+        ModernBody::HelloAck { .. } | ModernBody::CoachCommand(_) => None,
+    }
+}
+
+fn network_send_system(
+    module: Option<ResMut<CoachNetworkModule>>,
+    robots_data: Res<RobotsLegacyData>,
+    coach_commands: Res<CoachLegacyCommands>,
+) {
+    let Some(mut module) = module else {
+        return;
+    };
+    // 按固定间隔发送
+    let now_time = SystemTime::now();
+    if module.last_send_time.is_some_and(|last_send_time| {
+        now_time
+            .duration_since(last_send_time)
+            .is_ok_and(|duration| duration < module.send_interval)
+    }) {
+        return;
+    }
+    module.last_send_time = Some(now_time);
+    let online_robots: Vec<_> = robots_data.online(now_time, module.robot_timeout).collect();
+    let info = CoachLegacyInfo::merge(&online_robots, module.barrier_merge_dist);
+    let command_of = |robot_id: u8| {
+        coach_commands
+            .commands
+            .get(&robot_id)
+            .copied()
+            .unwrap_or_default()
+    };
+    // 旧版广播：每个编号一个数据包，已知为新版的机器人除外
+    if let LegacySendMode::Broadcast(target) = module.legacy_send {
+        for robot_id in 1..=LEGACY_ROBOT_COUNT as u8 {
+            let is_modern = robots_data
+                .robots
+                .get(&robot_id)
+                .is_some_and(|robot| matches!(robot.protocol, PeerProtocol::Modern { .. }));
+            if !is_modern {
+                let pack = info.coach_pack(robot_id, &command_of(robot_id));
+                module.send_bytes(&pack.to_bytes(), SocketAddr::V4(target));
+            }
+        }
+    }
+    for robot in online_robots {
+        let pack = info.coach_pack(robot.pack.id, &command_of(robot.pack.id));
+        match robot.protocol {
+            PeerProtocol::Legacy => {
+                if module.legacy_send == LegacySendMode::Unicast {
+                    module.send_bytes(&pack.to_bytes(), robot.addr);
+                }
+            }
+            PeerProtocol::Modern { version } => {
+                let frame = module.legacy_frame;
+                let mut modern_command = ModernCoachCommand::from_legacy_pack(&pack, &frame);
+                // 新版协议不限制障碍物数量
+                modern_command.obstacles = ObstacleList::from_legacy(&frame, &info.all_barriers);
+                let packet = module.modern_packet(
+                    robot.pack.id,
+                    version,
+                    ModernBody::CoachCommand(modern_command),
+                );
+                module.send_bytes(&packet.to_bytes(), robot.addr);
+            }
+        }
+    }
+}
+
+/*
+ * Part: Pack
+ */
+
+/// 由各机器人数据合并得到的场上信息，对所有机器人相同
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CoachLegacyInfo {
+    players: [LegacyPackFromCoachPlayer; LEGACY_ROBOT_COUNT],
+    /// 旧协议最多10个障碍物
+    barriers: [LegacyPackBarrier; 10],
+    /// 合并后的全部障碍物
+    all_barriers: Vec<LegacyPackBarrier>,
+    found_ball: bool,
+    ball_pos: I16Vec2,
+}
+
+impl CoachLegacyInfo {
+    /// 合并在线机器人的数据：
+    /// - 球员：按编号填入，未在线的为`Offline`
+    /// - 障碍物：距离小于`merge_dist`的视为同一个，取平均位置与最大尺寸；去掉与队友重合的障碍物
+    /// - 球：优先采用持球机器人的位置，否则取各机器人看到的球的平均位置
+    pub(crate) fn merge(robots: &[&RobotLegacyState], merge_dist: i16) -> Self {
+        let mut players = [LegacyPackFromCoachPlayer {
+            ctrl: LegacyCtrl::Offline,
+            ..Default::default()
+        }; LEGACY_ROBOT_COUNT];
+        for robot in robots {
+            let pack = &robot.pack;
+            players[pack.id as usize - 1] = LegacyPackFromCoachPlayer {
+                ctrl: pack.ctrl,
+                has_ball: pack.has_ball,
+                pos: pack.pos,
+                angle: pack.angle,
+            };
+        }
+
+        let is_near = |a: I16Vec2, b: I16Vec2| {
+            let delta = a.as_ivec2() - b.as_ivec2();
+            delta.length_squared() < i32::from(merge_dist).pow(2)
+        };
+        // 合并中的障碍物：尺寸、位置之和、数量
+        let mut merged: Vec<(u8, glam::IVec2, i32)> = Vec::new();
+        robots
+            .iter()
+            .flat_map(|robot| robot.pack.barriers)
+            .filter(|barrier| barrier.size > 0)
+            .filter(|barrier| {
+                !robots
+                    .iter()
+                    .any(|robot| is_near(robot.pack.pos, barrier.pos))
+            })
+            .for_each(|barrier| {
+                match merged.iter_mut().find(|(_, pos_sum, count)| {
+                    is_near((*pos_sum / *count).as_i16vec2(), barrier.pos)
+                }) {
+                    Some((size, pos_sum, count)) => {
+                        *size = (*size).max(barrier.size);
+                        *pos_sum += barrier.pos.as_ivec2();
+                        *count += 1;
+                    }
+                    None => merged.push((barrier.size, barrier.pos.as_ivec2(), 1)),
+                }
+            });
+        let all_barriers: Vec<_> = merged
+            .into_iter()
+            .map(|(size, pos_sum, count)| LegacyPackBarrier {
+                size,
+                pos: (pos_sum / count).as_i16vec2(),
+            })
+            .collect();
+        let mut barriers = [LegacyPackBarrier::default(); 10];
+        barriers
+            .iter_mut()
+            .zip(&all_barriers)
+            .for_each(|(barrier, merged_barrier)| *barrier = *merged_barrier);
+
+        let ball_pos = match robots.iter().find(|robot| robot.pack.has_ball) {
+            Some(robot) => Some(robot.pack.pos),
+            None => {
+                let found: Vec<_> = robots
+                    .iter()
+                    .filter(|robot| robot.pack.found_ball)
+                    .map(|robot| robot.pack.found_ball_pos.as_ivec2())
+                    .collect();
+                (!found.is_empty())
+                    .then(|| (found.iter().sum::<glam::IVec2>() / found.len() as i32).as_i16vec2())
+            }
+        };
+
+        Self {
+            players,
+            barriers,
+            all_barriers,
+            found_ball: ball_pos.is_some(),
+            ball_pos: ball_pos.unwrap_or_default(),
+        }
+    }
+
+    /// 生成发给编号为`robot_id`的机器人的数据包
+    pub(crate) fn coach_pack(
+        &self,
+        robot_id: u8,
+        command: &CoachLegacyCommand,
+    ) -> LegacyPackFromCoach {
+        LegacyPackFromCoach {
+            id: robot_id,
+            msg_type: LegacyMsgType::Cmd,
+            players: self.players,
+            barriers: self.barriers,
+            setup_pos: command.setup_pos,
+            found_ball: self.found_ball,
+            ball_pos_from_coach: self.ball_pos,
+            ctrl: command.ctrl,
+            target_pos: command.target_pos,
+            target_angle: command.target_angle,
+            speed: command.speed,
+            def_angle: command.def_angle,
+            def_dist: command.def_dist,
+            pass_target_pos: command.pass_target_pos,
+            catch_from_pos: command.catch_from_pos,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddrV4};
+
+    use super::*;
+
+    /// 经过编解码的机器人数据，与实际收到的相同
+    fn robot_state(
+        id: u8,
+        pos: I16Vec2,
+        barriers: &[(u8, I16Vec2)],
+        ball: Option<I16Vec2>,
+    ) -> RobotLegacyState {
+        let mut pack = LegacyPackFromRobot {
+            id,
+            pos,
+            ctrl: LegacyCtrl::Attack,
+            has_ball: false,
+            found_ball: ball.is_some(),
+            found_ball_pos: ball.unwrap_or_default(),
+            barriers: [Default::default(); 10],
+            ..Default::default()
+        };
+        for (barrier, (size, pos)) in pack.barriers.iter_mut().zip(barriers) {
+            *barrier = LegacyPackBarrier {
+                size: *size,
+                pos: *pos,
+            };
+        }
+        RobotLegacyState {
+            pack: LegacyPackFromRobot::try_from_bytes(&pack.to_bytes())
+                .expect("Failed to read data from bytes!"),
+            addr: SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 20091)),
+            receive_time: SystemTime::now(),
+            protocol: PeerProtocol::Legacy,
+            modern: None,
+            last_header: None,
+        }
+    }
+
+    #[test]
+    fn merge_players_and_barriers() {
+        let robot_1 = robot_state(
+            1,
+            I16Vec2::new(100, 100),
+            &[(30, I16Vec2::new(500, 500)), (20, I16Vec2::new(300, 310))],
+            None,
+        );
+        let robot_3 = robot_state(
+            3,
+            I16Vec2::new(300, 300),
+            &[(40, I16Vec2::new(510, 490)), (20, I16Vec2::new(100, 90))],
+            None,
+        );
+        let info = CoachLegacyInfo::merge(&[&robot_1, &robot_3], 50);
+        // 球员按编号填入
+        assert_eq!(info.players[0].pos, I16Vec2::new(100, 100));
+        assert_eq!(info.players[2].pos, I16Vec2::new(300, 300));
+        assert_eq!(info.players[1].ctrl, LegacyCtrl::Offline);
+        // 同一障碍物合并，队友不算障碍物
+        assert_eq!(
+            info.barriers[0],
+            LegacyPackBarrier {
+                size: 40,
+                pos: I16Vec2::new(505, 495),
+            }
+        );
+        assert_eq!(info.barriers[1], LegacyPackBarrier::default());
+        assert_eq!(info.all_barriers.len(), 1);
+        // 没有机器人看到球
+        assert!(!info.found_ball);
+    }
+
+    #[test]
+    fn merge_ball() {
+        let robot_1 = robot_state(1, I16Vec2::new(100, 100), &[], Some(I16Vec2::new(400, 400)));
+        let mut robot_2 = robot_state(2, I16Vec2::new(200, 200), &[], Some(I16Vec2::new(420, 380)));
+        // 没有看到球的机器人不参与平均
+        let robot_3 = robot_state(3, I16Vec2::new(300, 300), &[], None);
+        assert!(!robot_3.pack.found_ball);
+        let info = CoachLegacyInfo::merge(&[&robot_1, &robot_3], 50);
+        assert_eq!(info.ball_pos, I16Vec2::new(400, 400));
+        let info = CoachLegacyInfo::merge(&[&robot_1, &robot_2], 50);
+        assert!(info.found_ball);
+        assert_eq!(info.ball_pos, I16Vec2::new(410, 390));
+        // 持球机器人优先
+        robot_2.pack.has_ball = true;
+        let info = CoachLegacyInfo::merge(&[&robot_1, &robot_2], 50);
+        assert_eq!(info.ball_pos, I16Vec2::new(200, 200));
+        // 数据包互通
+        let pack = info.coach_pack(2, &CoachLegacyCommand::default());
+        let decoded = LegacyPackFromCoach::try_from_bytes(&pack.to_bytes())
+            .expect("Failed to read data from bytes!");
+        assert_eq!(decoded.id, 2);
+        assert_eq!(decoded.players, info.players);
+        assert_eq!(decoded.ball_pos_from_coach, I16Vec2::new(200, 200));
+    }
+}
@@ -1,7 +1,12 @@
+pub mod convert;
+
+use bevy_ecs::prelude::*;
 use glam::I16Vec2;
 
 use num_enum_derive::{FromPrimitive, IntoPrimitive};
-use primitive_byte_iter::ByteIter;
+use serde::{Deserialize, Serialize};
+
+use crate::error::LegacyDecodeError;
 
 /// 消息类型
 #[derive(
@@ -22,7 +27,18 @@ pub enum LegacyMsgType {
 
 /// 角色号
 #[derive(
-    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, IntoPrimitive, FromPrimitive,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    IntoPrimitive,
+    FromPrimitive,
+    Serialize,
+    Deserialize,
 )]
 #[repr(u8)]
 pub enum LegacyCtrl {
@@ -183,6 +199,198 @@ pub enum Match {
     UndefinedVal = 254,
 }
 
+/*
+ * 解码部分
+ */
+
+/// 包头
+const PACK_HEADER: u16 = 0x55AA;
+
+/// 按大端序读取数据包，并记录当前偏移量，便于报告出错的字段
+struct LegacyByteReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> LegacyByteReader<'a> {
+    /// 检查包头与长度，返回属于本数据包的字节（去掉多余的字节）与指向包头之后的读取器
+    fn new_pack(
+        bytes: &'a [u8],
+        expected_len: usize,
+    ) -> Result<(&'a [u8], Self), LegacyDecodeError> {
+        let mut reader = Self { bytes, offset: 0 };
+        // 包头
+        let header = reader.next_u16("header")?;
+        if header != PACK_HEADER {
+            return Err(LegacyDecodeError::WrongHeader { actual: header });
+        }
+        // 长度
+        let set_len = reader.next_u8("set_len")? as usize;
+        if set_len != expected_len {
+            return Err(LegacyDecodeError::WrongLength {
+                expected: expected_len,
+                actual: set_len,
+            });
+        }
+        if bytes.len() < expected_len {
+            return Err(LegacyDecodeError::WrongLength {
+                expected: expected_len,
+                actual: bytes.len(),
+            });
+        }
+        // 多余的字节不属于本数据包
+        let bytes = &bytes[..expected_len];
+        reader.bytes = bytes;
+        Ok((bytes, reader))
+    }
+
+    fn next_bytes<const N: usize>(
+        &mut self,
+        field: &'static str,
+    ) -> Result<[u8; N], LegacyDecodeError> {
+        let val = self
+            .bytes
+            .get(self.offset..self.offset + N)
+            .and_then(|slice| slice.try_into().ok())
+            .ok_or(LegacyDecodeError::TruncatedField {
+                field,
+                offset: self.offset,
+            })?;
+        self.offset += N;
+        Ok(val)
+    }
+
+    fn next_u8(&mut self, field: &'static str) -> Result<u8, LegacyDecodeError> {
+        self.next_bytes(field).map(u8::from_be_bytes)
+    }
+
+    fn next_bool(&mut self, field: &'static str) -> Result<bool, LegacyDecodeError> {
+        self.next_u8(field).map(|val| val != 0)
+    }
+
+    fn next_u16(&mut self, field: &'static str) -> Result<u16, LegacyDecodeError> {
+        self.next_bytes(field).map(u16::from_be_bytes)
+    }
+
+    fn next_i16(&mut self, field: &'static str) -> Result<i16, LegacyDecodeError> {
+        self.next_bytes(field).map(i16::from_be_bytes)
+    }
+
+    fn next_f32(&mut self, field: &'static str) -> Result<f32, LegacyDecodeError> {
+        self.next_bytes(field).map(f32::from_be_bytes)
+    }
+
+    fn next_i16vec2(&mut self, field: &'static str) -> Result<I16Vec2, LegacyDecodeError> {
+        Ok(I16Vec2::new(self.next_i16(field)?, self.next_i16(field)?))
+    }
+
+    fn next_barrier(&mut self) -> Result<LegacyPackBarrier, LegacyDecodeError> {
+        Ok(LegacyPackBarrier {
+            size: self.next_u8("barrier.size")?,
+            pos: self.next_i16vec2("barrier.pos")?,
+        })
+    }
+
+    fn next_msg_type(&mut self) -> Result<LegacyMsgType, LegacyDecodeError> {
+        let byte = self.next_u8("msg_type")?;
+        match LegacyMsgType::from(byte) {
+            LegacyMsgType::UndefinedVal if byte != u8::from(LegacyMsgType::UndefinedVal) => {
+                Err(LegacyDecodeError::UnknownDiscriminant {
+                    field: "msg_type",
+                    value: byte,
+                })
+            }
+            msg_type => Ok(msg_type),
+        }
+    }
+
+    fn next_ctrl(&mut self, field: &'static str) -> Result<LegacyCtrl, LegacyDecodeError> {
+        let byte = self.next_u8(field)?;
+        ctrl_from_byte(byte).ok_or(LegacyDecodeError::UnknownDiscriminant { field, value: byte })
+    }
+
+    /// 指令与持球标记共用一个字节：最高位为持球标记。
+    /// 字节本身是已定义的指令时，优先作为指令解读。
+    fn next_ctrl_with_ball(
+        &mut self,
+        field: &'static str,
+    ) -> Result<(LegacyCtrl, bool), LegacyDecodeError> {
+        let byte = self.next_u8(field)?;
+        if let Some(ctrl) = ctrl_from_byte(byte) {
+            return Ok((ctrl, false));
+        }
+        match ctrl_from_byte(byte & !BALL_FLAG) {
+            Some(ctrl) if byte & BALL_FLAG != 0 => Ok((ctrl, true)),
+            _ => Err(LegacyDecodeError::UnknownDiscriminant { field, value: byte }),
+        }
+    }
+}
+
+/// 持球标记
+const BALL_FLAG: u8 = 0b1000_0000;
+
+/// 机器人数据包中没有`found_ball`字段，`found_ball_pos`为此值时表示没有看到球。
+/// 原C++球员机没有约定此值，因此取场地范围之外的坐标，场地中心等真实位置不受影响。
+pub const LEGACY_NO_BALL_POS: I16Vec2 = I16Vec2::splat(i16::MIN);
+
+/// 已定义的指令，未定义时返回`None`
+fn ctrl_from_byte(byte: u8) -> Option<LegacyCtrl> {
+    match LegacyCtrl::from(byte) {
+        LegacyCtrl::UndefinedVal if byte != u8::from(LegacyCtrl::UndefinedVal) => None,
+        ctrl => Some(ctrl),
+    }
+}
+
+/// 校验和：最后一字节为其余字节之和
+fn check_sum(bytes: &[u8]) -> Result<(), LegacyDecodeError> {
+    let Some((&set_sum, bytes)) = bytes.split_last() else {
+        return Ok(());
+    };
+    let bytes_sum = bytes.iter().fold(0u8, |sum, val| sum.wrapping_add(*val));
+    if bytes_sum != set_sum {
+        return Err(LegacyDecodeError::WrongChecksum {
+            set: set_sum,
+            calculated: bytes_sum,
+        });
+    }
+    Ok(())
+}
+
+/// 解码统计：按错误类型对收到的数据包计数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Resource)]
+pub struct LegacyDecodeStats {
+    pub decoded: u64,
+    pub wrong_header: u64,
+    pub wrong_length: u64,
+    pub unknown_discriminant: u64,
+    pub truncated_field: u64,
+    pub wrong_checksum: u64,
+}
+
+impl LegacyDecodeStats {
+    /// 记录一次解码结果
+    pub fn record<T>(&mut self, result: &Result<T, LegacyDecodeError>) {
+        let counter = match result {
+            Ok(_) => &mut self.decoded,
+            Err(LegacyDecodeError::WrongHeader { .. }) => &mut self.wrong_header,
+            Err(LegacyDecodeError::WrongLength { .. }) => &mut self.wrong_length,
+            Err(LegacyDecodeError::UnknownDiscriminant { .. }) => &mut self.unknown_discriminant,
+            Err(LegacyDecodeError::TruncatedField { .. }) => &mut self.truncated_field,
+            Err(LegacyDecodeError::WrongChecksum { .. }) => &mut self.wrong_checksum,
+        };
+        *counter += 1;
+    }
+
+    /// 解码失败的数据包总数
+    pub fn malformed(&self) -> u64 {
+        self.wrong_header
+            + self.wrong_length
+            + self.unknown_discriminant
+            + self.truncated_field
+            + self.wrong_checksum
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct LegacyPackBarrier {
     pub size: u8,
@@ -203,7 +411,7 @@ pub struct LegacyPackFromRobot {
     pub ctrl: LegacyCtrl,
     /// 是否已经持有球
     pub has_ball: bool,
-    /// 发现的球
+    /// 发现的球：不在旧协议中传输，由`found_ball_pos`是否为`LEGACY_NO_BALL_POS`决定
     pub found_ball: bool,
     pub found_ball_pos: I16Vec2,
     /// 运动状态
@@ -241,114 +449,44 @@ pub struct LegacyPackFromRobot {
 
 const PACK_FROM_ROBOT_BYTE_LENGTH: usize = 176;
 impl LegacyPackFromRobot {
-    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, String> {
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, LegacyDecodeError> {
         let mut data = Self::default();
-        let mut iter = ByteIter::new(bytes.iter());
-        // 读取包头
-        iter.next_u16();
-        // 长度
-        let set_len = iter
-            .next_u8()
-            .ok_or("Failed to get val from bytes: set_len")?;
-        if bytes.len() < set_len as usize {
-            return Err("Pack bytes len is lower than set len!!!".to_string());
-        }
+        let (bytes, mut reader) = LegacyByteReader::new_pack(bytes, PACK_FROM_ROBOT_BYTE_LENGTH)?;
         // id
-        data.id = iter.next_u8().ok_or("Failed to get val from bytes: id")?;
+        data.id = reader.next_u8("id")?;
         // msg_type
-        data.msg_type = LegacyMsgType::from(
-            iter.next_u8()
-                .ok_or("Failed to get val from bytes: msg_type")?,
-        );
+        data.msg_type = reader.next_msg_type()?;
         // 机器人信息
-        data.pos = iter
-            .next_i16vec2()
-            .ok_or("Failed to get val from bytes: pos")?;
-        data.angle = iter
-            .next_i16()
-            .ok_or("Failed to get val from bytes: angle")?;
+        data.pos = reader.next_i16vec2("pos")?;
+        data.angle = reader.next_i16("angle")?;
         // 机器人状态 and 球标记
-        let byte_ctrl = iter
-            .next_u8()
-            .ok_or("Failed to get val from bytes: ctrl & found_ball")?;
-        data.ctrl = LegacyCtrl::from(byte_ctrl);
-        if let LegacyCtrl::UndefinedVal = data.ctrl {
-            data.has_ball = (byte_ctrl & 0b1000_0000) != 0;
-            data.ctrl = LegacyCtrl::from(byte_ctrl - if data.has_ball { 0b1000_0000 } else { 0 });
-        }
-        data.found_ball_pos = iter
-            .next_i16vec2()
-            .ok_or("Failed to get val from bytes: found_ball_pos")?;
+        (data.ctrl, data.has_ball) = reader.next_ctrl_with_ball("ctrl")?;
+        data.found_ball_pos = reader.next_i16vec2("found_ball_pos")?;
+        data.found_ball = data.found_ball_pos != LEGACY_NO_BALL_POS;
         // 机器人运动速度
-        data.velocity = iter
-            .next_u16()
-            .ok_or("Failed to get val from bytes: velocity")?;
-        data.velocity_angle = iter
-            .next_i16()
-            .ok_or("Failed to get val from bytes: velocity_angle")?;
+        data.velocity = reader.next_u16("velocity")?;
+        data.velocity_angle = reader.next_i16("velocity_angle")?;
         // 传球
-        data.pass_kick = iter
-            .next_bool()
-            .ok_or("Failed to get val from bytes: pass_kick")?;
-        data.pass_target_pos = iter
-            .next_i16vec2()
-            .ok_or("Failed to get val from bytes: pass_target_pos")?;
+        data.pass_kick = reader.next_bool("pass_kick")?;
+        data.pass_target_pos = reader.next_i16vec2("pass_target_pos")?;
         // 障碍物
         for barrier in data.barriers.iter_mut() {
-            barrier.size = iter
-                .next_u8()
-                .ok_or("Failed to get val from bytes: barrier.size")?;
-            barrier.pos = iter
-                .next_i16vec2()
-                .ok_or("Failed to get val from bytes: barrier.pos")?;
+            *barrier = reader.next_barrier()?;
         }
         // 电脑信息
-        data.computer_ac = iter
-            .next_u8()
-            .ok_or("Failed to get val from bytes: computer_ac")?;
-        data.computer_battery_flag = iter
-            .next_u8()
-            .ok_or("Failed to get val from bytes: computer_battery_flag")?;
-        data.computer_battery_percent = iter
-            .next_u8()
-            .ok_or("Failed to get val from bytes: computer_battery_percent")?;
-        data.computer_working_second_count = iter
-            .next_u16()
-            .ok_or("Failed to get val from bytes: computer_working_second_count")?;
-        data.computer_cpu_percent = iter
-            .next_u8()
-            .ok_or("Failed to get val from bytes: computer_cpu_percent")?;
-        data.computer_cpu_frequency_mhz = iter
-            .next_f32()
-            .ok_or("Failed to get val from bytes: computer_cpu_frequency")?;
-        data.soft_version = iter
-            .next_f32()
-            .ok_or("Failed to get val from bytes: soft_version")?;
-        data.robot_power_volt = iter
-            .next_u8()
-            .ok_or("Failed to get val from bytes: robot_power_volt")?;
-        data.robot_charge = iter
-            .next_bool()
-            .ok_or("Failed to get val from bytes: robot_charge")?;
-        data.video_fps = iter
-            .next_u8()
-            .ok_or("Failed to get val from bytes: video_fps")?;
-        data.multicast_fps = iter
-            .next_u8()
-            .ok_or("Failed to get val from bytes: multicast_fps")?;
+        data.computer_ac = reader.next_u8("computer_ac")?;
+        data.computer_battery_flag = reader.next_u8("computer_battery_flag")?;
+        data.computer_battery_percent = reader.next_u8("computer_battery_percent")?;
+        data.computer_working_second_count = reader.next_u16("computer_working_second_count")?;
+        data.computer_cpu_percent = reader.next_u8("computer_cpu_percent")?;
+        data.computer_cpu_frequency_mhz = reader.next_f32("computer_cpu_frequency_mhz")?;
+        data.soft_version = reader.next_f32("soft_version")?;
+        data.robot_power_volt = reader.next_u8("robot_power_volt")?;
+        data.robot_charge = reader.next_bool("robot_charge")?;
+        data.video_fps = reader.next_u8("video_fps")?;
+        data.multicast_fps = reader.next_u8("multicast_fps")?;
         // 返回数据：记得校验
-        let bytes_sum = bytes
-            .iter()
-            .map(ToOwned::to_owned)
-            .fold(0u8, u8::wrapping_add);
-        let set_sum = *bytes.last().expect("!!!");
-        let bytes_sum = bytes_sum.wrapping_sub(set_sum);
-        if bytes_sum != set_sum {
-            return Err(format!(
-                "Check failed: Set: {} Actual: {}",
-                set_sum, bytes_sum
-            ));
-        }
+        check_sum(bytes)?;
         Ok(data)
     }
 
@@ -373,11 +511,16 @@ impl LegacyPackFromRobot {
         write_to_bytes(&self.pos.y.to_be_bytes());
         write_to_bytes(&self.angle.to_be_bytes());
         // 控制 and 是否有球？
-        let ctrl_byte = u8::from(self.ctrl) | (if self.has_ball { 0b1000_0000 } else { 0 });
+        let ctrl_byte = u8::from(self.ctrl) | (if self.has_ball { BALL_FLAG } else { 0 });
         write_to_bytes(&ctrl_byte.to_be_bytes());
-        // 球坐标
-        write_to_bytes(&self.found_ball_pos.x.to_be_bytes());
-        write_to_bytes(&self.found_ball_pos.y.to_be_bytes());
+        // 球坐标：没有看到球时写入`LEGACY_NO_BALL_POS`
+        let found_ball_pos = if self.found_ball {
+            self.found_ball_pos
+        } else {
+            LEGACY_NO_BALL_POS
+        };
+        write_to_bytes(&found_ball_pos.x.to_be_bytes());
+        write_to_bytes(&found_ball_pos.y.to_be_bytes());
         // 速度
         write_to_bytes(&self.velocity.to_be_bytes());
         write_to_bytes(&self.velocity_angle.to_be_bytes());
@@ -458,7 +601,7 @@ impl From<LegacyPackFromRobot> for [u8; PACK_FROM_ROBOT_BYTE_LENGTH] {
 }
 
 impl TryFrom<[u8; PACK_FROM_ROBOT_BYTE_LENGTH]> for LegacyPackFromRobot {
-    type Error = String;
+    type Error = LegacyDecodeError;
 
     fn try_from(value: [u8; PACK_FROM_ROBOT_BYTE_LENGTH]) -> Result<Self, Self::Error> {
         Self::try_from_bytes(&value)
@@ -515,114 +658,50 @@ pub struct LegacyPackFromCoachPlayer {
  */
 const PACK_FROM_COACH_BYTE_LENGTH: usize = 205;
 impl LegacyPackFromCoach {
-    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, String> {
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, LegacyDecodeError> {
         let mut data = Self::default();
-        let mut iter = ByteIter::new(bytes.iter());
-        // 读取包头
-        iter.next_u16();
-        // 长度
-        let set_len = iter
-            .next_u8()
-            .ok_or("Failed to get val from bytes: set_len")?;
-        if bytes.len() < set_len as usize {
-            return Err("Pack bytes len is lower than set len!!!".to_string());
-        }
+        let (bytes, mut reader) = LegacyByteReader::new_pack(bytes, PACK_FROM_COACH_BYTE_LENGTH)?;
         // id
-        data.id = iter.next_u8().ok_or("Failed to get val from bytes: id")?;
+        data.id = reader.next_u8("id")?;
         // msg_type
-        data.msg_type = LegacyMsgType::from(
-            iter.next_u8()
-                .ok_or("Failed to get val from bytes: msg_type")?,
-        );
+        data.msg_type = reader.next_msg_type()?;
         // 读取数据：Info：球员
         for player in data.players.iter_mut() {
-            let ctrl_byte = iter
-                .next_u8()
-                .ok_or("Failed to get val from bytes: player.ctrl_byte")?;
-            player.ctrl = LegacyCtrl::from(ctrl_byte);
-            player.has_ball = false;
-            if let LegacyCtrl::UndefinedVal = player.ctrl {
-                player.has_ball = (ctrl_byte & 0b1000_0000) != 0;
-                player.ctrl = LegacyCtrl::from(if player.has_ball {
-                    ctrl_byte - 0b1000_0000
-                } else {
-                    ctrl_byte
-                });
-            }
-            player.pos = iter
-                .next_i16vec2()
-                .ok_or("Failed to get val from bytes: player.pos")?;
-            player.angle = iter
-                .next_i16()
-                .ok_or("Failed to get val from bytes: player.angle")?;
+            (player.ctrl, player.has_ball) = reader.next_ctrl_with_ball("player.ctrl")?;
+            player.pos = reader.next_i16vec2("player.pos")?;
+            player.angle = reader.next_i16("player.angle")?;
         }
         // 读取数据：Info：障碍
         for barrier in data.barriers.iter_mut() {
-            barrier.size = iter
-                .next_u8()
-                .ok_or("Failed to get val from bytes: barrier.size")?;
-            barrier.pos = iter
-                .next_i16vec2()
-                .ok_or("Failed to get val from bytes: barrier.pos")?;
+            *barrier = reader.next_barrier()?;
         }
         // 读取数据：Info：结尾
-        data.setup_pos = iter
-            .next_i16vec2()
-            .ok_or("Failed to get val from bytes: setup_pos")?;
-        data.found_ball = iter
-            .next_bool()
-            .ok_or("Failed to get val from bytes: found_ball")?;
-        data.ball_pos_from_coach = iter
-            .next_i16vec2()
-            .ok_or("Failed to get val from bytes: ball_pos_from_coach")?;
+        data.setup_pos = reader.next_i16vec2("setup_pos")?;
+        data.found_ball = reader.next_bool("found_ball")?;
+        data.ball_pos_from_coach = reader.next_i16vec2("ball_pos_from_coach")?;
         // 读取数据：获取控制指令类型
-        data.ctrl = LegacyCtrl::from(iter.next_u8().ok_or("Failed to get val from bytes: ctrl")?);
+        data.ctrl = reader.next_ctrl("ctrl")?;
         // 读取数据：控制指令
         match data.ctrl {
             LegacyCtrl::MoveTo => {
-                data.target_pos = iter
-                    .next_i16vec2()
-                    .ok_or("Failed to get val from bytes: target_pos")?;
-                data.target_angle = iter
-                    .next_i16()
-                    .ok_or("Failed to get val from bytes: target_angle")?;
-                data.speed = iter
-                    .next_u8()
-                    .ok_or("Failed to get val from bytes: speed")?;
+                data.target_pos = reader.next_i16vec2("target_pos")?;
+                data.target_angle = reader.next_i16("target_angle")?;
+                data.speed = reader.next_u8("speed")?;
             }
             LegacyCtrl::Defence => {
-                data.def_angle = iter
-                    .next_i16()
-                    .ok_or("Failed to get val from bytes: def_angle")?;
-                data.def_dist = iter
-                    .next_i16()
-                    .ok_or("Failed to get val from bytes: def_dist")?;
+                data.def_angle = reader.next_i16("def_angle")?;
+                data.def_dist = reader.next_i16("def_dist")?;
             }
             LegacyCtrl::Pass => {
-                data.pass_target_pos = iter
-                    .next_i16vec2()
-                    .ok_or("Failed to get val from bytes: pass_target_pos")?;
+                data.pass_target_pos = reader.next_i16vec2("pass_target_pos")?;
             }
             LegacyCtrl::Catch => {
-                data.catch_from_pos = iter
-                    .next_i16vec2()
-                    .ok_or("Failed to get val from bytes: catch_from_pos")?;
+                data.catch_from_pos = reader.next_i16vec2("catch_from_pos")?;
             }
             _ => (),
         }
         // 返回数据：记得校验
-        let bytes_sum = bytes
-            .iter()
-            .map(ToOwned::to_owned)
-            .fold(0u8, u8::wrapping_add);
-        let set_sum = *bytes.last().expect("!!!");
-        let bytes_sum = bytes_sum.wrapping_sub(set_sum);
-        if bytes_sum != set_sum {
-            return Err(format!(
-                "Check failed: Set: {} Actual: {}",
-                set_sum, bytes_sum
-            ));
-        }
+        check_sum(bytes)?;
         Ok(data)
     }
 
@@ -647,7 +726,7 @@ impl LegacyPackFromCoach {
         self.players.into_iter().for_each(|player| {
             // 状态
             write_to_bytes(
-                &(u8::from(player.ctrl) | if player.has_ball { 0b1000_0000 } else { 0 })
+                &(u8::from(player.ctrl) | if player.has_ball { BALL_FLAG } else { 0 })
                     .to_be_bytes(),
             );
             // 位置
@@ -680,8 +759,9 @@ impl LegacyPackFromCoach {
                 write_to_bytes(&self.speed.to_be_bytes());
             }
             LegacyCtrl::Defence => {
-                write_to_bytes(&self.def_dist.to_be_bytes());
+                // 按C++结构体中的声明顺序：先角度，后距离。尚未有抓包核对，见测试`captured_fixtures`
                 write_to_bytes(&self.def_angle.to_be_bytes());
+                write_to_bytes(&self.def_dist.to_be_bytes());
             }
             LegacyCtrl::Pass => {
                 // 位置
@@ -738,7 +818,7 @@ impl From<LegacyPackFromCoach> for [u8; PACK_FROM_COACH_BYTE_LENGTH] {
 }
 
 impl TryFrom<[u8; PACK_FROM_COACH_BYTE_LENGTH]> for LegacyPackFromCoach {
-    type Error = String;
+    type Error = LegacyDecodeError;
 
     fn try_from(value: [u8; PACK_FROM_COACH_BYTE_LENGTH]) -> Result<Self, Self::Error> {
         Self::try_from_bytes(&value)
@@ -747,6 +827,10 @@ impl TryFrom<[u8; PACK_FROM_COACH_BYTE_LENGTH]> for LegacyPackFromCoach {
 
 #[cfg(test)]
 mod tests {
+    use std::path::Path;
+
+    use proptest::prelude::*;
+
     use super::*;
 
     #[test]
@@ -758,6 +842,29 @@ mod tests {
         assert_eq!(original_data, new_data);
     }
 
+    #[test]
+    fn found_ball_on_wire() {
+        let round_trip = |found_ball, found_ball_pos| {
+            let pack = LegacyPackFromRobot {
+                found_ball,
+                found_ball_pos,
+                ..Default::default()
+            };
+            LegacyPackFromRobot::try_from_bytes(&pack.to_bytes())
+                .expect("Failed to read data from bytes!")
+        };
+        let seen = round_trip(true, I16Vec2::new(120, -40));
+        assert!(seen.found_ball);
+        assert_eq!(seen.found_ball_pos, I16Vec2::new(120, -40));
+        // 没有看到球：不论结构体中的位置，线上都是`LEGACY_NO_BALL_POS`
+        let unseen = round_trip(false, I16Vec2::new(120, -40));
+        assert!(!unseen.found_ball);
+        assert_eq!(unseen.found_ball_pos, LEGACY_NO_BALL_POS);
+        assert!(!round_trip(true, LEGACY_NO_BALL_POS).found_ball);
+        // 开球时球在场地中心
+        assert!(round_trip(true, I16Vec2::ZERO).found_ball);
+    }
+
     #[test]
     fn round_convert_coach() {
         let original_data: LegacyPackFromCoach = Default::default();
@@ -766,4 +873,677 @@ mod tests {
             .expect("Failed to read data from bytes!");
         assert_eq!(original_data, new_data);
     }
+
+    /*
+     * Part: 旧协议无法表示的组合
+     */
+
+    /// `has_ball`与`ctrl`共用一个字节（最高位），
+    /// `ctrl`本身占用最高位，或加上最高位后与其他指令冲突时（如`GoalKickPrime`与`TechCompFindBall`），无法表示持球。
+    fn has_ball_encodable(ctrl: LegacyCtrl) -> bool {
+        let ctrl_byte = u8::from(ctrl);
+        ctrl_byte < BALL_FLAG && LegacyCtrl::from(ctrl_byte | BALL_FLAG) == LegacyCtrl::UndefinedVal
+    }
+
+    /// 去掉机器人数据包中旧协议无法传输的部分
+    fn normalize_robot(mut pack: LegacyPackFromRobot) -> LegacyPackFromRobot {
+        // found_ball不在旧协议中传输，由球的位置是否为`LEGACY_NO_BALL_POS`决定
+        pack.found_ball &= pack.found_ball_pos != LEGACY_NO_BALL_POS;
+        if !pack.found_ball {
+            pack.found_ball_pos = LEGACY_NO_BALL_POS;
+        }
+        pack.has_ball &= has_ball_encodable(pack.ctrl);
+        pack
+    }
+
+    /// 去掉教练机数据包中旧协议无法传输的部分
+    fn normalize_coach(mut pack: LegacyPackFromCoach) -> LegacyPackFromCoach {
+        let default = LegacyPackFromCoach::default();
+        for player in pack.players.iter_mut() {
+            player.has_ball &= has_ball_encodable(player.ctrl);
+        }
+        // 额外部分只传输与ctrl对应的字段，其余字段解码时保持默认值
+        if pack.ctrl != LegacyCtrl::MoveTo {
+            pack.target_pos = default.target_pos;
+            pack.target_angle = default.target_angle;
+            pack.speed = default.speed;
+        }
+        if pack.ctrl != LegacyCtrl::Defence {
+            pack.def_angle = default.def_angle;
+            pack.def_dist = default.def_dist;
+        }
+        if pack.ctrl != LegacyCtrl::Pass {
+            pack.pass_target_pos = default.pass_target_pos;
+        }
+        if pack.ctrl != LegacyCtrl::Catch {
+            pack.catch_from_pos = default.catch_from_pos;
+        }
+        pack
+    }
+
+    /*
+     * Part: 随机数据生成
+     */
+
+    /// 偏向极值的i16
+    fn any_i16() -> impl Strategy<Value = i16> {
+        prop_oneof![
+            Just(i16::MIN),
+            Just(i16::MAX),
+            Just(0),
+            Just(-1),
+            any::<i16>()
+        ]
+    }
+
+    fn any_i16vec2() -> impl Strategy<Value = I16Vec2> {
+        (any_i16(), any_i16()).prop_map(|(x, y)| I16Vec2::new(x, y))
+    }
+
+    /// 排除NaN，便于比较
+    fn any_f32() -> impl Strategy<Value = f32> {
+        prop_oneof![
+            Just(f32::MIN),
+            Just(f32::MAX),
+            proptest::num::f32::NORMAL | proptest::num::f32::SUBNORMAL | proptest::num::f32::ZERO,
+        ]
+    }
+
+    /// 覆盖所有判别值，以及未定义的值
+    fn any_ctrl() -> impl Strategy<Value = LegacyCtrl> {
+        any::<u8>().prop_map(LegacyCtrl::from)
+    }
+
+    fn any_msg_type() -> impl Strategy<Value = LegacyMsgType> {
+        any::<u8>().prop_map(LegacyMsgType::from)
+    }
+
+    fn any_barriers() -> impl Strategy<Value = [LegacyPackBarrier; 10]> {
+        proptest::array::uniform10(
+            (any::<u8>(), any_i16vec2()).prop_map(|(size, pos)| LegacyPackBarrier { size, pos }),
+        )
+    }
+
+    fn any_robot_pack() -> impl Strategy<Value = LegacyPackFromRobot> {
+        (
+            (
+                any::<u8>(),
+                any_msg_type(),
+                any_i16vec2(),
+                any_i16(),
+                any_ctrl(),
+                any::<bool>(),
+                any_i16vec2(),
+                any::<u16>(),
+                any_i16(),
+                any::<bool>(),
+                any_i16vec2(),
+                any_barriers(),
+            ),
+            (
+                any::<u8>(),
+                any::<u8>(),
+                any::<u8>(),
+                any::<u16>(),
+                any::<u8>(),
+                any_f32(),
+                any_f32(),
+                any::<u8>(),
+                any::<bool>(),
+                any::<u8>(),
+                any::<u8>(),
+            ),
+        )
+            .prop_map(
+                |(
+                    (
+                        id,
+                        msg_type,
+                        pos,
+                        angle,
+                        ctrl,
+                        has_ball,
+                        found_ball_pos,
+                        velocity,
+                        velocity_angle,
+                        pass_kick,
+                        pass_target_pos,
+                        barriers,
+                    ),
+                    (
+                        computer_ac,
+                        computer_battery_flag,
+                        computer_battery_percent,
+                        computer_working_second_count,
+                        computer_cpu_percent,
+                        computer_cpu_frequency_mhz,
+                        soft_version,
+                        robot_power_volt,
+                        robot_charge,
+                        video_fps,
+                        multicast_fps,
+                    ),
+                )| LegacyPackFromRobot {
+                    id,
+                    msg_type,
+                    pos,
+                    angle,
+                    ctrl,
+                    has_ball,
+                    found_ball: false,
+                    found_ball_pos,
+                    velocity,
+                    velocity_angle,
+                    pass_kick,
+                    pass_target_pos,
+                    barriers,
+                    computer_ac,
+                    computer_battery_flag,
+                    computer_battery_percent,
+                    computer_working_second_count,
+                    computer_cpu_percent,
+                    computer_cpu_frequency_mhz,
+                    soft_version,
+                    robot_power_volt,
+                    robot_charge,
+                    video_fps,
+                    multicast_fps,
+                },
+            )
+    }
+
+    fn any_coach_pack() -> impl Strategy<Value = LegacyPackFromCoach> {
+        let any_player = (any_ctrl(), any::<bool>(), any_i16vec2(), any_i16()).prop_map(
+            |(ctrl, has_ball, pos, angle)| LegacyPackFromCoachPlayer {
+                ctrl,
+                has_ball,
+                pos,
+                angle,
+            },
+        );
+        // 让额外部分的指令出现得更频繁
+        let any_extra_ctrl = prop_oneof![
+            Just(LegacyCtrl::MoveTo),
+            Just(LegacyCtrl::Defence),
+            Just(LegacyCtrl::Pass),
+            Just(LegacyCtrl::Catch),
+            any_ctrl(),
+        ];
+        (
+            (
+                any::<u8>(),
+                any_msg_type(),
+                proptest::array::uniform5(any_player),
+                any_barriers(),
+                any_i16vec2(),
+                any::<bool>(),
+                any_i16vec2(),
+            ),
+            (
+                any_extra_ctrl,
+                any_i16vec2(),
+                any_i16(),
+                any::<u8>(),
+                any_i16(),
+                any_i16(),
+                any_i16vec2(),
+                any_i16vec2(),
+            ),
+        )
+            .prop_map(
+                |(
+                    (id, msg_type, players, barriers, setup_pos, found_ball, ball_pos_from_coach),
+                    (
+                        ctrl,
+                        target_pos,
+                        target_angle,
+                        speed,
+                        def_angle,
+                        def_dist,
+                        pass_target_pos,
+                        catch_from_pos,
+                    ),
+                )| LegacyPackFromCoach {
+                    id,
+                    msg_type,
+                    players,
+                    barriers,
+                    setup_pos,
+                    found_ball,
+                    ball_pos_from_coach,
+                    ctrl,
+                    target_pos,
+                    target_angle,
+                    speed,
+                    def_angle,
+                    def_dist,
+                    pass_target_pos,
+                    catch_from_pos,
+                },
+            )
+    }
+
+    /*
+     * Part: 随机往返测试
+     */
+
+    proptest! {
+        #[test]
+        fn prop_round_convert_robot(pack in any_robot_pack()) {
+            let pack = normalize_robot(pack);
+            let new_data = LegacyPackFromRobot::try_from_bytes(&pack.to_bytes());
+            prop_assert_eq!(new_data, Ok(pack));
+        }
+
+        #[test]
+        fn prop_round_convert_coach(pack in any_coach_pack()) {
+            let pack = normalize_coach(pack);
+            let new_data = LegacyPackFromCoach::try_from_bytes(&pack.to_bytes());
+            prop_assert_eq!(new_data, Ok(pack));
+        }
+
+        #[test]
+        fn prop_oversized_robot(
+            pack in any_robot_pack(),
+            tail in proptest::collection::vec(any::<u8>(), 1..64),
+        ) {
+            let pack = normalize_robot(pack);
+            let mut bytes = pack.to_bytes().to_vec();
+            bytes.extend(tail);
+            prop_assert_eq!(LegacyPackFromRobot::try_from_bytes(&bytes), Ok(pack));
+        }
+
+        #[test]
+        fn prop_oversized_coach(
+            pack in any_coach_pack(),
+            tail in proptest::collection::vec(any::<u8>(), 1..64),
+        ) {
+            let pack = normalize_coach(pack);
+            let mut bytes = pack.to_bytes().to_vec();
+            bytes.extend(tail);
+            prop_assert_eq!(LegacyPackFromCoach::try_from_bytes(&bytes), Ok(pack));
+        }
+
+        #[test]
+        fn prop_arbitrary_bytes(bytes in proptest::collection::vec(any::<u8>(), 0..256)) {
+            // 只要求不崩溃
+            let _ = LegacyPackFromRobot::try_from_bytes(&bytes);
+            let _ = LegacyPackFromCoach::try_from_bytes(&bytes);
+        }
+    }
+
+    /*
+     * Part: 判别值
+     */
+
+    #[test]
+    fn every_discriminant() {
+        for byte in u8::MIN..=u8::MAX {
+            let ctrl = LegacyCtrl::from(byte);
+            if ctrl != LegacyCtrl::UndefinedVal {
+                assert_eq!(u8::from(ctrl), byte);
+            }
+            let msg_type = LegacyMsgType::from(byte);
+            if msg_type != LegacyMsgType::UndefinedVal {
+                assert_eq!(u8::from(msg_type), byte);
+            }
+            let match_state = Match::from(byte);
+            if match_state != Match::UndefinedVal {
+                assert_eq!(u8::from(match_state), byte);
+            }
+        }
+    }
+
+    #[test]
+    fn every_ctrl_in_packs() {
+        for ctrl in (u8::MIN..=u8::MAX).map(LegacyCtrl::from) {
+            for has_ball in [false, true] {
+                let robot = normalize_robot(LegacyPackFromRobot {
+                    ctrl,
+                    has_ball,
+                    ..Default::default()
+                });
+                assert_eq!(
+                    LegacyPackFromRobot::try_from_bytes(&robot.to_bytes()),
+                    Ok(robot)
+                );
+                let mut coach = LegacyPackFromCoach {
+                    ctrl,
+                    ..Default::default()
+                };
+                coach.players[0] = LegacyPackFromCoachPlayer {
+                    ctrl,
+                    has_ball,
+                    ..Default::default()
+                };
+                let coach = normalize_coach(coach);
+                assert_eq!(
+                    LegacyPackFromCoach::try_from_bytes(&coach.to_bytes()),
+                    Ok(coach)
+                );
+            }
+        }
+    }
+
+    /*
+     * Part: 长度
+     */
+
+    #[test]
+    fn truncated() {
+        let robot_bytes = LegacyPackFromRobot::default().to_bytes();
+        let coach_bytes = LegacyPackFromCoach::default().to_bytes();
+        // 包头与长度不完整
+        assert_eq!(
+            LegacyPackFromRobot::try_from_bytes(&robot_bytes[..1]),
+            Err(LegacyDecodeError::TruncatedField {
+                field: "header",
+                offset: 0
+            })
+        );
+        assert_eq!(
+            LegacyPackFromCoach::try_from_bytes(&coach_bytes[..2]),
+            Err(LegacyDecodeError::TruncatedField {
+                field: "set_len",
+                offset: 2
+            })
+        );
+        // 数据不完整
+        for len in 3..robot_bytes.len() {
+            assert_eq!(
+                LegacyPackFromRobot::try_from_bytes(&robot_bytes[..len]),
+                Err(LegacyDecodeError::WrongLength {
+                    expected: PACK_FROM_ROBOT_BYTE_LENGTH,
+                    actual: len
+                })
+            );
+        }
+        for len in 3..coach_bytes.len() {
+            assert_eq!(
+                LegacyPackFromCoach::try_from_bytes(&coach_bytes[..len]),
+                Err(LegacyDecodeError::WrongLength {
+                    expected: PACK_FROM_COACH_BYTE_LENGTH,
+                    actual: len
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn wrong_set_len() {
+        let mut robot_bytes = LegacyPackFromRobot::default().to_bytes();
+        for set_len in [0, 3, 175, 177, 255] {
+            robot_bytes[2] = set_len;
+            assert_eq!(
+                LegacyPackFromRobot::try_from_bytes(&robot_bytes),
+                Err(LegacyDecodeError::WrongLength {
+                    expected: PACK_FROM_ROBOT_BYTE_LENGTH,
+                    actual: set_len as usize
+                })
+            );
+        }
+        let mut coach_bytes = LegacyPackFromCoach::default().to_bytes();
+        for set_len in [0, 3, 204, 206, 255] {
+            coach_bytes[2] = set_len;
+            assert_eq!(
+                LegacyPackFromCoach::try_from_bytes(&coach_bytes),
+                Err(LegacyDecodeError::WrongLength {
+                    expected: PACK_FROM_COACH_BYTE_LENGTH,
+                    actual: set_len as usize
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn wrong_header() {
+        let mut robot_bytes = LegacyPackFromRobot::default().to_bytes();
+        robot_bytes[0] = 0xAA;
+        assert_eq!(
+            LegacyPackFromRobot::try_from_bytes(&robot_bytes),
+            Err(LegacyDecodeError::WrongHeader { actual: 0xAAAA })
+        );
+    }
+
+    #[test]
+    fn wrong_checksum() {
+        let mut robot_bytes = LegacyPackFromRobot::default().to_bytes();
+        let set = robot_bytes[PACK_FROM_ROBOT_BYTE_LENGTH - 1];
+        robot_bytes[PACK_FROM_ROBOT_BYTE_LENGTH - 1] ^= 1;
+        assert_eq!(
+            LegacyPackFromRobot::try_from_bytes(&robot_bytes),
+            Err(LegacyDecodeError::WrongChecksum {
+                set: set ^ 1,
+                calculated: set
+            })
+        );
+        let mut coach_bytes = LegacyPackFromCoach::default().to_bytes();
+        coach_bytes[PACK_FROM_COACH_BYTE_LENGTH - 1] ^= 1;
+        assert!(matches!(
+            LegacyPackFromCoach::try_from_bytes(&coach_bytes),
+            Err(LegacyDecodeError::WrongChecksum { .. })
+        ));
+    }
+
+    #[test]
+    fn unknown_discriminant() {
+        // 10不是已定义的指令，138为持球的10
+        for ctrl_byte in [10, 10 | BALL_FLAG] {
+            let mut robot_bytes = LegacyPackFromRobot::default().to_bytes();
+            robot_bytes[11] = ctrl_byte;
+            assert_eq!(
+                LegacyPackFromRobot::try_from_bytes(&robot_bytes),
+                Err(LegacyDecodeError::UnknownDiscriminant {
+                    field: "ctrl",
+                    value: ctrl_byte
+                })
+            );
+        }
+        let mut coach_bytes = LegacyPackFromCoach::default().to_bytes();
+        coach_bytes[4] = 0;
+        assert_eq!(
+            LegacyPackFromCoach::try_from_bytes(&coach_bytes),
+            Err(LegacyDecodeError::UnknownDiscriminant {
+                field: "msg_type",
+                value: 0
+            })
+        );
+    }
+
+    /*
+     * Part: 固定字节
+     */
+
+    /// 按偏移量写入字节，其余为0，最后一字节为校验和
+    fn layout_bytes<const N: usize>(fields: &[(usize, &[u8])]) -> [u8; N] {
+        let mut bytes = [0u8; N];
+        for (offset, field) in fields {
+            bytes[*offset..*offset + field.len()].copy_from_slice(field);
+        }
+        bytes[N - 1] = bytes[..N - 1]
+            .iter()
+            .fold(0u8, |sum, val| sum.wrapping_add(*val));
+        bytes
+    }
+
+    /// 按原C++结构体布局逐字段写出的机器人数据包，防止字段顺序或偏移量被意外改动
+    #[test]
+    fn golden_layout_robot() {
+        let pack = LegacyPackFromRobot {
+            id: 3,
+            msg_type: LegacyMsgType::Teammate,
+            pos: I16Vec2::new(-1200, 350),
+            angle: -90,
+            ctrl: LegacyCtrl::Attack,
+            has_ball: true,
+            found_ball: true,
+            found_ball_pos: I16Vec2::new(i16::MIN, i16::MAX),
+            velocity: 300,
+            velocity_angle: 45,
+            pass_kick: true,
+            pass_target_pos: I16Vec2::new(600, -400),
+            barriers: {
+                let mut barriers = [LegacyPackBarrier::default(); 10];
+                barriers[0] = LegacyPackBarrier {
+                    size: 50,
+                    pos: I16Vec2::new(100, 200),
+                };
+                barriers[9] = LegacyPackBarrier {
+                    size: 60,
+                    pos: I16Vec2::new(-100, -200),
+                };
+                barriers
+            },
+            computer_ac: 1,
+            computer_battery_flag: 2,
+            computer_battery_percent: 80,
+            computer_working_second_count: 3600,
+            computer_cpu_percent: 25,
+            computer_cpu_frequency_mhz: 2400.0,
+            soft_version: 0.1,
+            robot_power_volt: 24,
+            robot_charge: true,
+            video_fps: 30,
+            multicast_fps: 33,
+        };
+        let expected: [u8; PACK_FROM_ROBOT_BYTE_LENGTH] = layout_bytes(&[
+            (0, &[0x55, 0xAA, 176, 3, 4]),
+            (5, &(-1200i16).to_be_bytes()),
+            (7, &350i16.to_be_bytes()),
+            (9, &(-90i16).to_be_bytes()),
+            (11, &[1 | BALL_FLAG]),
+            (12, &[0x80, 0x00, 0x7F, 0xFF]),
+            (16, &300u16.to_be_bytes()),
+            (18, &45i16.to_be_bytes()),
+            (20, &[1]),
+            (21, &600i16.to_be_bytes()),
+            (23, &(-400i16).to_be_bytes()),
+            (25, &[50, 0, 100, 0, 200]),
+            (70, &[60]),
+            (71, &(-100i16).to_be_bytes()),
+            (73, &(-200i16).to_be_bytes()),
+            (75, &[1, 2, 80]),
+            (78, &3600u16.to_be_bytes()),
+            (80, &[25]),
+            (81, &2400.0f32.to_be_bytes()),
+            (85, &0.1f32.to_be_bytes()),
+            (89, &[24, 1, 30, 33]),
+        ]);
+        assert_eq!(pack.to_bytes(), expected);
+        assert_eq!(LegacyPackFromRobot::try_from_bytes(&expected), Ok(pack));
+    }
+
+    /// 按原C++结构体布局逐字段写出的教练机数据包，防止字段顺序或偏移量被意外改动
+    #[test]
+    fn golden_layout_coach() {
+        let mut pack = LegacyPackFromCoach {
+            id: 2,
+            msg_type: LegacyMsgType::Cmd,
+            players: [LegacyPackFromCoachPlayer {
+                ctrl: LegacyCtrl::Stop,
+                ..Default::default()
+            }; 5],
+            barriers: [Default::default(); 10],
+            setup_pos: I16Vec2::new(-500, 0),
+            found_ball: true,
+            ball_pos_from_coach: I16Vec2::new(250, -250),
+            ctrl: LegacyCtrl::Defence,
+            def_angle: 90,
+            def_dist: 150,
+            ..Default::default()
+        };
+        pack.players[1] = LegacyPackFromCoachPlayer {
+            ctrl: LegacyCtrl::Catch,
+            has_ball: true,
+            pos: I16Vec2::new(700, -300),
+            angle: 180,
+        };
+        pack.barriers[0] = LegacyPackBarrier {
+            size: 50,
+            pos: I16Vec2::new(100, 200),
+        };
+        let expected: [u8; PACK_FROM_COACH_BYTE_LENGTH] = layout_bytes(&[
+            (0, &[0x55, 0xAA, 205, 2, 2]),
+            // 球员：每个7字节
+            (12, &[5 | BALL_FLAG]),
+            (13, &700i16.to_be_bytes()),
+            (15, &(-300i16).to_be_bytes()),
+            (17, &180i16.to_be_bytes()),
+            // 障碍物：每个5字节
+            (40, &[50, 0, 100, 0, 200]),
+            (90, &(-500i16).to_be_bytes()),
+            (92, &[0, 0, 1]),
+            (95, &250i16.to_be_bytes()),
+            (97, &(-250i16).to_be_bytes()),
+            (99, &[3]),
+            (100, &90i16.to_be_bytes()),
+            (102, &150i16.to_be_bytes()),
+        ]);
+        assert_eq!(pack.to_bytes(), expected);
+        assert_eq!(LegacyPackFromCoach::try_from_bytes(&expected), Ok(pack));
+    }
+
+    /// 从原C++程序抓取的数据包，见`fixtures/legacy/README.md`。
+    /// 文件名以`robot_`或`coach_`开头。C++结构体的填充字节可能不为0，因此比较的是重新编码再解码后的数据，而非原字节。
+    /// `coach_defence_a<角度>_d<距离>.bin`为教练机控制台上输入已知参数后抓取的`Defence`数据包，用于核对两个字段的顺序。
+    /// 必须至少有一个机器人数据包、一个教练机数据包与一个`Defence`数据包，否则测试失败。
+    #[test]
+    #[ignore = "尚未放入从原C++程序抓取的数据包，放入后去掉此标记，见fixtures/legacy/README.md"]
+    fn captured_fixtures() {
+        let fixture_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/legacy");
+        let entries = std::fs::read_dir(&fixture_dir).expect("Failed to read fixture dir!");
+        let (mut robot_count, mut coach_count, mut defence_count) = (0, 0, 0);
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let Some(stem) = file_name.strip_suffix(".bin") else {
+                continue;
+            };
+            let bytes = std::fs::read(&path).expect("Failed to read fixture!");
+            if stem.starts_with("robot_") {
+                assert_eq!(bytes.len(), 176, "{file_name}");
+                let pack = LegacyPackFromRobot::try_from_bytes(&bytes)
+                    .unwrap_or_else(|err| panic!("{file_name}: {err}"));
+                assert_eq!(
+                    LegacyPackFromRobot::try_from_bytes(&pack.to_bytes()),
+                    Ok(pack),
+                    "{file_name}"
+                );
+                robot_count += 1;
+            } else if stem.starts_with("coach_") {
+                assert_eq!(bytes.len(), 205, "{file_name}");
+                let pack = LegacyPackFromCoach::try_from_bytes(&bytes)
+                    .unwrap_or_else(|err| panic!("{file_name}: {err}"));
+                assert_eq!(
+                    LegacyPackFromCoach::try_from_bytes(&pack.to_bytes()),
+                    Ok(pack),
+                    "{file_name}"
+                );
+                coach_count += 1;
+                // 文件名中的参数：coach_defence_a<角度>_d<距离>
+                if let Some(params) = stem.strip_prefix("coach_defence_") {
+                    let (angle, dist) = params
+                        .strip_prefix('a')
+                        .and_then(|params| params.split_once("_d"))
+                        .and_then(|(angle, dist)| Some((angle.parse().ok()?, dist.parse().ok()?)))
+                        .unwrap_or_else(|| panic!("{file_name}: invalid defence params"));
+                    assert_eq!(pack.ctrl, LegacyCtrl::Defence, "{file_name}");
+                    assert_eq!(
+                        (pack.def_angle, pack.def_dist),
+                        (angle, dist),
+                        "{file_name}"
+                    );
+                    defence_count += 1;
+                }
+            }
+        }
+        assert!(robot_count > 0, "No robot pack captured in {fixture_dir:?}");
+        assert!(coach_count > 0, "No coach pack captured in {fixture_dir:?}");
+        assert!(
+            defence_count > 0,
+            "No Defence pack captured in {fixture_dir:?}"
+        );
+    }
 }
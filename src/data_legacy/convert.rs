@@ -0,0 +1,411 @@
+//! 旧版协议与场地坐标系之间的换算
+//!
+//! 旧协议中位置为`I16Vec2`、长度与速度为整数，单位默认为厘米；角度为`i16`，单位默认为度。
+//! 场地坐标系见README：场地中心为零点，敌方球门方向为x轴正方向，单位：米，角度单位：弧度。
+//! 网络部分只应通过此处换算，不要再手动乘除。
+
+use std::f32::consts::PI;
+
+use glam::{I16Vec2, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::field::world_state::{wrap_angle, BallState, Obstacle, ObstacleList, RobotPose};
+
+use super::{LegacyCtrl, LegacyPackBarrier, LegacyPackFromCoach, LegacyPackFromRobot};
+
+/*
+ * Part: 坐标系
+ */
+
+/// 旧协议中角度的单位
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LegacyAngleUnit {
+    /// 度
+    #[default]
+    Degree,
+    /// 毫弧度
+    Milliradian,
+}
+
+/// 旧协议坐标系
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LegacyFrame {
+    /// 每米对应的旧协议长度单位数，默认为100（厘米）
+    pub units_per_m: f32,
+    /// 场地中心在旧协议坐标中的位置，单位：旧协议长度单位。
+    /// 旧协议以场地角落为零点时在此设置。
+    pub origin: Vec2,
+    /// 角度单位
+    pub angle_unit: LegacyAngleUnit,
+    /// 旧协议的x轴指向己方球门（如换边后）时为真，此时位置与角度旋转180°
+    pub mirror: bool,
+}
+
+impl Default for LegacyFrame {
+    fn default() -> Self {
+        Self {
+            units_per_m: 100.0,
+            origin: Vec2::ZERO,
+            angle_unit: LegacyAngleUnit::Degree,
+            mirror: false,
+        }
+    }
+}
+
+/// 旧协议中使用的整数类型
+pub trait LegacyInt: Copy {
+    const MIN_F32: f32;
+    const MAX_F32: f32;
+    /// 四舍五入，超出范围时取最近的边界值，NaN为0
+    fn saturating_from_f32(val: f32) -> Self;
+    fn to_f32(self) -> f32;
+
+    /// 四舍五入，超出范围或不是有限值时为`None`
+    fn checked_from_f32(val: f32) -> Option<Self> {
+        let val = val.round();
+        (val.is_finite() && (Self::MIN_F32..=Self::MAX_F32).contains(&val))
+            .then(|| Self::saturating_from_f32(val))
+    }
+}
+
+macro_rules! impl_legacy_int {
+    ($($int:ty),*) => {
+        $(
+            impl LegacyInt for $int {
+                const MIN_F32: f32 = <$int>::MIN as f32;
+                const MAX_F32: f32 = <$int>::MAX as f32;
+
+                fn saturating_from_f32(val: f32) -> Self {
+                    // 浮点数转整数的`as`本身即为饱和转换
+                    val.round() as $int
+                }
+
+                fn to_f32(self) -> f32 {
+                    self as f32
+                }
+            }
+        )*
+    };
+}
+
+impl_legacy_int!(u8, u16, i16);
+
+impl LegacyFrame {
+    /// 旧协议位置 → 场地位置
+    pub fn to_field_pos(&self, pos: I16Vec2) -> Vec2 {
+        let pos = (pos.as_vec2() - self.origin) / self.units_per_m;
+        if self.mirror {
+            -pos
+        } else {
+            pos
+        }
+    }
+
+    fn legacy_pos_f32(&self, pos: Vec2) -> Vec2 {
+        let pos = if self.mirror { -pos } else { pos };
+        pos * self.units_per_m + self.origin
+    }
+
+    /// 场地位置 → 旧协议位置，超出范围时取边界值
+    pub fn to_legacy_pos(&self, pos: Vec2) -> I16Vec2 {
+        let pos = self.legacy_pos_f32(pos);
+        I16Vec2::new(
+            i16::saturating_from_f32(pos.x),
+            i16::saturating_from_f32(pos.y),
+        )
+    }
+
+    /// 场地位置 → 旧协议位置，超出范围时为`None`
+    pub fn checked_to_legacy_pos(&self, pos: Vec2) -> Option<I16Vec2> {
+        let pos = self.legacy_pos_f32(pos);
+        Some(I16Vec2::new(
+            i16::checked_from_f32(pos.x)?,
+            i16::checked_from_f32(pos.y)?,
+        ))
+    }
+
+    /// 旧协议长度、速度 → 米、米每秒
+    pub fn to_field_len(&self, len: impl LegacyInt) -> f32 {
+        len.to_f32() / self.units_per_m
+    }
+
+    /// 米、米每秒 → 旧协议长度、速度，超出范围时取边界值
+    pub fn to_legacy_len<T: LegacyInt>(&self, len: f32) -> T {
+        T::saturating_from_f32(len * self.units_per_m)
+    }
+
+    /// 米、米每秒 → 旧协议长度、速度，超出范围时为`None`
+    pub fn checked_to_legacy_len<T: LegacyInt>(&self, len: f32) -> Option<T> {
+        T::checked_from_f32(len * self.units_per_m)
+    }
+
+    /// 旧协议角度 → 场地角度，范围(-π, π]
+    pub fn to_field_angle(&self, angle: i16) -> f32 {
+        let angle = match self.angle_unit {
+            LegacyAngleUnit::Degree => f32::from(angle).to_radians(),
+            LegacyAngleUnit::Milliradian => f32::from(angle) / 1000.0,
+        };
+        wrap_angle(if self.mirror { angle + PI } else { angle })
+    }
+
+    fn legacy_angle_f32(&self, angle: f32) -> f32 {
+        let angle = wrap_angle(if self.mirror { angle + PI } else { angle });
+        match self.angle_unit {
+            LegacyAngleUnit::Degree => angle.to_degrees(),
+            LegacyAngleUnit::Milliradian => angle * 1000.0,
+        }
+    }
+
+    /// 场地角度 → 旧协议角度。角度先限制在(-π, π]内，因此只有NaN会被饱和为0
+    pub fn to_legacy_angle(&self, angle: f32) -> i16 {
+        i16::saturating_from_f32(self.legacy_angle_f32(angle))
+    }
+
+    /// 场地角度 → 旧协议角度，不是有限值时为`None`
+    pub fn checked_to_legacy_angle(&self, angle: f32) -> Option<i16> {
+        i16::checked_from_f32(self.legacy_angle_f32(angle))
+    }
+}
+
+/*
+ * Part: 场上状态
+ */
+
+impl RobotPose {
+    pub fn from_legacy(frame: &LegacyFrame, pos: I16Vec2, angle: i16) -> Self {
+        Self {
+            pos: frame.to_field_pos(pos),
+            angle: frame.to_field_angle(angle),
+        }
+    }
+
+    /// 返回旧协议中的位置与角度，超出范围时取边界值
+    pub fn to_legacy(&self, frame: &LegacyFrame) -> (I16Vec2, i16) {
+        (
+            frame.to_legacy_pos(self.pos),
+            frame.to_legacy_angle(self.angle),
+        )
+    }
+}
+
+impl BallState {
+    pub fn from_legacy(frame: &LegacyFrame, pos: I16Vec2) -> Self {
+        Self {
+            pos: frame.to_field_pos(pos),
+        }
+    }
+
+    /// 返回旧协议中的位置，超出范围时取边界值
+    pub fn to_legacy(&self, frame: &LegacyFrame) -> I16Vec2 {
+        frame.to_legacy_pos(self.pos)
+    }
+}
+
+impl ObstacleList {
+    /// 大小为0的障碍物为空位，跳过
+    pub fn from_legacy(frame: &LegacyFrame, barriers: &[LegacyPackBarrier]) -> Self {
+        Self {
+            obstacles: barriers
+                .iter()
+                .filter(|barrier| barrier.size > 0)
+                .map(|barrier| Obstacle {
+                    pos: frame.to_field_pos(barrier.pos),
+                    size: frame.to_field_len(barrier.size),
+                })
+                .collect(),
+        }
+    }
+
+    /// 最多10个，超出旧协议范围的障碍物被跳过，而不是挤到边界上；大小至少为1，以免被当作空位
+    pub fn to_legacy(&self, frame: &LegacyFrame) -> [LegacyPackBarrier; 10] {
+        let mut barriers = [LegacyPackBarrier::default(); 10];
+        let legacy_obstacles = self.obstacles.iter().filter_map(|obstacle| {
+            Some(LegacyPackBarrier {
+                size: frame.to_legacy_len::<u8>(obstacle.size).max(1),
+                pos: frame.checked_to_legacy_pos(obstacle.pos)?,
+            })
+        });
+        barriers
+            .iter_mut()
+            .zip(legacy_obstacles)
+            .for_each(|(barrier, legacy_obstacle)| *barrier = legacy_obstacle);
+        barriers
+    }
+}
+
+/*
+ * Part: 数据包
+ */
+
+impl LegacyPackFromRobot {
+    /// 机器人位姿
+    pub fn pose(&self, frame: &LegacyFrame) -> RobotPose {
+        RobotPose::from_legacy(frame, self.pos, self.angle)
+    }
+
+    /// 机器人看到的球
+    pub fn ball(&self, frame: &LegacyFrame) -> Option<BallState> {
+        self.found_ball
+            .then(|| BallState::from_legacy(frame, self.found_ball_pos))
+    }
+
+    /// 机器人看到的障碍物
+    pub fn obstacles(&self, frame: &LegacyFrame) -> ObstacleList {
+        ObstacleList::from_legacy(frame, &self.barriers)
+    }
+}
+
+impl LegacyPackFromCoach {
+    /// 在线球员的编号（1~5）与位姿
+    pub fn player_poses<'a>(
+        &'a self,
+        frame: &'a LegacyFrame,
+    ) -> impl Iterator<Item = (u8, RobotPose)> + 'a {
+        (1u8..)
+            .zip(self.players.iter())
+            .filter(|(_, player)| player.ctrl != LegacyCtrl::Offline)
+            .map(|(id, player)| (id, RobotPose::from_legacy(frame, player.pos, player.angle)))
+    }
+
+    /// 教练机合并得到的球
+    pub fn ball(&self, frame: &LegacyFrame) -> Option<BallState> {
+        self.found_ball
+            .then(|| BallState::from_legacy(frame, self.ball_pos_from_coach))
+    }
+
+    /// 教练机合并得到的障碍物
+    pub fn obstacles(&self, frame: &LegacyFrame) -> ObstacleList {
+        ObstacleList::from_legacy(frame, &self.barriers)
+    }
+
+    /// `MoveTo`指令的目标位姿
+    pub fn target_pose(&self, frame: &LegacyFrame) -> Option<RobotPose> {
+        (self.ctrl == LegacyCtrl::MoveTo)
+            .then(|| RobotPose::from_legacy(frame, self.target_pos, self.target_angle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn round_convert_pos() {
+        let frame = LegacyFrame {
+            origin: Vec2::new(900.0, 600.0),
+            ..Default::default()
+        };
+        let pose = RobotPose {
+            pos: Vec2::new(-1.23, 4.5),
+            angle: -PI / 2.0,
+        };
+        let (pos, angle) = pose.to_legacy(&frame);
+        assert_eq!(pos, I16Vec2::new(777, 1050));
+        assert_eq!(angle, -90);
+        let new_pose = RobotPose::from_legacy(&frame, pos, angle);
+        assert_relative_eq!(new_pose.pos, pose.pos, epsilon = 1e-5);
+        assert_relative_eq!(new_pose.angle, pose.angle, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn mirror() {
+        let frame = LegacyFrame {
+            mirror: true,
+            ..Default::default()
+        };
+        let pose = RobotPose {
+            pos: Vec2::new(3.0, -1.0),
+            angle: PI / 4.0,
+        };
+        let (pos, angle) = pose.to_legacy(&frame);
+        assert_eq!(pos, I16Vec2::new(-300, 100));
+        assert_eq!(angle, -135);
+        let new_pose = RobotPose::from_legacy(&frame, pos, angle);
+        assert_relative_eq!(new_pose.pos, pose.pos, epsilon = 1e-5);
+        assert_relative_eq!(new_pose.angle, pose.angle, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn angle_unit_and_wrap() {
+        let frame = LegacyFrame::default();
+        assert_eq!(frame.to_legacy_angle(PI), 180);
+        assert_eq!(frame.to_legacy_angle(-PI), 180);
+        assert_eq!(frame.to_legacy_angle(3.0 * PI / 2.0), -90);
+        assert_relative_eq!(frame.to_field_angle(270), -PI / 2.0, epsilon = 1e-5);
+        let frame = LegacyFrame {
+            angle_unit: LegacyAngleUnit::Milliradian,
+            ..Default::default()
+        };
+        assert_eq!(frame.to_legacy_angle(1.0), 1000);
+        assert_relative_eq!(frame.to_field_angle(-1571), -1.571, epsilon = 1e-5);
+        assert_eq!(frame.checked_to_legacy_angle(f32::NAN), None);
+    }
+
+    #[test]
+    fn robot_ball_from_bytes() {
+        let frame = LegacyFrame::default();
+        let pack = LegacyPackFromRobot {
+            found_ball: false,
+            ..Default::default()
+        };
+        let decoded = LegacyPackFromRobot::try_from_bytes(&pack.to_bytes())
+            .expect("Failed to read data from bytes!");
+        assert_eq!(decoded.ball(&frame), None);
+        let pack = LegacyPackFromRobot {
+            found_ball: true,
+            found_ball_pos: I16Vec2::new(150, -50),
+            ..Default::default()
+        };
+        let decoded = LegacyPackFromRobot::try_from_bytes(&pack.to_bytes())
+            .expect("Failed to read data from bytes!");
+        assert_eq!(
+            decoded.ball(&frame),
+            Some(BallState {
+                pos: Vec2::new(1.5, -0.5),
+            })
+        );
+    }
+
+    #[test]
+    fn saturation() {
+        let frame = LegacyFrame::default();
+        assert_eq!(
+            frame.to_legacy_pos(Vec2::new(1000.0, -1000.0)),
+            I16Vec2::new(i16::MAX, i16::MIN)
+        );
+        assert_eq!(frame.checked_to_legacy_pos(Vec2::new(1000.0, 0.0)), None);
+        assert_eq!(frame.to_legacy_len::<u8>(3.0), u8::MAX);
+        assert_eq!(frame.to_legacy_len::<u16>(-1.0), 0);
+        assert_eq!(frame.checked_to_legacy_len::<u8>(3.0), None);
+        assert_eq!(frame.to_legacy_len::<u16>(f32::NAN), 0);
+        // 超出范围的障碍物被跳过
+        let obstacles = ObstacleList {
+            obstacles: vec![
+                Obstacle {
+                    pos: Vec2::new(500.0, 0.0),
+                    size: 0.5,
+                },
+                Obstacle {
+                    pos: Vec2::new(1.0, 2.0),
+                    size: 0.001,
+                },
+            ],
+        };
+        let barriers = obstacles.to_legacy(&frame);
+        assert_eq!(
+            barriers[0],
+            LegacyPackBarrier {
+                size: 1,
+                pos: I16Vec2::new(100, 200),
+            }
+        );
+        assert_eq!(barriers[1], LegacyPackBarrier::default());
+        assert_eq!(
+            ObstacleList::from_legacy(&frame, &barriers).obstacles.len(),
+            1
+        );
+    }
+}
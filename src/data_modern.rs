@@ -0,0 +1,592 @@
+//! 新版协议：教练机与机器人之间的数据包
+//!
+//! 数据包以`MODERN_MAGIC`开头，之后为bincode编码的`ModernPacket`。
+//! 旧版数据包以`0x55 0xAA`开头，两者可以在同一端口上共存，便于逐台机器人迁移。
+//!
+//! 协商：机器人发送`Hello`，教练机回复`HelloAck`，机器人按协商结果发送。
+//! 教练机以机器人最近一次发来的数据包的格式回复，因此可以逐台机器人切换到新版协议。
+//! 机器人长时间只收到旧版数据包时（如教练机换回了旧程序），回退到旧版协议并重新协商。
+
+use bincode::Options;
+use glam::{I16Vec2, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data_legacy::{
+        convert::LegacyFrame, LegacyCtrl, LegacyMsgType, LegacyPackFromCoach,
+        LegacyPackFromCoachPlayer, LegacyPackFromRobot, LEGACY_NO_BALL_POS,
+    },
+    error::ModernDecodeError,
+    field::world_state::{BallState, ObstacleList, RobotPose},
+};
+
+/*
+ * Part: 版本
+ */
+
+/// 包头标记，与旧版包头`0x55 0xAA`不同
+pub const MODERN_MAGIC: [u8; 2] = *b"BX";
+/// 当前协议版本
+pub const MODERN_PROTOCOL_VERSION: u16 = 1;
+/// 仍支持的最低协议版本
+pub const MODERN_MIN_PROTOCOL_VERSION: u16 = 1;
+/// 数据包最大长度，防止恶意数据导致分配过多内存
+pub const MODERN_MAX_PACKET_LENGTH: u64 = 64 * 1024;
+
+/// 协商协议版本：取双方都支持的最高版本，没有时为`None`
+pub fn negotiate_version(peer_min_version: u16, peer_max_version: u16) -> Option<u16> {
+    let version = peer_max_version.min(MODERN_PROTOCOL_VERSION);
+    (version >= peer_min_version.max(MODERN_MIN_PROTOCOL_VERSION)).then_some(version)
+}
+
+/// 协议选择，写在配置文件中
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProtocolMode {
+    /// 只使用旧版协议
+    #[default]
+    Legacy,
+    /// 先使用旧版协议，同时发起协商，对方支持时切换到新版协议
+    Auto,
+    /// 只使用新版协议，协商成功之前不发送状态
+    Modern,
+}
+
+/// 对方当前使用的协议
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PeerProtocol {
+    #[default]
+    Legacy,
+    Modern {
+        version: u16,
+    },
+}
+
+/*
+ * Part: 数据包
+ */
+
+/// 包头
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModernHeader {
+    /// 发送方使用的协议版本
+    pub version: u16,
+    /// 机器人编号，教练机发出的数据包中为接收方的编号
+    pub robot_id: u8,
+    /// 序号，每发送一个数据包加1，用于丢弃乱序的数据包
+    pub seq: u32,
+    /// 发送时间，Unix时间戳，单位：毫秒
+    pub send_time_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModernPacket {
+    pub header: ModernHeader,
+    pub body: ModernBody,
+}
+
+/// 数据包内容。`Hello`与`HelloAck`的格式在各版本之间保持不变
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ModernBody {
+    /// 协商：发送方支持的版本范围
+    Hello { min_version: u16, max_version: u16 },
+    /// 协商结果，`None`表示没有共同支持的版本，应使用旧版协议
+    HelloAck { version: Option<u16> },
+    /// 机器人 → 教练机
+    RobotState(ModernRobotState),
+    /// 教练机 → 机器人
+    CoachCommand(ModernCoachCommand),
+}
+
+/// 指令及其参数
+/// 坐标系：场地中心为零点，敌方球门方向为x轴正方向，正前方朝着敌方球门时，左侧为y轴正方向，单位：米
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ModernIntent {
+    /// 没有额外参数的指令
+    Ctrl(LegacyCtrl),
+    MoveTo {
+        target: RobotPose,
+        /// 速度档位，与旧协议相同
+        speed: u8,
+    },
+    Defence {
+        /// 单位：弧度
+        angle: f32,
+        /// 单位：米
+        dist: f32,
+    },
+    Pass {
+        target_pos: Vec2,
+    },
+    Catch {
+        from_pos: Vec2,
+    },
+}
+
+impl Default for ModernIntent {
+    fn default() -> Self {
+        Self::Ctrl(LegacyCtrl::Stop)
+    }
+}
+
+/// 机器人状态
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModernRobotState {
+    pub pose: RobotPose,
+    /// 单位：米每秒
+    pub velocity: Vec2,
+    pub has_ball: bool,
+    /// 看到的球
+    pub ball: Option<BallState>,
+    /// 看到的障碍物，数量不限
+    pub obstacles: ObstacleList,
+    /// 正在执行的指令
+    pub intent: ModernIntent,
+}
+
+/// 教练机眼中的队友
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModernPlayer {
+    pub robot_id: u8,
+    pub pose: RobotPose,
+    pub has_ball: bool,
+    pub intent: ModernIntent,
+}
+
+/// 教练机指令
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModernCoachCommand {
+    /// 在线的队友，数量不限
+    pub players: Vec<ModernPlayer>,
+    pub ball: Option<BallState>,
+    pub obstacles: ObstacleList,
+    pub setup_pos: Vec2,
+    /// 给接收方的指令
+    pub intent: ModernIntent,
+}
+
+fn bincode_options() -> impl Options {
+    bincode::DefaultOptions::new().with_limit(MODERN_MAX_PACKET_LENGTH)
+}
+
+impl ModernPacket {
+    /// 是否为新版数据包
+    pub fn is_modern(bytes: &[u8]) -> bool {
+        bytes.starts_with(&MODERN_MAGIC)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MODERN_MAGIC.to_vec();
+        bincode_options()
+            .serialize_into(&mut bytes, self)
+            .expect("Failed to serialize modern packet!");
+        bytes
+    }
+
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, ModernDecodeError> {
+        let Some(payload) = bytes.strip_prefix(&MODERN_MAGIC) else {
+            return Err(ModernDecodeError::WrongMagic);
+        };
+        let packet: Self = bincode_options()
+            .deserialize(payload)
+            .map_err(|err| ModernDecodeError::Malformed(err.to_string()))?;
+        // 协商用的数据包不检查版本
+        let version = packet.header.version;
+        let is_handshake = matches!(
+            packet.body,
+            ModernBody::Hello { .. } | ModernBody::HelloAck { .. }
+        );
+        if !is_handshake
+            && !(MODERN_MIN_PROTOCOL_VERSION..=MODERN_PROTOCOL_VERSION).contains(&version)
+        {
+            return Err(ModernDecodeError::UnsupportedVersion(version));
+        }
+        Ok(packet)
+    }
+}
+
+/// 判断序号是否比上一个更新，考虑回绕
+pub fn is_newer_seq(seq: u32, last_seq: Option<u32>) -> bool {
+    match last_seq {
+        Some(last_seq) => (seq.wrapping_sub(last_seq) as i32) > 0,
+        None => true,
+    }
+}
+
+/// 序号倒退超过此值时，视为对方已重启
+pub const SEQ_RESTART_GAP: u32 = 1000;
+
+/// 判断数据包是否应当处理：序号比上一个更新，或对方已重启。
+/// 对方重启后序号从头开始，此时序号倒退超过`SEQ_RESTART_GAP`，或序号倒退但发送时间更晚，视为新的会话；
+/// 其余序号倒退的数据包为乱序到达的旧数据包
+pub fn is_newer_packet(header: &ModernHeader, last_header: Option<&ModernHeader>) -> bool {
+    let Some(last_header) = last_header else {
+        return true;
+    };
+    is_newer_seq(header.seq, Some(last_header.seq))
+        || last_header.seq.wrapping_sub(header.seq) > SEQ_RESTART_GAP
+        || header.send_time_ms > last_header.send_time_ms
+}
+
+/*
+ * Part: 旧版兼容
+ */
+
+impl ModernIntent {
+    /// 对应的旧版指令
+    pub fn ctrl(&self) -> LegacyCtrl {
+        match self {
+            ModernIntent::Ctrl(ctrl) => *ctrl,
+            ModernIntent::MoveTo { .. } => LegacyCtrl::MoveTo,
+            ModernIntent::Defence { .. } => LegacyCtrl::Defence,
+            ModernIntent::Pass { .. } => LegacyCtrl::Pass,
+            ModernIntent::Catch { .. } => LegacyCtrl::Catch,
+        }
+    }
+
+    /// 从旧版教练机数据包中读取指令及其参数
+    pub fn from_legacy(pack: &LegacyPackFromCoach, frame: &LegacyFrame) -> Self {
+        match pack.ctrl {
+            LegacyCtrl::MoveTo => ModernIntent::MoveTo {
+                target: RobotPose::from_legacy(frame, pack.target_pos, pack.target_angle),
+                speed: pack.speed,
+            },
+            LegacyCtrl::Defence => ModernIntent::Defence {
+                angle: frame.to_field_angle(pack.def_angle),
+                dist: frame.to_field_len(pack.def_dist),
+            },
+            LegacyCtrl::Pass => ModernIntent::Pass {
+                target_pos: frame.to_field_pos(pack.pass_target_pos),
+            },
+            LegacyCtrl::Catch => ModernIntent::Catch {
+                from_pos: frame.to_field_pos(pack.catch_from_pos),
+            },
+            ctrl => ModernIntent::Ctrl(ctrl),
+        }
+    }
+
+    /// 将指令及其参数写入旧版教练机数据包
+    pub fn write_to_legacy(&self, pack: &mut LegacyPackFromCoach, frame: &LegacyFrame) {
+        pack.ctrl = self.ctrl();
+        match *self {
+            ModernIntent::Ctrl(_) => {}
+            ModernIntent::MoveTo { target, speed } => {
+                (pack.target_pos, pack.target_angle) = target.to_legacy(frame);
+                pack.speed = speed;
+            }
+            ModernIntent::Defence { angle, dist } => {
+                pack.def_angle = frame.to_legacy_angle(angle);
+                pack.def_dist = frame.to_legacy_len(dist);
+            }
+            ModernIntent::Pass { target_pos } => {
+                pack.pass_target_pos = frame.to_legacy_pos(target_pos);
+            }
+            ModernIntent::Catch { from_pos } => {
+                pack.catch_from_pos = frame.to_legacy_pos(from_pos);
+            }
+        }
+    }
+}
+
+impl ModernRobotState {
+    /// 转为旧版数据包，便于与旧版机器人的数据一起处理。障碍物最多保留10个。
+    /// 新版协议中没有的字段（传球、电脑与电源状态等）为0或`false`
+    pub fn to_legacy_pack(&self, robot_id: u8, frame: &LegacyFrame) -> LegacyPackFromRobot {
+        let (pos, angle) = self.pose.to_legacy(frame);
+        LegacyPackFromRobot {
+            id: robot_id,
+            msg_type: LegacyMsgType::Teammate,
+            pos,
+            angle,
+            ctrl: self.intent.ctrl(),
+            has_ball: self.has_ball,
+            found_ball: self.ball.is_some(),
+            found_ball_pos: self
+                .ball
+                .map_or(LEGACY_NO_BALL_POS, |ball| ball.to_legacy(frame)),
+            velocity: frame.to_legacy_len(self.velocity.length()),
+            velocity_angle: self
+                .velocity
+                .try_normalize()
+                .map_or(0, |direction| frame.to_legacy_angle(direction.to_angle())),
+            pass_kick: false,
+            pass_target_pos: I16Vec2::ZERO,
+            barriers: self.obstacles.to_legacy(frame),
+            computer_ac: 0,
+            computer_battery_flag: 0,
+            computer_battery_percent: 0,
+            computer_working_second_count: 0,
+            computer_cpu_percent: 0,
+            computer_cpu_frequency_mhz: 0.0,
+            soft_version: 0.0,
+            robot_power_volt: 0,
+            robot_charge: false,
+            video_fps: 0,
+            multicast_fps: 0,
+        }
+    }
+}
+
+impl ModernCoachCommand {
+    /// 由旧版数据包生成，用于教练机向新版机器人发送合并后的信息
+    pub fn from_legacy_pack(pack: &LegacyPackFromCoach, frame: &LegacyFrame) -> Self {
+        Self {
+            players: (1u8..)
+                .zip(pack.players.iter())
+                .filter(|(_, player)| player.ctrl != LegacyCtrl::Offline)
+                .map(|(robot_id, player)| ModernPlayer {
+                    robot_id,
+                    pose: RobotPose::from_legacy(frame, player.pos, player.angle),
+                    has_ball: player.has_ball,
+                    intent: ModernIntent::Ctrl(player.ctrl),
+                })
+                .collect(),
+            ball: pack.ball(frame),
+            obstacles: pack.obstacles(frame),
+            setup_pos: frame.to_field_pos(pack.setup_pos),
+            intent: ModernIntent::from_legacy(pack, frame),
+        }
+    }
+
+    /// 转为发给`robot_id`的旧版数据包，便于按旧版指令执行。队友最多5个，障碍物最多10个。
+    /// 只填写指令用到的参数，其余参数为0
+    pub fn to_legacy_pack(&self, robot_id: u8, frame: &LegacyFrame) -> LegacyPackFromCoach {
+        let mut pack = LegacyPackFromCoach {
+            id: robot_id,
+            msg_type: LegacyMsgType::Cmd,
+            players: [LegacyPackFromCoachPlayer {
+                ctrl: LegacyCtrl::Offline,
+                has_ball: false,
+                pos: I16Vec2::ZERO,
+                angle: 0,
+            }; 5],
+            barriers: self.obstacles.to_legacy(frame),
+            setup_pos: frame.to_legacy_pos(self.setup_pos),
+            found_ball: self.ball.is_some(),
+            ball_pos_from_coach: self
+                .ball
+                .map(|ball| ball.to_legacy(frame))
+                .unwrap_or_default(),
+            ctrl: LegacyCtrl::Stop,
+            target_pos: I16Vec2::ZERO,
+            target_angle: 0,
+            speed: 0,
+            def_angle: 0,
+            def_dist: 0,
+            pass_target_pos: I16Vec2::ZERO,
+            catch_from_pos: I16Vec2::ZERO,
+        };
+        for player in &self.players {
+            let Some(legacy_player) = (player.robot_id as usize)
+                .checked_sub(1)
+                .and_then(|index| pack.players.get_mut(index))
+            else {
+                continue;
+            };
+            let (pos, angle) = player.pose.to_legacy(frame);
+            *legacy_player = LegacyPackFromCoachPlayer {
+                ctrl: player.intent.ctrl(),
+                has_ball: player.has_ball,
+                pos,
+                angle,
+            };
+        }
+        self.intent.write_to_legacy(&mut pack, frame);
+        pack
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::field::world_state::Obstacle;
+
+    use super::*;
+
+    fn header() -> ModernHeader {
+        ModernHeader {
+            version: MODERN_PROTOCOL_VERSION,
+            robot_id: 2,
+            seq: 7,
+            send_time_ms: 1_700_000_000_000,
+        }
+    }
+
+    #[test]
+    fn round_convert_modern() {
+        let packet = ModernPacket {
+            header: header(),
+            body: ModernBody::RobotState(ModernRobotState {
+                pose: RobotPose {
+                    pos: Vec2::new(1.0, -2.0),
+                    angle: 0.5,
+                },
+                velocity: Vec2::new(0.3, 0.4),
+                has_ball: true,
+                ball: Some(BallState {
+                    pos: Vec2::new(1.2, -2.0),
+                }),
+                // 超过旧协议的10个
+                obstacles: ObstacleList {
+                    obstacles: (0..15)
+                        .map(|index| Obstacle {
+                            pos: Vec2::new(index as f32, 0.0),
+                            size: 0.5,
+                        })
+                        .collect(),
+                },
+                intent: ModernIntent::Pass {
+                    target_pos: Vec2::new(3.0, 1.0),
+                },
+            }),
+        };
+        let bytes = packet.to_bytes();
+        assert!(ModernPacket::is_modern(&bytes));
+        assert_eq!(ModernPacket::try_from_bytes(&bytes), Ok(packet));
+    }
+
+    #[test]
+    fn distinguish_legacy() {
+        let legacy_bytes = LegacyPackFromRobot::default().to_bytes();
+        assert!(!ModernPacket::is_modern(&legacy_bytes));
+        assert_eq!(
+            ModernPacket::try_from_bytes(&legacy_bytes),
+            Err(ModernDecodeError::WrongMagic)
+        );
+        assert!(matches!(
+            ModernPacket::try_from_bytes(&MODERN_MAGIC),
+            Err(ModernDecodeError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn version() {
+        let mut packet = ModernPacket {
+            header: ModernHeader {
+                version: MODERN_PROTOCOL_VERSION + 1,
+                ..header()
+            },
+            body: ModernBody::CoachCommand(Default::default()),
+        };
+        assert_eq!(
+            ModernPacket::try_from_bytes(&packet.to_bytes()),
+            Err(ModernDecodeError::UnsupportedVersion(
+                MODERN_PROTOCOL_VERSION + 1
+            ))
+        );
+        // 协商用的数据包不检查版本
+        packet.body = ModernBody::Hello {
+            min_version: 1,
+            max_version: MODERN_PROTOCOL_VERSION + 1,
+        };
+        assert_eq!(ModernPacket::try_from_bytes(&packet.to_bytes()), Ok(packet));
+        // 协商
+        assert_eq!(
+            negotiate_version(1, MODERN_PROTOCOL_VERSION + 1),
+            Some(MODERN_PROTOCOL_VERSION)
+        );
+        assert_eq!(
+            negotiate_version(MODERN_PROTOCOL_VERSION + 1, MODERN_PROTOCOL_VERSION + 2),
+            None
+        );
+    }
+
+    #[test]
+    fn seq() {
+        assert!(is_newer_seq(0, None));
+        assert!(is_newer_seq(8, Some(7)));
+        assert!(!is_newer_seq(7, Some(7)));
+        assert!(!is_newer_seq(6, Some(7)));
+        assert!(is_newer_seq(1, Some(u32::MAX)));
+    }
+
+    #[test]
+    fn peer_restart() {
+        let header = |seq, send_time_ms| ModernHeader {
+            version: MODERN_PROTOCOL_VERSION,
+            robot_id: 1,
+            seq,
+            send_time_ms,
+        };
+        let last = header(5000, 1_000_000);
+        assert!(is_newer_packet(&header(5001, 1_000_030), Some(&last)));
+        // 乱序到达的旧数据包
+        assert!(!is_newer_packet(&header(4999, 999_970), Some(&last)));
+        assert!(!is_newer_packet(&header(5000, 1_000_000), Some(&last)));
+        // 对方重启：序号从头开始
+        assert!(is_newer_packet(&header(1, 1_002_000), Some(&last)));
+        // 对方重启后很快又重启，序号倒退不多，但发送时间更晚
+        let last = header(20, 1_002_600);
+        assert!(is_newer_packet(&header(1, 1_003_000), Some(&last)));
+        // 对方时钟回拨，但序号大幅倒退
+        let last = header(5000, 1_000_000);
+        assert!(is_newer_packet(&header(1, 0), Some(&last)));
+        // 重启后的第一个数据包被接受，此后按新的序号判断
+        let last = header(1, 1_002_000);
+        assert!(is_newer_packet(&header(2, 1_002_030), Some(&last)));
+        assert!(!is_newer_packet(&header(1, 1_002_000), Some(&last)));
+    }
+
+    #[test]
+    fn legacy_fallback() {
+        let frame = LegacyFrame::default();
+        let mut legacy_pack = LegacyPackFromCoach {
+            id: 3,
+            msg_type: LegacyMsgType::Cmd,
+            players: [LegacyPackFromCoachPlayer {
+                ctrl: LegacyCtrl::Offline,
+                ..Default::default()
+            }; 5],
+            barriers: Default::default(),
+            setup_pos: I16Vec2::new(-300, 100),
+            found_ball: true,
+            ball_pos_from_coach: I16Vec2::new(50, -50),
+            ctrl: LegacyCtrl::MoveTo,
+            target_pos: I16Vec2::new(200, 100),
+            target_angle: 90,
+            speed: 5,
+            // MoveTo以外的参数为0
+            def_angle: 0,
+            def_dist: 0,
+            pass_target_pos: I16Vec2::ZERO,
+            catch_from_pos: I16Vec2::ZERO,
+        };
+        legacy_pack.players[2] = LegacyPackFromCoachPlayer {
+            ctrl: LegacyCtrl::Attack,
+            has_ball: true,
+            pos: I16Vec2::new(100, 100),
+            angle: 45,
+        };
+        let command = ModernCoachCommand::from_legacy_pack(&legacy_pack, &frame);
+        assert_eq!(command.players.len(), 1);
+        assert_eq!(command.players[0].robot_id, 3);
+        assert_eq!(command.intent.ctrl(), LegacyCtrl::MoveTo);
+        assert_eq!(command.to_legacy_pack(3, &frame), legacy_pack);
+        // 没有参数的指令：参数都为0
+        let command = ModernCoachCommand {
+            intent: ModernIntent::Ctrl(LegacyCtrl::Attack),
+            ..command
+        };
+        let pack = command.to_legacy_pack(3, &frame);
+        assert_eq!(
+            (pack.target_pos, pack.speed, pack.def_angle, pack.def_dist),
+            (I16Vec2::ZERO, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn robot_state_to_legacy() {
+        let frame = LegacyFrame::default();
+        let pack = ModernRobotState::default().to_legacy_pack(4, &frame);
+        assert_eq!(pack.id, 4);
+        assert!(!pack.found_ball);
+        assert_eq!(pack.found_ball_pos, LEGACY_NO_BALL_POS);
+        assert_eq!((pack.velocity, pack.velocity_angle), (0, 0));
+        // 新版协议中没有的字段不使用旧版数据包的默认值
+        assert!(!pack.pass_kick);
+        assert_eq!(pack.pass_target_pos, I16Vec2::ZERO);
+        assert!(!pack.robot_charge);
+        assert_eq!(pack.computer_cpu_frequency_mhz, 0.0);
+        assert_eq!(pack.soft_version, 0.0);
+        assert_eq!((pack.video_fps, pack.multicast_fps), (0, 0));
+    }
+}
@@ -1,9 +1,21 @@
+use std::path::PathBuf;
+
+use crate::app_dir::AppDirSource;
+
 /// 将程序所有的错误都放在这。
 #[derive(Debug, Clone)]
 pub enum BigHeroXError {
     DialogClosed,
     IoError(std::io::ErrorKind),
     OpenCVError(OpenCVError),
+    /// 指定的程序目录不存在
+    AppDirNotFound(AppDirSource, PathBuf),
+    /// 旧版协议数据包解码失败
+    LegacyDecode(LegacyDecodeError),
+    /// 新版协议数据包解码失败
+    ModernDecode(ModernDecodeError),
+    /// 消息通道错误
+    Channel(ChannelError),
 }
 
 impl From<std::io::Error> for BigHeroXError {
@@ -24,6 +36,24 @@ impl From<OpenCVError> for BigHeroXError {
     }
 }
 
+impl From<LegacyDecodeError> for BigHeroXError {
+    fn from(value: LegacyDecodeError) -> Self {
+        Self::LegacyDecode(value)
+    }
+}
+
+impl From<ModernDecodeError> for BigHeroXError {
+    fn from(value: ModernDecodeError) -> Self {
+        Self::ModernDecode(value)
+    }
+}
+
+impl From<ChannelError> for BigHeroXError {
+    fn from(value: ChannelError) -> Self {
+        Self::Channel(value)
+    }
+}
+
 /// OpenCV Error Wrapping. (因为opencv::Error目前不是Debug/Clone的)
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct OpenCVError {
@@ -31,6 +61,108 @@ pub struct OpenCVError {
     pub message: String,
 }
 
+/// 旧版协议数据包解码错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegacyDecodeError {
+    /// 包头不是`0x55 0xAA`
+    WrongHeader { actual: u16 },
+    /// 长度错误：数据包中的长度字段，或收到的字节数与协议规定不符
+    WrongLength { expected: usize, actual: usize },
+    /// 未定义的枚举值
+    UnknownDiscriminant { field: &'static str, value: u8 },
+    /// 字段不完整，`offset`为该字段的起始位置
+    TruncatedField { field: &'static str, offset: usize },
+    /// 校验和错误
+    WrongChecksum { set: u8, calculated: u8 },
+}
+
+impl std::fmt::Display for LegacyDecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LegacyDecodeError::WrongHeader { actual } => {
+                write!(f, "Wrong header: {actual:#06X}")
+            }
+            LegacyDecodeError::WrongLength { expected, actual } => {
+                write!(f, "Wrong length: expected {expected}, actual {actual}")
+            }
+            LegacyDecodeError::UnknownDiscriminant { field, value } => {
+                write!(f, "Unknown value of {field}: {value}")
+            }
+            LegacyDecodeError::TruncatedField { field, offset } => {
+                write!(f, "Truncated field {field} at offset {offset}")
+            }
+            LegacyDecodeError::WrongChecksum { set, calculated } => {
+                write!(f, "Check failed: Set: {set} Actual: {calculated}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LegacyDecodeError {}
+
+/// 新版协议数据包解码错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModernDecodeError {
+    /// 不是新版数据包
+    WrongMagic,
+    /// 不支持的协议版本
+    UnsupportedVersion(u16),
+    /// 数据格式错误
+    Malformed(String),
+}
+
+impl std::fmt::Display for ModernDecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModernDecodeError::WrongMagic => f.write_str("Not a modern packet"),
+            ModernDecodeError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported protocol version: {version}")
+            }
+            ModernDecodeError::Malformed(message) => write!(f, "Malformed packet: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ModernDecodeError {}
+
+/// 消息通道错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelError {
+    /// 读写失败，连接已断开
+    Io(std::io::ErrorKind),
+    /// 帧长度超过`CHANNEL_MAX_FRAME_LENGTH`
+    FrameTooLarge { len: usize },
+    /// 消息编码失败
+    Encode(String),
+    /// 消息解码失败，只丢弃这一帧，连接不受影响
+    Decode(String),
+    /// 地址格式错误，或当前平台不支持该类型的地址
+    InvalidAddr(String),
+    /// 没有已连接的对方
+    NotConnected,
+}
+
+impl From<std::io::Error> for ChannelError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value.kind())
+    }
+}
+
+impl std::fmt::Display for ChannelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChannelError::Io(kind) => write!(f, "IO error: {kind}"),
+            ChannelError::FrameTooLarge { len } => write!(f, "Frame too large: {len} bytes"),
+            ChannelError::Encode(message) => write!(f, "Failed to encode message: {message}"),
+            ChannelError::Decode(message) => write!(f, "Failed to decode message: {message}"),
+            ChannelError::InvalidAddr(addr) => write!(f, "Invalid address: {addr}"),
+            ChannelError::NotConnected => f.write_str("Not connected"),
+        }
+    }
+}
+
+impl std::error::Error for ChannelError {}
+
 /// 重置Result
 #[allow(unused)]
 pub type BigHeroXResult<T> = Result<T, BigHeroXError>;
@@ -1,18 +1,18 @@
 //! 场地表示，包括球与球员
 
 pub mod data;
+pub mod world_state;
 pub use data::FieldData;
 
 use std::f32::consts::PI;
 
 use bevy::{
-    asset::AssetPath,
     prelude::*,
     sprite::{MaterialMesh2dBundle, Mesh2dHandle},
 };
 use bevy_mod_picking::prelude::*;
 
-use crate::FONT_PATH;
+use crate::load_font;
 
 /*
  * Part：插件
@@ -406,7 +406,7 @@ fn draw_info_system(
             text: Text::from_section(
                 format!("{player_index}"),
                 TextStyle {
-                    font: asset_server.load(AssetPath::from_path(&FONT_PATH)),
+                    font: load_font(&asset_server),
                     font_size: 48.0,
                     color: Color::BLACK,
                 },
@@ -0,0 +1,52 @@
+//! 场上状态：球员位姿、球与障碍物
+//!
+//! 与旧版协议之间的换算见`data_legacy::convert`。
+
+use std::f32::consts::{PI, TAU};
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+/// 将角度限制在(-π, π]内
+pub fn wrap_angle(angle: f32) -> f32 {
+    let wrapped = (angle + PI).rem_euclid(TAU) - PI;
+    if wrapped <= -PI {
+        wrapped + TAU
+    } else {
+        wrapped
+    }
+}
+
+/// 球员位姿
+/// 坐标系：场地中心为零点，敌方球门方向为x轴正方向，正前方朝着敌方球门时，左侧为y轴正方向，单位：米
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct RobotPose {
+    /// 位置
+    pub pos: Vec2,
+    /// 朝向，x轴正方向为0，逆时针为正，单位：弧度，范围(-π, π]
+    pub angle: f32,
+}
+
+/// 球
+/// 坐标系：场地中心为零点，敌方球门方向为x轴正方向，正前方朝着敌方球门时，左侧为y轴正方向，单位：米
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct BallState {
+    /// 位置
+    pub pos: Vec2,
+}
+
+/// 障碍物
+/// 坐标系：场地中心为零点，敌方球门方向为x轴正方向，正前方朝着敌方球门时，左侧为y轴正方向，单位：米
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Obstacle {
+    /// 位置
+    pub pos: Vec2,
+    /// 大小
+    pub size: f32,
+}
+
+/// 障碍物列表
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ObstacleList {
+    pub obstacles: Vec<Obstacle>,
+}
@@ -0,0 +1,99 @@
+//! 命令行参数：教练机与球员机共用
+//!
+//! 命令行中给出的值会覆盖从配置文件中读取的值，便于同一份程序在不同机器人上由systemd等工具分别启动。
+
+use std::{net::Ipv4Addr, path::PathBuf};
+
+use bevy::{log::Level, prelude::*};
+use clap::{Parser, ValueEnum};
+
+use crate::{app_dir, error::BigHeroXResult, robot::RobotRole, Mode};
+
+/// 启动参数
+/// 插件构建时可从Resource中读取，用于覆盖配置文件中的值。
+#[derive(Debug, Clone, Default, Parser, Resource)]
+#[command(version, about = "BigHeroX RoboCup")]
+pub struct LaunchArgs {
+    /// 机器人编号（1~5），覆盖robot_config/config.toml中的robot_id
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=5))]
+    pub robot_id: Option<u8>,
+    /// 球员角色，覆盖程序默认的角色
+    #[arg(long, value_enum)]
+    pub role: Option<RobotRole>,
+    /// 教练机IP地址
+    #[arg(long)]
+    pub coach_ip: Option<Ipv4Addr>,
+    /// 教练机端口
+    #[arg(long)]
+    pub coach_port: Option<u16>,
+    /// MPU串口，如`COM5`、`/dev/ttyUSB0`，覆盖robot_config/mpu.toml中的设置
+    #[arg(long)]
+    pub mpu_port: Option<String>,
+    /// 下位机串口，如`COM6`、`/dev/ttyUSB1`，覆盖robot_config/lower.toml中的设置
+    #[arg(long)]
+    pub lower_port: Option<String>,
+    /// 配置目录，其下存放robot_config、fonts等
+    #[arg(long)]
+    pub config_dir: Option<PathBuf>,
+    /// 无窗口模式
+    #[arg(long)]
+    pub headless: bool,
+    /// 日志等级
+    #[arg(long, value_enum)]
+    pub log_level: Option<LogLevel>,
+}
+
+impl LaunchArgs {
+    /// 根据命令行参数查找程序目录。需在程序使用配置文件之前调用。
+    pub fn init_app_dirs(&self) -> BigHeroXResult<()> {
+        if let Some(config_dir) = &self.config_dir {
+            app_dir::set_cli_config_dir(config_dir.clone());
+        }
+        app_dir::app_dirs().map(|_| ())
+    }
+
+    /// 用命令行参数覆盖程序运行模式
+    pub fn override_mode(&self, mode: Mode) -> Mode {
+        match (mode, self.role) {
+            (Mode::Robot { .. }, Some(role)) => Mode::Robot { role },
+            (mode, _) => mode,
+        }
+    }
+}
+
+/// 日志等级
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl From<LogLevel> for Level {
+    fn from(value: LogLevel) -> Self {
+        match value {
+            LogLevel::Trace => Level::TRACE,
+            LogLevel::Debug => Level::DEBUG,
+            LogLevel::Info => Level::INFO,
+            LogLevel::Warn => Level::WARN,
+            LogLevel::Error => Level::ERROR,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn robot_id_range() {
+        let args = LaunchArgs::try_parse_from(["striker", "--robot-id", "3"])
+            .expect("Failed to parse args!");
+        assert_eq!(args.robot_id, Some(3));
+        for robot_id in ["0", "6"] {
+            assert!(LaunchArgs::try_parse_from(["striker", "--robot-id", robot_id]).is_err());
+        }
+    }
+}
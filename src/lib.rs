@@ -1,7 +1,11 @@
+pub mod app_dir;
 pub mod coach;
 pub mod data_legacy;
+pub mod data_modern;
 pub mod error;
 pub mod field;
+pub mod launch_args;
+pub mod message_channel;
 pub mod robot;
 pub mod test_network_transfer;
 pub mod traits;
@@ -13,7 +17,10 @@ use std::{
 };
 
 use bevy::{
+    app::ScheduleRunnerPlugin,
+    asset::AssetPath,
     input::mouse::MouseWheel,
+    log::LogPlugin,
     prelude::*,
     window::WindowResolution,
     winit::{UpdateMode, WinitSettings},
@@ -22,6 +29,9 @@ use bevy_mod_picking::prelude::*;
 use static_init::dynamic;
 
 use coach::{CoachMode, CoachPlugin};
+use error::BigHeroXResult;
+use field::FieldData;
+use launch_args::LaunchArgs;
 use robot::{RobotPlugin, RobotRole};
 use ui_components::UiComponentsPlugin;
 
@@ -32,55 +42,97 @@ use ui_components::UiComponentsPlugin;
 #[derive(Debug)]
 pub struct MainPlugin {
     pub mode: Mode,
+    /// 无窗口模式：不创建窗口、不绘制界面，只运行通信与逻辑部分。
+    /// 用于SSH登录机器人电脑运行，或在无显示器的环境中测试。
+    pub headless: bool,
+    /// 命令行参数，用于覆盖配置文件
+    pub launch_args: LaunchArgs,
+}
+
+impl MainPlugin {
+    /// 根据命令行参数创建
+    pub fn from_launch_args(default_mode: Mode, launch_args: LaunchArgs) -> Self {
+        Self {
+            mode: launch_args.override_mode(default_mode),
+            headless: launch_args.headless,
+            launch_args,
+        }
+    }
 }
 
 impl Plugin for MainPlugin {
     fn build(&self, app: &mut App) {
-        // 主窗口
-        app
-            // 设置固定时间系统的执行间隔，FPS 500
-            .insert_resource(Time::<Fixed>::from_duration(Duration::from_millis(2)))
-            // 设置运行更新模式：一直更新
-            .insert_resource(WinitSettings {
-                focused_mode: UpdateMode::Continuous,
-                unfocused_mode: UpdateMode::Continuous,
-            })
-            // 设置窗口
-            .add_plugins(DefaultPlugins.set(WindowPlugin {
-                primary_window: Some(Window {
-                    // 窗口标题
-                    title: format!("BigHeroX RoboCup：{}", self.mode),
-                    // 设置窗口大小，且无视系统DPI
-                    resolution:
-                        WindowResolution::new(1760.0, 990.0).with_scale_factor_override(1.0),
-                    ..Default::default()
-                }),
-                ..low_latency_window_plugin()
-            }));
-        // 设置bevy_mod_picking相关参数``
-        app
-            .add_plugins(DefaultPickingPlugins.build().disable::<DefaultHighlightingPlugin>())
-            // .insert_resource(DebugPickingMode::Normal)
-            ;
-        // 初始化图形
-        app.add_systems(Startup, camera_setup_system);
+        // 设置固定时间系统的执行间隔，FPS 500
+        app.insert_resource(Time::<Fixed>::from_duration(Duration::from_millis(2)))
+            // 命令行参数：供各插件覆盖配置
+            .insert_resource(self.launch_args.clone());
+        // 日志
+        let log_plugin = LogPlugin {
+            level: self
+                .launch_args
+                .log_level
+                .map_or(LogPlugin::default().level, Into::into),
+            ..Default::default()
+        };
+        if self.headless {
+            // 无窗口：只保留调度与日志
+            app.add_plugins(
+                MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_millis(1))),
+            )
+            .add_plugins(log_plugin);
+        } else {
+            // 主窗口
+            app
+                // 设置运行更新模式：一直更新
+                .insert_resource(WinitSettings {
+                    focused_mode: UpdateMode::Continuous,
+                    unfocused_mode: UpdateMode::Continuous,
+                })
+                // 设置窗口
+                .add_plugins(DefaultPlugins.set(log_plugin).set(WindowPlugin {
+                    primary_window: Some(Window {
+                        // 窗口标题
+                        title: format!("BigHeroX RoboCup：{}", self.mode),
+                        // 设置窗口大小，且无视系统DPI
+                        resolution: WindowResolution::new(1760.0, 990.0)
+                            .with_scale_factor_override(1.0),
+                        ..Default::default()
+                    }),
+                    ..low_latency_window_plugin()
+                }));
+            // 设置bevy_mod_picking相关参数``
+            app
+                .add_plugins(DefaultPickingPlugins.build().disable::<DefaultHighlightingPlugin>())
+                // .insert_resource(DebugPickingMode::Normal)
+                ;
+            // 初始化图形
+            app.add_systems(Startup, camera_setup_system)
+                // 鼠标滚轮事件
+                // .add_systems(Update, scroll_system)
+                // UI组件
+                .add_plugins(UiComponentsPlugin);
+        }
         // 添加时间戳事件
-        app.add_systems(FixedPreUpdate, time_flag_activate_system)
-            // 鼠标滚轮事件
-            // .add_systems(Update, scroll_system)
-            // UI组件
-            .add_plugins(UiComponentsPlugin);
+        app.add_systems(FixedPreUpdate, time_flag_activate_system);
         // 根据模式添加对应组件
         match self.mode {
             Mode::Coach { mode } => {
                 app.add_plugins(CoachPlugin { mode });
             }
             Mode::Robot { role } => {
-                app.add_plugins(RobotPlugin { role });
+                app.add_plugins(RobotPlugin {
+                    role,
+                    headless: self.headless,
+                });
             }
         }
-        // 添加场地绘制组件
-        app.add_plugins(field::FieldPlugin);
+        if self.headless {
+            // 无窗口时不绘制场地，但仍提供场地数据
+            app.insert_resource(FieldData::default());
+        } else {
+            // 添加场地绘制组件
+            app.add_plugins(field::FieldPlugin);
+        }
     }
 }
 
@@ -170,34 +222,20 @@ fn time_flag_activate_system(mut commands: Commands, quary_flags: Query<(Entity,
  * 静态常量
  */
 
-/// crate目录
-#[dynamic]
-static CRATE_DIR: PathBuf = {
-    let current_dir = std::env::current_dir().expect("Failed to get current dir!");
-    [
-        current_dir.clone(),
-        current_dir.join("bigherox-robocup"),
-        current_dir.join(".."),
-        current_dir.join("..").join("bigherox-robocup"),
-        current_dir.join("..").join(".."),
-        current_dir.join("..").join("..").join("bigherox-robocup"),
-        current_dir.join("..").join("..").join(".."),
-        current_dir
-            .join("..")
-            .join("..")
-            .join("..")
-            .join("bigherox-robocup"),
-    ]
-    .into_iter()
-    .chain(std::env::split_paths(
-        std::env::var_os("Path")
-            .expect("Failed to find system path!")
-            .as_os_str(),
-    ))
-    // 寻找Cargo.lock
-    .find(|dir| dir.join("Cargo.lock").is_file())
-    .expect("Unable to find workspace dir. Please change current dir to the workspace.")
-};
-
-#[dynamic]
-static FONT_PATH: PathBuf = CRATE_DIR.join("fonts").join("SourceHanSansCN-Regular.otf");
+#[dynamic(lazy)]
+static FONT_PATH: BigHeroXResult<PathBuf> = app_dir::app_dirs().map(|dirs| {
+    dirs.data_dir
+        .join("fonts")
+        .join("SourceHanSansCN-Regular.otf")
+});
+
+/// 加载界面字体。找不到程序目录时使用Bevy自带的默认字体。
+pub(crate) fn load_font(asset_server: &AssetServer) -> Handle<Font> {
+    match FONT_PATH.as_ref() {
+        Ok(font_path) => asset_server.load(AssetPath::from_path(font_path)),
+        Err(err) => {
+            warn!("Failed to locate font: {err:?}, now using default font.");
+            Handle::default()
+        }
+    }
+}
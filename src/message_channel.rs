@@ -0,0 +1,848 @@
+//! 消息通道：基于TCP或Unix套接字，收发任意serde消息
+//!
+//! 用于调试控制台、记录用电脑等不需要UDP的工具连接。
+//! 每条消息为一帧：4字节大端长度，之后为bincode编码的`ChannelFrame`。
+//! 每帧带有编号，回复时在`reply_to`中填入请求的编号，以便对应请求与回复。
+//!
+//! 收发在后台线程中进行：Bevy系统通过`ChannelReceiveEvent`接收，通过`MessageChannel::send`等发送。
+//! 主动连接的一方断开后按`ChannelBackoff`自动重连。
+//! 同一种消息类型只能有一个通道，需要多个通道时请为每个通道定义各自的消息类型。
+
+use std::{
+    collections::HashMap,
+    io::{self, prelude::*},
+    marker::PhantomData,
+    net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread,
+    time::Duration,
+};
+
+#[cfg(unix)]
+use std::{
+    os::unix::{
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+};
+
+use bevy::prelude::*;
+use bincode::Options;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::error::ChannelError;
+
+/*
+ * Part: Plugin
+ */
+
+/// 可在通道中收发的消息
+pub trait ChannelMessage: Serialize + DeserializeOwned + Send + Sync + 'static {}
+
+impl<M: Serialize + DeserializeOwned + Send + Sync + 'static> ChannelMessage for M {}
+
+/// 添加一个消息通道，并在`FixedPreUpdate`中把收到的消息转为事件
+pub struct MessageChannelPlugin<M: ChannelMessage> {
+    pub endpoint: ChannelEndpoint,
+    marker: PhantomData<fn() -> M>,
+}
+
+impl<M: ChannelMessage> MessageChannelPlugin<M> {
+    /// 主动连接`addr`，断开后自动重连
+    pub fn connect(addr: ChannelAddr, backoff: ChannelBackoff) -> Self {
+        Self {
+            endpoint: ChannelEndpoint::Connect { addr, backoff },
+            marker: PhantomData,
+        }
+    }
+
+    /// 监听`addr`，接受任意数量的连接
+    pub fn listen(addr: ChannelAddr) -> Self {
+        Self {
+            endpoint: ChannelEndpoint::Listen { addr },
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<M: ChannelMessage> Plugin for MessageChannelPlugin<M> {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ChannelReceiveEvent<M>>()
+            .add_event::<ChannelConnectionEvent<M>>()
+            .add_systems(FixedPreUpdate, channel_receive_system::<M>);
+        match &self.endpoint {
+            ChannelEndpoint::Connect { addr, backoff } => {
+                app.insert_resource(MessageChannel::<M>::connect(addr.clone(), *backoff));
+            }
+            ChannelEndpoint::Listen { addr } => match MessageChannel::<M>::listen(addr) {
+                Ok(channel) => {
+                    app.insert_resource(channel);
+                }
+                Err(err) => warn!("Failed to listen on {addr}: {err}"),
+            },
+        }
+    }
+}
+
+/// 通道的一端
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelEndpoint {
+    Connect {
+        addr: ChannelAddr,
+        backoff: ChannelBackoff,
+    },
+    Listen {
+        addr: ChannelAddr,
+    },
+}
+
+/*
+ * Part: Event
+ */
+
+/// 收到消息
+#[derive(Debug, Clone, Event)]
+pub struct ChannelReceiveEvent<M: ChannelMessage> {
+    pub peer: PeerId,
+    /// 对方分配的帧编号，回复时使用
+    pub id: u64,
+    /// 该消息回复的请求编号
+    pub reply_to: Option<u64>,
+    pub message: M,
+}
+
+/// 连接建立或断开
+#[derive(Debug, Clone, Copy, Event)]
+pub struct ChannelConnectionEvent<M: ChannelMessage> {
+    pub peer: PeerId,
+    pub connected: bool,
+    marker: PhantomData<fn() -> M>,
+}
+
+fn channel_receive_system<M: ChannelMessage>(
+    channel: Option<Res<MessageChannel<M>>>,
+    mut receive_event_writer: EventWriter<ChannelReceiveEvent<M>>,
+    mut connection_event_writer: EventWriter<ChannelConnectionEvent<M>>,
+) {
+    let Some(channel) = channel else {
+        return;
+    };
+    while let Some(input) = channel.try_recv() {
+        match input {
+            ChannelInput::Connected(peer) | ChannelInput::Disconnected(peer) => {
+                connection_event_writer.send(ChannelConnectionEvent {
+                    peer,
+                    connected: matches!(input, ChannelInput::Connected(_)),
+                    marker: PhantomData,
+                });
+            }
+            ChannelInput::Frame(peer, frame) => {
+                receive_event_writer.send(ChannelReceiveEvent {
+                    peer,
+                    id: frame.id,
+                    reply_to: frame.reply_to,
+                    message: frame.message,
+                });
+            }
+        }
+    }
+}
+
+/*
+ * Part: Frame
+ */
+
+/// 单帧最大长度，防止错误数据导致分配过多内存
+pub const CHANNEL_MAX_FRAME_LENGTH: u32 = 16 * 1024 * 1024;
+/// 长度前缀的字节数
+const FRAME_LENGTH_BYTES: usize = 4;
+
+/// 一帧的内容
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelFrame<M> {
+    /// 帧编号，由发送方分配
+    pub id: u64,
+    /// 回复的请求编号
+    pub reply_to: Option<u64>,
+    pub message: M,
+}
+
+fn bincode_options() -> impl Options {
+    bincode::DefaultOptions::new().with_limit(CHANNEL_MAX_FRAME_LENGTH as u64)
+}
+
+/// 编码一帧，包括长度前缀
+pub fn encode_frame<M: Serialize>(frame: &ChannelFrame<M>) -> Result<Vec<u8>, ChannelError> {
+    let mut bytes = vec![0u8; FRAME_LENGTH_BYTES];
+    bincode_options()
+        .serialize_into(&mut bytes, frame)
+        .map_err(|err| match *err {
+            bincode::ErrorKind::SizeLimit => ChannelError::FrameTooLarge {
+                len: CHANNEL_MAX_FRAME_LENGTH as usize + 1,
+            },
+            err => ChannelError::Encode(err.to_string()),
+        })?;
+    let len = (bytes.len() - FRAME_LENGTH_BYTES) as u32;
+    bytes[..FRAME_LENGTH_BYTES].copy_from_slice(&len.to_be_bytes());
+    Ok(bytes)
+}
+
+/// 读取一帧。除`ChannelError::Decode`外，出错后连接不再可用
+pub fn read_frame<M: DeserializeOwned>(
+    reader: &mut impl Read,
+) -> Result<ChannelFrame<M>, ChannelError> {
+    let mut len_bytes = [0u8; FRAME_LENGTH_BYTES];
+    reader.read_exact(&mut len_bytes)?;
+    let len = u32::from_be_bytes(len_bytes);
+    if len > CHANNEL_MAX_FRAME_LENGTH {
+        return Err(ChannelError::FrameTooLarge { len: len as usize });
+    }
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload)?;
+    bincode_options()
+        .deserialize(&payload)
+        .map_err(|err| ChannelError::Decode(err.to_string()))
+}
+
+/*
+ * Part: Address
+ */
+
+/// 通道地址。配置文件与命令行中写作`127.0.0.1:11451`或`unix:/tmp/bigherox.sock`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChannelAddr {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl FromStr for ChannelAddr {
+    type Err = ChannelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            #[cfg(unix)]
+            return Ok(ChannelAddr::Unix(PathBuf::from(path)));
+            #[cfg(not(unix))]
+            return Err(ChannelError::InvalidAddr(format!(
+                "Unix sockets are not supported on this platform: {path}"
+            )));
+        }
+        s.parse()
+            .map(ChannelAddr::Tcp)
+            .map_err(|_| ChannelError::InvalidAddr(s.to_string()))
+    }
+}
+
+impl std::fmt::Display for ChannelAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChannelAddr::Tcp(addr) => write!(f, "{addr}"),
+            #[cfg(unix)]
+            ChannelAddr::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// 重连的退避时间：从`initial_ms`开始每次翻倍，最多`max_ms`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelBackoff {
+    pub initial_ms: u64,
+    pub max_ms: u64,
+}
+
+impl Default for ChannelBackoff {
+    fn default() -> Self {
+        Self {
+            initial_ms: 100,
+            max_ms: 5000,
+        }
+    }
+}
+
+impl ChannelBackoff {
+    /// 第`attempt`次（从0开始）连接失败后的等待时间
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay_ms = self
+            .initial_ms
+            .saturating_mul(1u64.checked_shl(attempt).unwrap_or(u64::MAX))
+            .min(self.max_ms);
+        Duration::from_millis(delay_ms)
+    }
+}
+
+enum ChannelStream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl ChannelStream {
+    fn connect(addr: &ChannelAddr) -> io::Result<Self> {
+        match addr {
+            ChannelAddr::Tcp(addr) => {
+                let stream = TcpStream::connect(addr)?;
+                stream.set_nodelay(true)?;
+                Ok(ChannelStream::Tcp(stream))
+            }
+            #[cfg(unix)]
+            ChannelAddr::Unix(path) => UnixStream::connect(path).map(ChannelStream::Unix),
+        }
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            ChannelStream::Tcp(stream) => stream.try_clone().map(ChannelStream::Tcp),
+            #[cfg(unix)]
+            ChannelStream::Unix(stream) => stream.try_clone().map(ChannelStream::Unix),
+        }
+    }
+
+    fn shutdown(&self) {
+        // 对方可能已经断开，忽略错误
+        let _ = match self {
+            ChannelStream::Tcp(stream) => stream.shutdown(Shutdown::Both),
+            #[cfg(unix)]
+            ChannelStream::Unix(stream) => stream.shutdown(Shutdown::Both),
+        };
+    }
+}
+
+impl Read for ChannelStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            ChannelStream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            ChannelStream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for ChannelStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            ChannelStream::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            ChannelStream::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            ChannelStream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            ChannelStream::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+enum ChannelListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl ChannelListener {
+    fn bind(addr: &ChannelAddr) -> io::Result<Self> {
+        match addr {
+            ChannelAddr::Tcp(addr) => TcpListener::bind(addr).map(ChannelListener::Tcp),
+            #[cfg(unix)]
+            ChannelAddr::Unix(path) => {
+                // 上次运行留下的套接字文件
+                if std::fs::symlink_metadata(path)
+                    .is_ok_and(|metadata| metadata.file_type().is_socket())
+                {
+                    std::fs::remove_file(path)?;
+                }
+                UnixListener::bind(path).map(ChannelListener::Unix)
+            }
+        }
+    }
+
+    fn local_addr(&self) -> Option<ChannelAddr> {
+        match self {
+            ChannelListener::Tcp(listener) => listener.local_addr().ok().map(ChannelAddr::Tcp),
+            #[cfg(unix)]
+            ChannelListener::Unix(listener) => listener
+                .local_addr()
+                .ok()
+                .and_then(|addr| addr.as_pathname().map(|path| path.to_path_buf()))
+                .map(ChannelAddr::Unix),
+        }
+    }
+
+    fn accept(&self) -> io::Result<ChannelStream> {
+        match self {
+            ChannelListener::Tcp(listener) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nodelay(true)?;
+                Ok(ChannelStream::Tcp(stream))
+            }
+            #[cfg(unix)]
+            ChannelListener::Unix(listener) => listener
+                .accept()
+                .map(|(stream, _)| ChannelStream::Unix(stream)),
+        }
+    }
+}
+
+/*
+ * Part: Channel
+ */
+
+/// 连接编号，每个连接（包括重连）各不相同
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PeerId(pub u64);
+
+/// 后台线程传给通道的内容
+#[derive(Debug)]
+pub enum ChannelInput<M> {
+    Connected(PeerId),
+    Disconnected(PeerId),
+    Frame(PeerId, ChannelFrame<M>),
+}
+
+type PeerSenders = Arc<Mutex<HashMap<PeerId, Sender<Vec<u8>>>>>;
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // 后台线程出错不影响数据本身
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// 消息通道。删除后断开所有连接，并停止重连
+#[derive(Resource)]
+pub struct MessageChannel<M: ChannelMessage> {
+    peers: PeerSenders,
+    inputs: Mutex<Receiver<ChannelInput<M>>>,
+    closed: Arc<AtomicBool>,
+    next_id: AtomicU64,
+    local_addr: Option<ChannelAddr>,
+    listen_thread: Option<thread::JoinHandle<()>>,
+}
+
+/// 后台线程共用的部分
+struct ChannelShared<M> {
+    peers: PeerSenders,
+    inputs: Sender<ChannelInput<M>>,
+    closed: Arc<AtomicBool>,
+    next_peer: Arc<AtomicU64>,
+}
+
+impl<M> Clone for ChannelShared<M> {
+    fn clone(&self) -> Self {
+        Self {
+            peers: Arc::clone(&self.peers),
+            inputs: self.inputs.clone(),
+            closed: Arc::clone(&self.closed),
+            next_peer: Arc::clone(&self.next_peer),
+        }
+    }
+}
+
+impl<M: ChannelMessage> MessageChannel<M> {
+    fn new(local_addr: Option<ChannelAddr>) -> (Self, ChannelShared<M>) {
+        let (input_sender, input_receiver) = mpsc::channel();
+        let channel = Self {
+            peers: Default::default(),
+            inputs: Mutex::new(input_receiver),
+            closed: Default::default(),
+            next_id: AtomicU64::new(1),
+            local_addr,
+            listen_thread: None,
+        };
+        let shared = ChannelShared {
+            peers: Arc::clone(&channel.peers),
+            inputs: input_sender,
+            closed: Arc::clone(&channel.closed),
+            next_peer: Default::default(),
+        };
+        (channel, shared)
+    }
+
+    /// 主动连接`addr`，断开后按`backoff`自动重连
+    pub fn connect(addr: ChannelAddr, backoff: ChannelBackoff) -> Self {
+        let (channel, shared) = Self::new(None);
+        thread::spawn(move || connect_thread(addr, backoff, shared));
+        channel
+    }
+
+    /// 监听`addr`。TCP端口为0时由系统分配，可通过`local_addr`获取
+    pub fn listen(addr: &ChannelAddr) -> Result<Self, ChannelError> {
+        let listener = ChannelListener::bind(addr)?;
+        let (mut channel, shared) = Self::new(listener.local_addr());
+        info!("Channel listening on {addr}");
+        channel.listen_thread = Some(thread::spawn(move || listen_thread(listener, shared)));
+        Ok(channel)
+    }
+
+    /// 监听的地址
+    pub fn local_addr(&self) -> Option<&ChannelAddr> {
+        self.local_addr.as_ref()
+    }
+
+    /// 当前已连接的对方
+    pub fn peers(&self) -> Vec<PeerId> {
+        let mut peers: Vec<_> = lock(&self.peers).keys().copied().collect();
+        peers.sort();
+        peers
+    }
+
+    /// 向所有已连接的对方发送消息，返回帧编号
+    pub fn send(&self, message: &M) -> Result<u64, ChannelError> {
+        self.send_frame(None, None, message)
+    }
+
+    /// 向`peer`发送消息，返回帧编号
+    pub fn send_to(&self, peer: PeerId, message: &M) -> Result<u64, ChannelError> {
+        self.send_frame(Some(peer), None, message)
+    }
+
+    /// 回复`request`，返回帧编号
+    pub fn reply(
+        &self,
+        request: &ChannelReceiveEvent<M>,
+        message: &M,
+    ) -> Result<u64, ChannelError> {
+        self.send_frame(Some(request.peer), Some(request.id), message)
+    }
+
+    fn send_frame(
+        &self,
+        peer: Option<PeerId>,
+        reply_to: Option<u64>,
+        message: &M,
+    ) -> Result<u64, ChannelError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let bytes = encode_frame(&ChannelFrame {
+            id,
+            reply_to,
+            message,
+        })?;
+        let peers = lock(&self.peers);
+        let mut sent = false;
+        for (_, sender) in peers
+            .iter()
+            .filter(|(peer_id, _)| peer.is_none() || peer == Some(**peer_id))
+        {
+            // 发送失败说明该连接正在断开，由后台线程处理
+            sent |= sender.send(bytes.clone()).is_ok();
+        }
+        if sent {
+            Ok(id)
+        } else {
+            Err(ChannelError::NotConnected)
+        }
+    }
+
+    /// 取出后台线程收到的内容，没有时返回`None`
+    pub fn try_recv(&self) -> Option<ChannelInput<M>> {
+        lock(&self.inputs).try_recv().ok()
+    }
+}
+
+impl<M: ChannelMessage> Drop for MessageChannel<M> {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+        // 写线程随之退出并关闭连接，读线程因此退出
+        lock(&self.peers).clear();
+        // 连接一次自己，唤醒阻塞在accept上的线程，使其退出并释放端口
+        let Some(listen_thread) = self.listen_thread.take() else {
+            return;
+        };
+        let wake_addr = match self.local_addr.clone() {
+            Some(ChannelAddr::Tcp(mut addr)) => {
+                if addr.ip().is_unspecified() {
+                    addr.set_ip(Ipv4Addr::LOCALHOST.into());
+                }
+                Some(ChannelAddr::Tcp(addr))
+            }
+            addr => addr,
+        };
+        if wake_addr.is_some_and(|addr| ChannelStream::connect(&addr).is_ok()) {
+            let _ = listen_thread.join();
+        }
+    }
+}
+
+/// 主动连接，断开后重连
+fn connect_thread<M: ChannelMessage>(
+    addr: ChannelAddr,
+    backoff: ChannelBackoff,
+    shared: ChannelShared<M>,
+) {
+    let mut attempt = 0u32;
+    while !shared.closed.load(Ordering::Relaxed) {
+        match ChannelStream::connect(&addr) {
+            Ok(stream) => {
+                info!("Channel connected: {addr}");
+                attempt = 0;
+                serve_stream(stream, &shared);
+                info!("Channel disconnected: {addr}");
+                continue;
+            }
+            // 只在第一次失败时提示，避免刷屏
+            Err(err) if attempt == 0 => warn!("Failed to connect {addr}: {err}, retrying"),
+            Err(err) => debug!("Failed to connect {addr}: {err}"),
+        }
+        thread::sleep(backoff.delay(attempt));
+        attempt = attempt.saturating_add(1);
+    }
+}
+
+/// 接受连接，每个连接一个线程
+fn listen_thread<M: ChannelMessage>(listener: ChannelListener, shared: ChannelShared<M>) {
+    while !shared.closed.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok(stream) => {
+                let shared = shared.clone();
+                thread::spawn(move || serve_stream(stream, &shared));
+            }
+            Err(err) => {
+                warn!("Failed to accept channel connection: {err}");
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+}
+
+/// 处理一个连接，直到断开
+fn serve_stream<M: ChannelMessage>(mut stream: ChannelStream, shared: &ChannelShared<M>) {
+    if shared.closed.load(Ordering::Relaxed) {
+        stream.shutdown();
+        return;
+    }
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(err) => {
+            warn!("Failed to clone channel stream: {err}");
+            stream.shutdown();
+            return;
+        }
+    };
+    let peer = PeerId(shared.next_peer.fetch_add(1, Ordering::Relaxed));
+    let (sender, receiver) = mpsc::channel::<Vec<u8>>();
+    lock(&shared.peers).insert(peer, sender);
+    let _ = shared.inputs.send(ChannelInput::Connected(peer));
+
+    // 写：发送端被删除或写入失败时退出
+    let writer_thread = thread::spawn(move || {
+        for bytes in receiver {
+            if writer.write_all(&bytes).is_err() {
+                break;
+            }
+        }
+        writer.shutdown();
+    });
+    // 读
+    loop {
+        match read_frame::<M>(&mut stream) {
+            Ok(frame) => {
+                if shared
+                    .inputs
+                    .send(ChannelInput::Frame(peer, frame))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            Err(ChannelError::Decode(err)) => warn!("Drop malformed channel frame: {err}"),
+            Err(err) => {
+                debug!("Channel peer {peer:?} closed: {err}");
+                break;
+            }
+        }
+    }
+    lock(&shared.peers).remove(&peer);
+    stream.shutdown();
+    let _ = writer_thread.join();
+    let _ = shared.inputs.send(ChannelInput::Disconnected(peer));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, net::SocketAddrV4, time::Instant};
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    enum TestMessage {
+        Ping(u32),
+        Pong(u32),
+        Text(String),
+    }
+
+    #[test]
+    fn round_convert_frame() {
+        let frame = ChannelFrame {
+            id: 42,
+            reply_to: Some(7),
+            message: TestMessage::Text("a".repeat(1000)),
+        };
+        let bytes = encode_frame(&frame).expect("Failed to encode frame!");
+        // 长度前缀为u32，不再受255字节限制
+        let len = u32::from_be_bytes(bytes[..4].try_into().unwrap()) as usize;
+        assert_eq!(len, bytes.len() - 4);
+        assert!(len > 1000);
+        // 连续多帧
+        let mut stream = bytes.clone();
+        stream.extend(
+            encode_frame(&ChannelFrame {
+                id: 43,
+                reply_to: None,
+                message: TestMessage::Ping(1),
+            })
+            .unwrap(),
+        );
+        let mut reader = Cursor::new(stream);
+        assert_eq!(read_frame::<TestMessage>(&mut reader), Ok(frame));
+        assert_eq!(
+            read_frame::<TestMessage>(&mut reader).map(|frame| frame.message),
+            Ok(TestMessage::Ping(1))
+        );
+        assert_eq!(
+            read_frame::<TestMessage>(&mut reader),
+            Err(ChannelError::Io(io::ErrorKind::UnexpectedEof))
+        );
+    }
+
+    #[test]
+    fn malformed_frame() {
+        // 长度过大：不分配内存，直接报错
+        let mut reader = Cursor::new(u32::MAX.to_be_bytes().to_vec());
+        assert_eq!(
+            read_frame::<TestMessage>(&mut reader),
+            Err(ChannelError::FrameTooLarge {
+                len: u32::MAX as usize
+            })
+        );
+        // 内容错误：只丢弃这一帧
+        let mut bytes = vec![0, 0, 0, 2, 0xFF, 0xFF];
+        bytes.extend(
+            encode_frame(&ChannelFrame {
+                id: 1,
+                reply_to: None,
+                message: TestMessage::Pong(3),
+            })
+            .unwrap(),
+        );
+        let mut reader = Cursor::new(bytes);
+        assert!(matches!(
+            read_frame::<TestMessage>(&mut reader),
+            Err(ChannelError::Decode(_))
+        ));
+        assert_eq!(
+            read_frame::<TestMessage>(&mut reader).map(|frame| frame.message),
+            Ok(TestMessage::Pong(3))
+        );
+    }
+
+    #[test]
+    fn addr_and_backoff() {
+        assert_eq!(
+            "127.0.0.1:11451".parse(),
+            Ok(ChannelAddr::Tcp(SocketAddr::V4(SocketAddrV4::new(
+                Ipv4Addr::LOCALHOST,
+                11451
+            ))))
+        );
+        assert!(matches!(
+            "localhost".parse::<ChannelAddr>(),
+            Err(ChannelError::InvalidAddr(_))
+        ));
+        #[cfg(unix)]
+        assert_eq!(
+            "unix:/tmp/bigherox.sock".parse(),
+            Ok(ChannelAddr::Unix(PathBuf::from("/tmp/bigherox.sock")))
+        );
+        let backoff = ChannelBackoff {
+            initial_ms: 100,
+            max_ms: 1000,
+        };
+        assert_eq!(backoff.delay(0), Duration::from_millis(100));
+        assert_eq!(backoff.delay(2), Duration::from_millis(400));
+        assert_eq!(backoff.delay(4), Duration::from_millis(1000));
+        assert_eq!(backoff.delay(100), Duration::from_millis(1000));
+    }
+
+    /// 等待通道收到满足条件的内容
+    fn wait_for<M: ChannelMessage, T>(
+        channel: &MessageChannel<M>,
+        mut filter: impl FnMut(ChannelInput<M>) -> Option<T>,
+    ) -> T {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            match channel.try_recv() {
+                Some(input) => {
+                    if let Some(value) = filter(input) {
+                        return value;
+                    }
+                }
+                None => thread::sleep(Duration::from_millis(1)),
+            }
+        }
+        panic!("Timeout waiting for channel input!");
+    }
+
+    #[test]
+    fn request_response_and_reconnect() {
+        let addr = ChannelAddr::Tcp(SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0)));
+        let server = MessageChannel::<TestMessage>::listen(&addr).unwrap();
+        let server_addr = server.local_addr().unwrap().clone();
+        let backoff = ChannelBackoff {
+            initial_ms: 10,
+            max_ms: 50,
+        };
+        let client = MessageChannel::<TestMessage>::connect(server_addr.clone(), backoff);
+        wait_for(&client, |input| {
+            matches!(input, ChannelInput::Connected(_)).then_some(())
+        });
+
+        // 请求与回复一一对应
+        let request_id = client.send(&TestMessage::Ping(5)).unwrap();
+        let (peer, frame) = wait_for(&server, |input| match input {
+            ChannelInput::Frame(peer, frame) => Some((peer, frame)),
+            _ => None,
+        });
+        assert_eq!(frame.message, TestMessage::Ping(5));
+        let request = ChannelReceiveEvent {
+            peer,
+            id: frame.id,
+            reply_to: frame.reply_to,
+            message: frame.message,
+        };
+        server.reply(&request, &TestMessage::Pong(5)).unwrap();
+        let response = wait_for(&client, |input| match input {
+            ChannelInput::Frame(_, frame) => Some(frame),
+            _ => None,
+        });
+        assert_eq!(response.reply_to, Some(request_id));
+        assert_eq!(response.message, TestMessage::Pong(5));
+
+        // 服务端重启后客户端自动重连
+        drop(server);
+        wait_for(&client, |input| {
+            matches!(input, ChannelInput::Disconnected(_)).then_some(())
+        });
+        assert_eq!(
+            client.send(&TestMessage::Ping(6)),
+            Err(ChannelError::NotConnected)
+        );
+        let server = MessageChannel::<TestMessage>::listen(&server_addr).unwrap();
+        wait_for(&client, |input| {
+            matches!(input, ChannelInput::Connected(_)).then_some(())
+        });
+        client.send(&TestMessage::Ping(7)).unwrap();
+        let frame = wait_for(&server, |input| match input {
+            ChannelInput::Frame(_, frame) => Some(frame),
+            _ => None,
+        });
+        assert_eq!(frame.message, TestMessage::Ping(7));
+    }
+}
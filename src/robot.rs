@@ -2,14 +2,16 @@ pub mod com_mpu;
 pub mod com_robot;
 pub mod logic;
 pub mod motion;
+pub mod network;
 pub mod panorama_camera;
+pub mod serial;
 pub mod test_cpp;
-pub mod test_network_legacy;
 pub mod test_rust;
 pub mod ui;
 
-use std::path::PathBuf;
+use std::{net::Ipv4Addr, path::PathBuf};
 
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 use static_init::dynamic;
 use std::fs::create_dir_all;
@@ -17,43 +19,72 @@ use std::fs::create_dir_all;
 use bevy::prelude::*;
 
 use crate::{
-    field::FieldData, test_network_transfer::TestNetworkTransferPlugin, traits::FastAccessData,
-    CRATE_DIR,
+    app_dir::app_dirs, data_legacy::convert::LegacyFrame, data_modern::ProtocolMode,
+    error::BigHeroXResult, field::FieldData, launch_args::LaunchArgs,
+    test_network_transfer::TestNetworkTransferPlugin, traits::FastAccessData,
 };
 
-use self::{test_cpp::TestCppInputPlugin, test_rust::TestRustInputPlugin};
+use self::{
+    logic::behaviour::BehaviourConfig,
+    motion::{
+        chassis::ChassisConfig, limiter::MotionLimits, path_plan::PathPlanConfig,
+        pose_control::PoseControlConfig, wheel_control::WheelControlConfig,
+    },
+    test_cpp::TestCppInputPlugin,
+    test_rust::TestRustInputPlugin,
+};
 
 pub struct RobotPlugin {
     pub role: RobotRole,
+    /// 无窗口模式：不添加界面组件
+    pub headless: bool,
 }
 
 impl Plugin for RobotPlugin {
     fn build(&self, app: &mut App) {
+        // 读取配置文件，再用命令行参数覆盖
+        let mut config = RobotConfig::load_or_default();
+        if let Some(launch_args) = app.world.get_resource::<LaunchArgs>() {
+            config.override_with(launch_args);
+        }
+        config.chassis.validate();
         app
             // 添加角色
             .insert_resource(self.role)
-            // 读取配置文件
-            .insert_resource(RobotConfig::load_or_default())
-            // 添加界面组件
-            .add_plugins(ui::RobotUiPlugin)
+            // 添加配置
+            .insert_resource(config)
             // 添加下位机组件
             .add_plugins(com_mpu::RobotMPUPlugin)
+            .add_plugins(com_robot::RobotLowerPlugin)
+            // 添加里程计
+            .add_plugins(motion::odometry::RobotOdometryPlugin)
+            // 添加底盘电机转速闭环
+            .add_plugins(motion::wheel_control::WheelControlPlugin)
+            // 添加运动逻辑：行为、路径规划与位姿控制
+            .add_plugins(logic::RobotMotionLogicPlugin)
+            // 添加教练机通信组件
+            .add_plugins(network::RobotNetworkPlugin)
             // 添加输入
             .add_plugins(TestRustInputPlugin)
             .add_plugins(TestCppInputPlugin)
             .add_plugins(TestNetworkTransferPlugin)
             // To be continued
         ;
+        if !self.headless {
+            // 添加界面组件
+            app.add_plugins(ui::RobotUiPlugin);
+        }
     }
 }
 
 /// 球员模式列表，如进攻球员、守门员等。
 /// 应在一开始被添加至Resource中。
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Resource)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Resource, ValueEnum)]
 pub enum RobotRole {
     /// 前锋
     Striker,
     /// 守门员
+    #[value(name = "goalkeeper")]
     GoalKeeper,
 }
 
@@ -67,27 +98,115 @@ impl std::fmt::Display for RobotRole {
     }
 }
 
-#[dynamic]
-pub static ROBOT_CONFIG_DIR: PathBuf = {
-    let config_dir = CRATE_DIR.join("robot_config");
+#[dynamic(lazy)]
+pub static ROBOT_CONFIG_DIR: BigHeroXResult<PathBuf> = app_dirs().and_then(|dirs| {
+    let config_dir = dirs.config_dir.join("robot_config");
     if !config_dir.is_dir() {
-        create_dir_all(&config_dir).expect("Failed to create robot_config dir!");
+        create_dir_all(&config_dir)?;
     }
-    config_dir
-};
+    Ok(config_dir)
+});
 
 /// 机器人设置
-#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize, Resource)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Resource)]
+#[serde(default)]
 pub struct RobotConfig {
+    /// 机器人编号，1~5
+    pub robot_id: u8,
+    pub network: RobotNetworkConfig,
     pub field_data: FieldData,
+    /// 底盘参数
+    pub chassis: ChassisConfig,
+    /// 底盘加速度与加加速度上限
+    pub motion_limits: MotionLimits,
+    /// 底盘电机转速闭环
+    pub wheel_control: WheelControlConfig,
+    /// 位姿控制
+    pub pose_control: PoseControlConfig,
+    /// 路径规划
+    pub path_plan: PathPlanConfig,
+    /// 传球、接球与防守
+    pub behaviour: BehaviourConfig,
+}
+
+impl RobotConfig {
+    /// 用命令行参数覆盖配置
+    pub fn override_with(&mut self, launch_args: &LaunchArgs) {
+        if let Some(robot_id) = launch_args.robot_id {
+            self.robot_id = robot_id;
+        }
+        if let Some(coach_ip) = launch_args.coach_ip {
+            self.network.coach_ip = coach_ip;
+        }
+        if let Some(coach_port) = launch_args.coach_port {
+            self.network.coach_port = coach_port;
+        }
+    }
+}
+
+impl Default for RobotConfig {
+    fn default() -> Self {
+        Self {
+            robot_id: 1,
+            network: Default::default(),
+            field_data: Default::default(),
+            chassis: Default::default(),
+            motion_limits: Default::default(),
+            wheel_control: Default::default(),
+            pose_control: Default::default(),
+            path_plan: Default::default(),
+            behaviour: Default::default(),
+        }
+    }
+}
+
+/// 机器人设置：网络
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RobotNetworkConfig {
+    /// 教练机IP地址
+    pub coach_ip: Ipv4Addr,
+    /// 教练机端口
+    pub coach_port: u16,
+    /// 本机绑定的IP地址，默认监听所有网卡
+    pub bind_ip: Ipv4Addr,
+    /// 本机绑定的端口
+    pub bind_port: u16,
+    /// 教练机以组播发送旧版数据包时加入的组播地址，广播或单播时为空
+    pub multicast_group: Option<Ipv4Addr>,
+    /// 向教练机发送数据的间隔，单位：毫秒
+    pub send_interval_ms: u64,
+    /// 超过此时间没有收到教练机数据包时原地停下，单位：毫秒
+    pub coach_timeout_ms: u64,
+    /// 协议：旧版、自动协商或新版
+    pub protocol: ProtocolMode,
+    /// 旧协议坐标系
+    pub legacy_frame: LegacyFrame,
+}
+
+impl Default for RobotNetworkConfig {
+    fn default() -> Self {
+        Self {
+            coach_ip: Ipv4Addr::new(10, 31, 1, 2),
+            coach_port: 20090,
+            bind_ip: Ipv4Addr::UNSPECIFIED,
+            bind_port: 20091,
+            multicast_group: None,
+            send_interval_ms: 30,
+            coach_timeout_ms: 1000,
+            protocol: Default::default(),
+            legacy_frame: Default::default(),
+        }
+    }
 }
 
 impl FastAccessData<'_> for RobotConfig {
-    fn file_path() -> &'static str {
-        #[dynamic]
-        static FILE_PATH: PathBuf = ROBOT_CONFIG_DIR.join("config.toml");
-        #[dynamic]
-        static FILE_PATH_STRING: String = FILE_PATH.to_string_lossy().to_string();
-        &FILE_PATH_STRING
+    fn file_path() -> BigHeroXResult<&'static str> {
+        #[dynamic(lazy)]
+        static FILE_PATH_STRING: BigHeroXResult<String> = ROBOT_CONFIG_DIR
+            .as_ref()
+            .map(|dir| dir.join("config.toml").to_string_lossy().to_string())
+            .map_err(Clone::clone);
+        FILE_PATH_STRING.as_deref().map_err(Clone::clone)
     }
 }
@@ -1,21 +1,36 @@
 pub mod mpu_data;
+pub mod mpu_decoder;
+pub mod orientation;
+#[cfg(unix)]
+pub mod virtual_mpu;
 
 use std::{
     io::{self, prelude::*},
-    path::PathBuf,
-    sync::Mutex,
-    time::{Duration, SystemTime},
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant, SystemTime},
 };
 
 use bevy::prelude::*;
-use mio::{Events, Interest, Poll, Token};
-use mio_serial::{SerialPortBuilderExt, SerialPortInfo, SerialPortType, SerialStream};
+use mio::{Events, Poll, Token};
+use mio_serial::SerialStream;
 use serde::{Deserialize, Serialize};
 use static_init::dynamic;
 
-use crate::{robot::ROBOT_CONFIG_DIR, traits::FastAccessData, TimeFlag};
+use crate::{
+    error::BigHeroXResult, launch_args::LaunchArgs, message_channel::ChannelBackoff,
+    robot::ROBOT_CONFIG_DIR, traits::FastAccessData, TimeFlag,
+};
+
+use self::{
+    mpu_decoder::{MPUFrameDecoder, MPUFrameStats},
+    orientation::{mpu_orientation_system, MPUScale},
+};
 
-use self::mpu_data::{MPURawData, MPU_DATA_BYTES_LENGTH};
+use super::serial::{
+    available_candidates,
+    link::{SerialConnectionState, SerialLink},
+    open_serial_stream, SerialSelector,
+};
 
 /*
 * Part: Plugin
@@ -25,15 +40,38 @@ pub(super) struct RobotMPUPlugin;
 
 impl Plugin for RobotMPUPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(MPUConfig::load_or_default())
+        // 读取配置文件（已插入配置资源时直接使用，如测试中），再用命令行参数覆盖
+        let mut config = app
+            .world
+            .remove_resource::<MPUConfig>()
+            .unwrap_or_else(MPUConfig::load_or_default);
+        if let Some(mpu_port) = app
+            .world
+            .get_resource::<LaunchArgs>()
+            .and_then(|launch_args| launch_args.mpu_port.clone())
+        {
+            config.serial_path = mpu_port;
+        }
+        app.insert_resource(config)
+            .init_resource::<MPUFrameDecoder>()
+            .init_resource::<MPUFrameStats>()
+            .init_resource::<MPULink>()
+            .init_resource::<MPUConnectionState>()
             .add_systems(Startup, enumerate_com_system)
-            .add_systems(FixedPreUpdate, connect_serial_port_system)
-            .add_systems(FixedPreUpdate, read_serial_port_system)
             .add_systems(
                 FixedPreUpdate,
-                read_buffer_system.after(read_serial_port_system),
+                (
+                    connect_serial_port_system,
+                    read_serial_port_system,
+                    read_buffer_system,
+                    update_connection_state_system,
+                )
+                    .chain(),
             )
-            .add_event::<MPUConnectEvent>();
+            .add_systems(FixedUpdate, mpu_orientation_system)
+            .add_event::<MPUConnectEvent>()
+            .add_event::<MPUDisConnectEvent>()
+            .add_event::<MPUFetchBufferEvent>();
     }
 }
 
@@ -41,18 +79,56 @@ impl Plugin for RobotMPUPlugin {
 * Part: Config
 */
 
-#[derive(Resource, Default, Serialize, Deserialize)]
-struct MPUConfig {
+#[derive(Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub(in crate::robot) struct MPUConfig {
+    /// USB串口的序列号
     serial_name: String,
+    /// 串口路径，如`COM5`、`/dev/ttyUSB0`、`/dev/serial/by-id/...`
+    serial_path: String,
+    /// USB的VID:PID（十六进制），如`1a86:7523`
+    usb_id: String,
+    /// 波特率
+    baud_rate: u32,
+    /// 超过此时间没有收到有效数据时，状态变为`Stale`，单位：毫秒
+    stale_timeout_ms: u64,
+    /// 超过此时间没有收到有效数据时，关闭串口并重新连接，单位：毫秒
+    stale_reconnect_ms: u64,
+    /// 打开或读取失败后重试的退避时间
+    reconnect: ChannelBackoff,
+    /// 原始数据的量程换算
+    scale: MPUScale,
+}
+
+impl Default for MPUConfig {
+    fn default() -> Self {
+        Self {
+            serial_name: String::new(),
+            serial_path: String::new(),
+            usb_id: String::new(),
+            baud_rate: 1_000_000,
+            stale_timeout_ms: 200,
+            stale_reconnect_ms: 2000,
+            reconnect: ChannelBackoff::default(),
+            scale: MPUScale::default(),
+        }
+    }
+}
+
+impl MPUConfig {
+    fn selector(&self) -> Option<SerialSelector> {
+        SerialSelector::from_config(&self.serial_path, &self.serial_name, &self.usb_id)
+    }
 }
 
 impl FastAccessData<'_> for MPUConfig {
-    fn file_path() -> &'static str {
-        #[dynamic]
-        static FILE_PATH: PathBuf = ROBOT_CONFIG_DIR.join("mpu.toml");
-        #[dynamic]
-        static FILE_PATH_STRING: String = FILE_PATH.to_string_lossy().to_string();
-        &FILE_PATH_STRING
+    fn file_path() -> BigHeroXResult<&'static str> {
+        #[dynamic(lazy)]
+        static FILE_PATH_STRING: BigHeroXResult<String> = ROBOT_CONFIG_DIR
+            .as_ref()
+            .map(|dir| dir.join("mpu.toml").to_string_lossy().to_string())
+            .map_err(Clone::clone);
+        FILE_PATH_STRING.as_deref().map_err(Clone::clone)
     }
 }
 
@@ -60,36 +136,38 @@ impl FastAccessData<'_> for MPUConfig {
 * Part: Event
 */
 
-#[derive(Event)]
+/// 连接MPU串口
+#[derive(Event, Default)]
 pub(super) struct MPUConnectEvent {
-    pub com: usize,
+    /// 指定的串口路径，为`None`时按配置文件选择
+    pub path: Option<String>,
 }
 
+/// 关闭MPU串口，不再自动重连
 #[derive(Event)]
-#[allow(unused)]
 pub(super) struct MPUDisConnectEvent;
 
-fn enumerate_com_system() {
-    let Ok(available_ports) = mio_serial::available_ports() else {
-        warn!("Failed to Enumerate COM Ports!");
-        return;
-    };
-    info!("Available ports: {:?}", available_ports);
-    for port in available_ports {
-        let SerialPortInfo {
-            port_name,
-            port_type,
-        } = port;
-        let SerialPortType::UsbPort(usb_port_info) = port_type else {
-            continue;
-        };
-        let Some(serial_name) = usb_port_info.serial_number else {
-            continue;
-        };
-        println!("Port: {} Serial Name: {}", port_name, serial_name);
+/// 列出串口，配置了串口时自动连接
+fn enumerate_com_system(
+    config: Res<MPUConfig>,
+    mut connect_event_writer: EventWriter<MPUConnectEvent>,
+) {
+    for candidate in available_candidates() {
+        info!("Available port: {candidate}");
+    }
+    if config.selector().is_some() {
+        connect_event_writer.send_default();
     }
 }
 
+/// MPU连接状态，供界面与逻辑部分查询
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deref, Resource)]
+pub struct MPUConnectionState(pub SerialConnectionState);
+
+/// MPU串口的连接记录
+#[derive(Debug, Default, Deref, DerefMut, Resource)]
+struct MPULink(SerialLink);
+
 #[derive(Resource)]
 pub(super) struct MPUSerialModule {
     poll: Poll,
@@ -99,54 +177,105 @@ pub(super) struct MPUSerialModule {
 
 const SERIAL_TOKEN: Token = Token(0);
 
+/// 处理连接请求，并在断开后按退避时间重试
 fn connect_serial_port_system(
     mut commands: Commands,
+    config: Res<MPUConfig>,
+    module: Option<Res<MPUSerialModule>>,
+    mut link: ResMut<MPULink>,
+    mut decoder: ResMut<MPUFrameDecoder>,
     mut connect_events: EventReader<MPUConnectEvent>,
+    mut disconnect_events: EventReader<MPUDisConnectEvent>,
 ) {
+    let now = Instant::now();
+    let mut opened = module.is_some();
     // Read event
-    let Some(connect_event) = connect_events.read().next() else {
+    if let Some(connect_event) = connect_events.read().last() {
+        link.request(connect_event.path.clone(), now);
+        opened = false;
+    }
+    if disconnect_events.read().last().is_some() {
+        info!("Closing MPU serial port");
+        link.cancel();
+        opened = false;
+    }
+    if !opened {
+        commands.remove_resource::<MPUSerialModule>();
+    }
+
+    // 长时间没有有效数据：可能已经拔出，但没有报错
+    let stale_reconnect = Duration::from_millis(config.stale_reconnect_ms);
+    if opened
+        && link
+            .silence(now)
+            .is_some_and(|silence| silence >= stale_reconnect)
+    {
+        warn!("No valid MPU data in {stale_reconnect:?}, reconnecting");
+        commands.remove_resource::<MPUSerialModule>();
+        link.failed("No valid data".to_string(), now, &config.reconnect);
         return;
-    };
+    }
 
-    // Create a poll instance.
-    let poll = match Poll::new() {
-        Ok(poll) => poll,
+    if opened {
+        return;
+    }
+    let Some(path) = link.due(now).cloned() else {
+        return;
+    };
+    match open_serial_port(path, &config) {
+        Ok(module) => {
+            commands.insert_resource(module);
+            // 丢弃上次连接留下的半帧
+            decoder.reset();
+            link.opened(now);
+        }
         Err(err) => {
-            warn!("Failed to create pull using mio! {err:?}");
-            return;
+            warn!("{err}");
+            link.failed(err, now, &config.reconnect);
         }
-    };
+    }
+}
 
+fn open_serial_port(path: Option<String>, config: &MPUConfig) -> Result<MPUSerialModule, String> {
     // Create storage for events. Since we will only register a single serialport, a
     // capacity of 1 will do.
     let events = Events::with_capacity(1);
 
-    // Create the serial port
-    info!("Opening COM{}", connect_event.com);
-    let path = format!("COM{}", connect_event.com);
-    let mut rx = match mio_serial::new(path, 1_000_000)
-        .baud_rate(1_000_000)
-        .data_bits(mio_serial::DataBits::Eight)
-        .stop_bits(mio_serial::StopBits::One)
-        .parity(mio_serial::Parity::None)
-        .open_native_async()
-    {
-        Ok(rx) => rx,
-        Err(err) => {
-            warn!("Failed to open serial device using mio! {err:?}");
-            return;
+    // Select the serial port
+    let path = match path {
+        Some(path) => path,
+        None => {
+            let selector = config
+                .selector()
+                .ok_or_else(|| "No MPU serial port configured!".to_string())?;
+            selector
+                .resolve(&available_candidates())
+                .ok_or_else(|| format!("No serial port matches {selector}!"))?
         }
     };
 
-    poll.registry()
-        .register(&mut rx, SERIAL_TOKEN, Interest::READABLE)
-        .unwrap();
-
-    commands.insert_resource(MPUSerialModule {
+    let (poll, stream) = open_serial_stream(&path, config.baud_rate, SERIAL_TOKEN)?;
+    Ok(MPUSerialModule {
         poll,
         events,
-        stream: Mutex::new(rx),
-    });
+        stream: Mutex::new(stream),
+    })
+}
+
+/// 更新`MPUConnectionState`
+fn update_connection_state_system(
+    config: Res<MPUConfig>,
+    link: Res<MPULink>,
+    mut state: ResMut<MPUConnectionState>,
+) {
+    let new_state = link.state(
+        Instant::now(),
+        Duration::from_millis(config.stale_timeout_ms),
+    );
+    if **state != new_state {
+        info!("MPU connection: {new_state:?}");
+        *state = MPUConnectionState(new_state);
+    }
 }
 
 /*
@@ -160,7 +289,10 @@ struct MPUFetchBufferEvent {
 }
 
 fn read_serial_port_system(
+    mut commands: Commands,
+    config: Res<MPUConfig>,
     module: Option<ResMut<MPUSerialModule>>,
+    mut link: ResMut<MPULink>,
     mut fetch_event_writer: EventWriter<MPUFetchBufferEvent>,
 ) {
     let Some(mut module) = module else {
@@ -173,64 +305,67 @@ fn read_serial_port_system(
     } = module.as_mut();
     let stream = &mut *stream_mutex
         .get_mut()
-        .expect("Failed to get lock on MPU mio!!!");
+        .unwrap_or_else(PoisonError::into_inner);
     let mut buf = [0u8; 1024];
 
-    // Poll to check if we have events waiting for us.
-    poll.poll(events, None)
-        .expect("Failed to pull events on MPU mio!!!");
+    // Poll to check if we have events waiting for us, without blocking.
+    let mut error = poll
+        .poll(events, Some(Duration::ZERO))
+        .err()
+        .map(|err| format!("Failed to poll events on MPU mio! {err}"));
 
     // Process each event.
-    'read_events: for event in events.iter() {
-        let token: Token = event.token();
-        // Validate the token we registered our socket with,
-        // in this example it will only ever be one but we
-        // make sure it's valid none the less.
-        match token {
-            SERIAL_TOKEN => 'read_this_event: loop {
-                // In this loop we receive all packets queued for the socket.
-                match stream.read(&mut buf) {
-                    Ok(count) => {
-                        fetch_event_writer.send(MPUFetchBufferEvent { buf, count });
-                    }
-                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-                        break 'read_this_event;
-                    }
-                    Err(e) => {
-                        println!("Quitting due to read error: {}", e);
-                        break 'read_events;
-                    }
+    for event in events.iter() {
+        if event.token() != SERIAL_TOKEN {
+            continue;
+        }
+        // In this loop we receive all data queued for the port.
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => break,
+                Ok(count) => {
+                    fetch_event_writer.send(MPUFetchBufferEvent { buf, count });
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    error = Some(format!("Failed to read MPU serial port! {err}"));
+                    break;
                 }
-            },
-            _ => {
-                // This should never happen as we only registered our
-                // `UdpSocket` using the `UDP_SOCKET` token, but if it ever
-                // does we'll log it.
-                // warn!("Got event for unexpected token: {:?}", event);
             }
         }
+        // 设备拔出时，串口被挂断
+        if error.is_none() && (event.is_error() || event.is_read_closed()) {
+            error = Some("MPU serial port closed".to_string());
+        }
+    }
+
+    if let Some(error) = error {
+        warn!("{error}");
+        commands.remove_resource::<MPUSerialModule>();
+        link.failed(error, Instant::now(), &config.reconnect);
     }
 }
 
 fn read_buffer_system(
     mut commands: Commands,
+    mut link: ResMut<MPULink>,
+    mut decoder: ResMut<MPUFrameDecoder>,
+    mut stats: ResMut<MPUFrameStats>,
     mut fetch_buffer_event: EventReader<MPUFetchBufferEvent>,
 ) {
-    let Some(MPUFetchBufferEvent { buf, count }) = fetch_buffer_event.read().last() else {
-        return;
-    };
-    let Some(data) = std::iter::repeat(0)
-        .take(count / MPU_DATA_BYTES_LENGTH)
-        .enumerate()
-        .map(|(index, _)| {
-            &buf[(index * MPU_DATA_BYTES_LENGTH)..((index + 1) * MPU_DATA_BYTES_LENGTH)]
-        })
-        .filter_map(|buf_part| MPURawData::from_raw_parts(buf_part.iter()))
-        .last()
-    else {
-        warn!("MPU: Wrong data format! Buf: {:?}", buf);
+    // 按顺序处理所有读到的数据，半帧留到下次
+    let last_data = fetch_buffer_event
+        .read()
+        .flat_map(|MPUFetchBufferEvent { buf, count }| decoder.push(&buf[..*count]))
+        .last();
+    if **stats != decoder.stats() {
+        **stats = decoder.stats();
+    }
+    let Some(data) = last_data else {
         return;
     };
+    link.received(Instant::now());
     commands.spawn((
         data,
         TimeFlag {
@@ -2,9 +2,22 @@ use bevy::prelude::*;
 use glam::{I16Vec3, I16Vec4, Vec3Swizzles, Vec4Swizzles};
 use primitive_byte_iter::ByteIter;
 
+/// 数据帧格式（大端）：
+/// - 包头：`0x55 0xAA`
+/// - 数据：10个值，依次为四元数w、x、y、z，角速度x、y、z，加速度x、y、z。
+///   每个值占4字节，前2字节为i16数值，后2字节发送时为0，接收时忽略
+/// - 校验和：1字节，见`mpu_checksum`
 pub const MPU_DATA_HEADER: [u8; 2] = [0x55, 0xAA];
 pub const MPU_DATA_BYTES_LENGTH: usize = 2 + 40 + 1;
 
+/// 校验和：包头与数据全部字节之和的低8位。
+/// 由于`0x55 + 0xAA = 0xFF`，也等价于以`0xFF`为初值累加数据部分。
+pub fn mpu_checksum<'a>(bytes: impl IntoIterator<Item = &'a u8>) -> u8 {
+    bytes
+        .into_iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Component)]
 pub struct MPURawData {
     pub quat: I16Vec4,
@@ -13,29 +26,30 @@ pub struct MPURawData {
 }
 
 impl MPURawData {
+    /// 解析一帧。包头、长度或校验和错误时返回`None`
     pub fn from_raw_parts<'a, T: Iterator<Item = &'a u8>>(data: T) -> Option<Self> {
         // 读取
-        let mut byte_iter = ByteIter::new(data);
-        let _ = byte_iter
-            .next_u16_be()
-            .map(|val| val.to_be_bytes())
-            .filter(|header| *header == MPU_DATA_HEADER)?;
-        let data: Vec<i16> = std::iter::repeat(0i16)
-            .take(20)
-            .filter_map(|_| byte_iter.next_i16_be())
-            .collect::<Vec<_>>();
-        let set_sum = byte_iter.next_u8()?;
-        // 处理
-        let sum = data
-            .iter()
-            .flat_map(|val| val.to_be_bytes())
-            .fold(0xffu8, |val, now| val.wrapping_add(now));
-        // let sum = 0;
-        let mut actual_data = data
-            .into_iter()
-            .enumerate()
-            .filter_map(|(count, value)| Some(value).filter(|_| count % 2 == 0));
-        // Check SUM
+        let mut bytes = [0u8; MPU_DATA_BYTES_LENGTH];
+        let mut count = 0;
+        for (slot, byte) in bytes.iter_mut().zip(data) {
+            *slot = *byte;
+            count += 1;
+        }
+        if count < MPU_DATA_BYTES_LENGTH {
+            return None;
+        }
+        let (set_sum, frame) = bytes.split_last()?;
+        // Check header & SUM
+        if !frame.starts_with(&MPU_DATA_HEADER) || mpu_checksum(frame) != *set_sum {
+            return None;
+        }
+        // 处理：每个值只取前2字节
+        let mut byte_iter = ByteIter::new(frame[MPU_DATA_HEADER.len()..].iter());
+        let mut actual_data = std::iter::from_fn(|| {
+            let value = byte_iter.next_i16_be();
+            let _padding = byte_iter.next_i16_be();
+            value
+        });
         Some(MPURawData {
             quat: {
                 let w = actual_data.next()?;
@@ -57,7 +71,6 @@ impl MPURawData {
                 actual_data.next()?,
             ),
         })
-        .filter(|_| sum == set_sum)
     }
 
     pub fn generate_bytes(&self) -> [u8; MPU_DATA_BYTES_LENGTH] {
@@ -74,9 +87,7 @@ impl MPURawData {
                     .flat_map(|val| val.to_be_bytes()),
             )
             .collect();
-        let sum = data_bytes
-            .iter()
-            .fold(0u8, |val, now| val.wrapping_add(*now));
+        let sum = mpu_checksum(&data_bytes);
         let data_vec = data_bytes.into_iter().chain([sum]).collect::<Vec<_>>();
         let mut data = [0; MPU_DATA_BYTES_LENGTH];
         data.copy_from_slice(data_vec.as_slice());
@@ -86,6 +97,8 @@ impl MPURawData {
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use super::*;
 
     #[test]
@@ -114,4 +127,55 @@ mod tests {
         let new_bytes = new_data.generate_bytes();
         assert_eq!(new_bytes, origin_slice);
     }
+
+    #[test]
+    fn golden_checksum() {
+        let data = MPURawData {
+            quat: I16Vec4::new(0, 0, 0, 0x0102),
+            gyro: I16Vec3::new(0, 0, -1),
+            acc: I16Vec3::new(0x1000, 0, 0),
+        };
+        let bytes = data.generate_bytes();
+        assert_eq!(&bytes[..6], &[0x55, 0xAA, 0x01, 0x02, 0x00, 0x00]);
+        // 0x55 + 0xAA + 0x01 + 0x02 + 0xFF + 0xFF + 0x10 = 0x0310
+        assert_eq!(bytes[MPU_DATA_BYTES_LENGTH - 1], 0x10);
+        // 两种算法等价
+        let payload_sum = bytes[2..MPU_DATA_BYTES_LENGTH - 1]
+            .iter()
+            .fold(0xFFu8, |sum, byte| sum.wrapping_add(*byte));
+        assert_eq!(payload_sum, 0x10);
+        assert_eq!(MPURawData::from_raw_parts(bytes.iter()), Some(data));
+        // 长度不足
+        assert_eq!(MPURawData::from_raw_parts(bytes[..42].iter()), None);
+    }
+
+    fn arb_raw_data() -> impl Strategy<Value = MPURawData> {
+        (any::<[i16; 4]>(), any::<[i16; 3]>(), any::<[i16; 3]>()).prop_map(|(quat, gyro, acc)| {
+            MPURawData {
+                quat: I16Vec4::from_array(quat),
+                gyro: I16Vec3::from_array(gyro),
+                acc: I16Vec3::from_array(acc),
+            }
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn round_convert(data in arb_raw_data()) {
+            let bytes = data.generate_bytes();
+            prop_assert_eq!(MPURawData::from_raw_parts(bytes.iter()), Some(data));
+        }
+
+        /// 任意单个字节出错都会被发现
+        #[test]
+        fn reject_corrupted(
+            data in arb_raw_data(),
+            index in 0..MPU_DATA_BYTES_LENGTH,
+            mask in 1u8..=u8::MAX,
+        ) {
+            let mut bytes = data.generate_bytes();
+            bytes[index] ^= mask;
+            prop_assert_eq!(MPURawData::from_raw_parts(bytes.iter()), None);
+        }
+    }
 }
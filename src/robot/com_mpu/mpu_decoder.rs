@@ -0,0 +1,114 @@
+//! MPU串口数据流解码，分帧见`robot::serial::frame`
+
+use bevy::prelude::*;
+
+use super::mpu_data::{MPURawData, MPU_DATA_BYTES_LENGTH, MPU_DATA_HEADER};
+use crate::robot::serial::frame::{FrameCheck, FrameDecoder, FrameFormat, FrameStats};
+
+/// MPU数据帧：定长，由`MPURawData::from_raw_parts`校验
+#[derive(Debug)]
+pub struct MPUFrame;
+
+impl FrameFormat for MPUFrame {
+    type Frame = MPURawData;
+    const HEADER: [u8; 2] = MPU_DATA_HEADER;
+    const HEAD_LEN: usize = MPU_DATA_BYTES_LENGTH;
+
+    fn frame_len(_head: &[u8]) -> Option<usize> {
+        Some(MPU_DATA_BYTES_LENGTH)
+    }
+
+    fn check(frame: &[u8]) -> FrameCheck<MPURawData> {
+        match MPURawData::from_raw_parts(frame.iter()) {
+            Some(data) => FrameCheck::Good(data),
+            None => FrameCheck::BadChecksum,
+        }
+    }
+}
+
+/// 解码统计，供界面显示
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deref, DerefMut, Resource)]
+pub struct MPUFrameStats(pub FrameStats);
+
+/// 流式解码器
+#[derive(Debug, Default, Deref, DerefMut, Resource)]
+pub struct MPUFrameDecoder(FrameDecoder<MPUFrame>);
+
+#[cfg(test)]
+mod tests {
+    use glam::{I16Vec3, I16Vec4};
+
+    use super::*;
+
+    fn frame(seed: i16) -> MPURawData {
+        MPURawData {
+            quat: I16Vec4::new(seed, -seed, 2 * seed, 16000),
+            gyro: I16Vec3::new(seed, 3, -3),
+            acc: I16Vec3::new(0, -seed, 16384),
+        }
+    }
+
+    fn stream(frames: &[MPURawData]) -> Vec<u8> {
+        frames
+            .iter()
+            .flat_map(|frame| frame.generate_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn split_reads() {
+        let frames: Vec<_> = (1..=5).map(frame).collect();
+        let bytes = stream(&frames);
+        // 各种切分方式
+        for chunk_len in [
+            1,
+            7,
+            MPU_DATA_BYTES_LENGTH - 1,
+            MPU_DATA_BYTES_LENGTH + 5,
+            1024,
+        ] {
+            let mut decoder = MPUFrameDecoder::default();
+            let decoded: Vec<_> = bytes
+                .chunks(chunk_len)
+                .flat_map(|chunk| decoder.push(chunk))
+                .collect();
+            assert_eq!(decoded, frames, "chunk_len: {chunk_len}");
+            assert_eq!(decoder.stats().good, 5);
+            assert_eq!(decoder.stats().resync, 0);
+        }
+    }
+
+    #[test]
+    fn misaligned_and_corrupted() {
+        let frames: Vec<_> = (1..=3).map(frame).collect();
+        // 开头多一个字节、中间一帧校验错误、结尾半帧
+        let mut bytes = vec![0x12];
+        bytes.extend(frames[0].generate_bytes());
+        let mut corrupted = frames[1].generate_bytes();
+        corrupted[10] ^= 0xFF;
+        bytes.extend(corrupted);
+        bytes.extend(frames[2].generate_bytes());
+        bytes.extend(&frames[0].generate_bytes()[..20]);
+        let mut decoder = MPUFrameDecoder::default();
+        assert_eq!(decoder.push(&bytes), vec![frames[0], frames[2]]);
+        let stats = decoder.stats();
+        assert_eq!(stats.good, 2);
+        assert_eq!(stats.bad_checksum, 1);
+        assert!(stats.resync >= 2);
+        // 补全最后半帧
+        assert_eq!(
+            decoder.push(&frames[0].generate_bytes()[20..]),
+            vec![frames[0]]
+        );
+    }
+
+    #[test]
+    fn garbage_only() {
+        let mut decoder = MPUFrameDecoder::default();
+        assert!(decoder.push(&[0x00, 0x11, 0x22, 0x55]).is_empty());
+        // 包头跨越两次读取
+        let bytes = frame(9).generate_bytes();
+        assert_eq!(decoder.push(&bytes[1..]), vec![frame(9)]);
+        assert_eq!(decoder.stats().skipped_bytes, 3);
+    }
+}
@@ -0,0 +1,182 @@
+//! MPU姿态处理：原始数据 → 物理量 → 场地坐标系下的朝向
+
+use std::{f32::consts::PI, time::SystemTime};
+
+use bevy::prelude::*;
+use glam::{EulerRot, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{field::world_state::wrap_angle, robot::panorama_camera::PanoramaEntryData, TimeFlag};
+
+use super::mpu_data::MPURawData;
+
+/// 重力加速度，单位：米每二次方秒
+pub const STANDARD_GRAVITY: f32 = 9.80665;
+
+/// 原始数据的量程换算，与MPU固件的设置对应
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MPUScale {
+    /// 四元数各分量为1.0时的原始值（Q14定点数）
+    pub quat_lsb: f32,
+    /// 角速度每度每秒的原始值（量程±2000°/s）
+    pub gyro_lsb_per_dps: f32,
+    /// 加速度每g的原始值（量程±2g）
+    pub acc_lsb_per_g: f32,
+}
+
+impl Default for MPUScale {
+    fn default() -> Self {
+        Self {
+            quat_lsb: 16384.0,
+            gyro_lsb_per_dps: 16.4,
+            acc_lsb_per_g: 16384.0,
+        }
+    }
+}
+
+/// 换算后的MPU数据
+/// 坐标系：MPU自身坐标系，z轴向上
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MPUData {
+    /// 姿态，已归一化
+    pub quat: Quat,
+    /// 角速度，单位：弧度每秒
+    pub gyro_rps: Vec3,
+    /// 加速度，单位：米每二次方秒
+    pub acc_mps2: Vec3,
+}
+
+impl MPUData {
+    /// 换算原始数据。四元数全为0（如MPU尚未初始化完成）时返回`None`
+    pub fn from_raw(raw: &MPURawData, scale: &MPUScale) -> Option<Self> {
+        let quat = Quat::from_vec4(raw.quat.as_vec4() / scale.quat_lsb);
+        if quat.length_squared() < f32::EPSILON {
+            return None;
+        }
+        Some(Self {
+            quat: quat.normalize(),
+            gyro_rps: raw.gyro.as_vec3() / scale.gyro_lsb_per_dps * (PI / 180.0),
+            acc_mps2: raw.acc.as_vec3() / scale.acc_lsb_per_g * STANDARD_GRAVITY,
+        })
+    }
+
+    /// 绕z轴的转角，单位：弧度（逆时针为正）
+    pub fn yaw(&self) -> f32 {
+        self.quat.to_euler(EulerRot::ZYX).0
+    }
+}
+
+/// 机器人朝向，供定位与运动控制使用
+/// 坐标系：场地中心为零点，敌方球门方向为x轴正方向，正前方朝着敌方球门时，左侧为y轴正方向
+#[derive(Debug, Clone, Copy, PartialEq, Resource)]
+pub struct RobotHeading {
+    /// 正前方在场地坐标系中的角度，单位：弧度（东侧为0，增加方向为逆时针），范围(-PI, PI]
+    pub yaw: f32,
+    /// 绕z轴的角速度，单位：弧度每秒（逆时针为正）
+    pub yaw_rate: f32,
+    /// 换算后的MPU数据
+    pub mpu: MPUData,
+    /// 对应的MPU数据的接收时间
+    pub update_time: SystemTime,
+}
+
+/// MPU读数转为场地坐标系下的朝向：入场时MPU读数为`entry_angle_z`，对应场地角度`set_entry_angle`
+pub fn field_yaw(mpu_yaw: f32, entry: &PanoramaEntryData) -> f32 {
+    wrap_angle(mpu_yaw - entry.entry_angle_z + entry.set_entry_angle)
+}
+
+/// 处理最新的MPU数据，更新`RobotHeading`
+pub(in crate::robot) fn mpu_orientation_system(
+    mut commands: Commands,
+    config: Res<super::MPUConfig>,
+    entry_data: Option<Res<PanoramaEntryData>>,
+    raw_data_query: Query<(&MPURawData, &TimeFlag), Added<MPURawData>>,
+) {
+    let Some((raw_data, time_flag)) = raw_data_query
+        .iter()
+        .max_by_key(|(_, time_flag)| time_flag.spawn_time)
+    else {
+        return;
+    };
+    let Some(mpu) = MPUData::from_raw(raw_data, &config.scale) else {
+        return;
+    };
+    let entry = entry_data.as_deref().copied().unwrap_or_default();
+    commands.insert_resource(RobotHeading {
+        yaw: field_yaw(mpu.yaw(), &entry),
+        yaw_rate: mpu.gyro_rps.z,
+        mpu,
+        update_time: time_flag.spawn_time,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use glam::{I16Vec3, I16Vec4, Vec4};
+
+    use super::*;
+
+    fn raw_from_quat(quat: Quat, scale: &MPUScale) -> MPURawData {
+        let quat = Vec4::from(quat) * scale.quat_lsb;
+        MPURawData {
+            quat: I16Vec4::new(
+                quat.x.round() as i16,
+                quat.y.round() as i16,
+                quat.z.round() as i16,
+                quat.w.round() as i16,
+            ),
+            gyro: I16Vec3::ZERO,
+            acc: I16Vec3::ZERO,
+        }
+    }
+
+    #[test]
+    fn scale_raw_data() {
+        let scale = MPUScale::default();
+        let raw = MPURawData {
+            quat: I16Vec4::new(0, 0, 0, 16383),
+            // 90°/s，绕z轴
+            gyro: I16Vec3::new(0, 0, 1476),
+            // 1g，z轴向上
+            acc: I16Vec3::new(0, 0, 16384),
+        };
+        let data = MPUData::from_raw(&raw, &scale).expect("Failed to scale MPU data!");
+        assert_relative_eq!(data.quat.length(), 1.0, epsilon = 1e-6);
+        assert_relative_eq!(data.yaw(), 0.0, epsilon = 1e-4);
+        assert_relative_eq!(data.gyro_rps.z, PI / 2.0, epsilon = 1e-4);
+        assert_relative_eq!(data.acc_mps2.z, STANDARD_GRAVITY, epsilon = 1e-4);
+        // 未初始化
+        assert_eq!(MPUData::from_raw(&MPURawData::default(), &scale), None);
+    }
+
+    #[test]
+    fn yaw_from_quat() {
+        let scale = MPUScale::default();
+        for angle in [-3.0f32, -PI / 2.0, -0.3, 0.0, 0.7, PI / 2.0, 3.0] {
+            // 叠加少量俯仰与横滚，不影响转角
+            let quat = Quat::from_euler(EulerRot::ZYX, angle, 0.05, -0.05);
+            let data = MPUData::from_raw(&raw_from_quat(quat, &scale), &scale)
+                .expect("Failed to scale MPU data!");
+            assert_relative_eq!(data.yaw(), angle, epsilon = 1e-3);
+        }
+    }
+
+    #[test]
+    fn entry_offset() {
+        let entry = PanoramaEntryData {
+            set_entry_pos: Default::default(),
+            // 入场时面朝敌方球门左侧
+            set_entry_angle: PI / 2.0,
+            // 入场时MPU读数
+            entry_angle_z: 0.4,
+        };
+        assert_relative_eq!(field_yaw(0.4, &entry), PI / 2.0);
+        assert_relative_eq!(field_yaw(0.4 - PI / 2.0, &entry), 0.0, epsilon = 1e-6);
+        // 跨过±PI
+        assert_relative_eq!(field_yaw(0.4 + PI, &entry), -PI / 2.0, epsilon = 1e-5);
+        assert_relative_eq!(wrap_angle(-PI), PI);
+        assert_relative_eq!(wrap_angle(3.0 * PI), PI, epsilon = 1e-5);
+    }
+}
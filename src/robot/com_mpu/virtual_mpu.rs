@@ -0,0 +1,341 @@
+//! 虚拟MPU：通过伪终端（PTY）模拟MPU模块，用于没有实物时测试`RobotMPUPlugin`
+//!
+//! 主端由后台线程按设定的帧率写入`MPURawData::generate_bytes`生成的数据，
+//! 从端的路径（如`/dev/pts/3`）作为`serial_path`交给插件打开。
+
+use std::{
+    io::{self, prelude::*},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use glam::{EulerRot, I16Vec3, I16Vec4, Quat, Vec3};
+use mio_serial::{SerialPort, SerialStream};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::field::world_state::wrap_angle;
+
+use super::{mpu_data::MPURawData, orientation::MPUScale};
+
+/// 虚拟MPU的设置
+#[derive(Debug, Clone)]
+pub struct VirtualMPUConfig {
+    /// 发送帧率，单位：赫兹，须大于0
+    pub rate_hz: f32,
+    /// 姿态脚本：(开始发送后的时刻, 姿态)，按时刻升序排列。之间球面插值，最后一个之后保持不变
+    pub script: Vec<(Duration, Quat)>,
+    /// 叠加在各原始值上的均匀噪声幅度，单位：LSB
+    pub noise_lsb: i16,
+    /// 每个字节被丢弃的概率
+    pub drop_byte_probability: f64,
+    /// 每帧校验和被破坏的概率
+    pub corrupt_probability: f64,
+    /// 原始数据的量程换算，应与插件的设置一致
+    pub scale: MPUScale,
+    /// 随机数种子
+    pub seed: u64,
+}
+
+impl Default for VirtualMPUConfig {
+    fn default() -> Self {
+        Self {
+            rate_hz: 200.0,
+            script: Vec::new(),
+            noise_lsb: 0,
+            drop_byte_probability: 0.0,
+            corrupt_probability: 0.0,
+            scale: MPUScale::default(),
+            seed: 0,
+        }
+    }
+}
+
+impl VirtualMPUConfig {
+    /// 时刻`time`的姿态
+    pub fn orientation(&self, time: Duration) -> Quat {
+        match self
+            .script
+            .iter()
+            .position(|(key_time, _)| *key_time > time)
+        {
+            None => self.script.last().map_or(Quat::IDENTITY, |(_, quat)| *quat),
+            Some(0) => self.script[0].1,
+            Some(index) => {
+                let (start_time, start) = self.script[index - 1];
+                let (end_time, end) = self.script[index];
+                let ratio =
+                    (time - start_time).as_secs_f32() / (end_time - start_time).as_secs_f32();
+                start.slerp(end, ratio)
+            }
+        }
+    }
+
+    /// 生成时刻`time`的原始数据，角速度由到`time + period`的姿态变化求得
+    pub fn raw_data(&self, time: Duration, period: Duration, rng: &mut impl Rng) -> MPURawData {
+        let quat = self.orientation(time);
+        let yaw = |quat: Quat| quat.to_euler(EulerRot::ZYX).0;
+        let yaw_rate =
+            wrap_angle(yaw(self.orientation(time + period)) - yaw(quat)) / period.as_secs_f32();
+        // 静止时只受重力，转到MPU自身坐标系
+        let acc = quat.inverse() * Vec3::Z;
+
+        let mut to_raw = |value: f32| {
+            let noise = match self.noise_lsb {
+                0 => 0,
+                noise_lsb => rng.gen_range(-noise_lsb.abs()..=noise_lsb.abs()),
+            };
+            (value.round() as i32 + noise as i32).clamp(i16::MIN as i32, i16::MAX as i32) as i16
+        };
+        let quat = quat * self.scale.quat_lsb;
+        let gyro_z = yaw_rate.to_degrees() * self.scale.gyro_lsb_per_dps;
+        let acc = acc * self.scale.acc_lsb_per_g;
+        MPURawData {
+            quat: I16Vec4::new(
+                to_raw(quat.x),
+                to_raw(quat.y),
+                to_raw(quat.z),
+                to_raw(quat.w),
+            ),
+            gyro: I16Vec3::new(to_raw(0.0), to_raw(0.0), to_raw(gyro_z)),
+            acc: I16Vec3::new(to_raw(acc.x), to_raw(acc.y), to_raw(acc.z)),
+        }
+    }
+}
+
+/// 运行中的虚拟MPU，drop时停止发送并关闭伪终端
+pub struct VirtualMPU {
+    path: String,
+    stop: Arc<AtomicBool>,
+    sent_frames: Arc<AtomicU64>,
+    thread: Option<JoinHandle<()>>,
+    /// 保持从端打开，插件断开重连期间伪终端不会被挂断
+    _slave: SerialStream,
+}
+
+impl VirtualMPU {
+    /// 创建伪终端并开始发送
+    pub fn spawn(config: VirtualMPUConfig) -> mio_serial::Result<Self> {
+        let (mut master, slave) = SerialStream::pair()?;
+        let path = slave.name().ok_or_else(|| {
+            mio_serial::Error::new(
+                mio_serial::ErrorKind::NoDevice,
+                "Failed to get the path of the pseudo terminal",
+            )
+        })?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let sent_frames = Arc::new(AtomicU64::new(0));
+        let thread = {
+            let stop = stop.clone();
+            let sent_frames = sent_frames.clone();
+            thread::spawn(move || {
+                let period = Duration::from_secs_f32(1.0 / config.rate_hz);
+                let mut rng = StdRng::seed_from_u64(config.seed);
+                let start = Instant::now();
+                let mut next = start;
+                while !stop.load(Ordering::Relaxed) {
+                    let mut bytes = config
+                        .raw_data(next - start, period, &mut rng)
+                        .generate_bytes()
+                        .to_vec();
+                    if rng.gen_bool(config.corrupt_probability.clamp(0.0, 1.0)) {
+                        if let Some(checksum) = bytes.last_mut() {
+                            *checksum ^= 0xFF;
+                        }
+                    }
+                    let drop_probability = config.drop_byte_probability.clamp(0.0, 1.0);
+                    bytes.retain(|_| !rng.gen_bool(drop_probability));
+                    match master.write_all(&bytes) {
+                        Ok(()) => {
+                            sent_frames.fetch_add(1, Ordering::Relaxed);
+                        }
+                        // 对方没有及时读取，缓冲区已满：丢弃这一帧
+                        Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                        Err(_) => break,
+                    }
+                    next += period;
+                    thread::sleep(next.saturating_duration_since(Instant::now()));
+                }
+            })
+        };
+        Ok(Self {
+            path,
+            stop,
+            sent_frames,
+            thread: Some(thread),
+            _slave: slave,
+        })
+    }
+
+    /// 从端路径，作为串口路径使用
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// 已写入的帧数
+    pub fn sent_frames(&self) -> u64 {
+        self.sent_frames.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for VirtualMPU {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use approx::assert_relative_eq;
+    use bevy::{app::App, ecs::world::World, MinimalPlugins};
+
+    use crate::robot::{
+        com_mpu::{
+            mpu_decoder::MPUFrameStats, orientation::RobotHeading, MPUConfig, MPUConnectionState,
+            RobotMPUPlugin,
+        },
+        serial::link::SerialConnectionState,
+    };
+
+    use super::*;
+
+    fn yaw_script(keys: &[(u64, f32)]) -> Vec<(Duration, Quat)> {
+        keys.iter()
+            .map(|(millis, yaw)| (Duration::from_millis(*millis), Quat::from_rotation_z(*yaw)))
+            .collect()
+    }
+
+    fn mpu_app(path: &str) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(MPUConfig {
+                serial_path: path.to_string(),
+                ..Default::default()
+            })
+            .add_plugins(RobotMPUPlugin);
+        app
+    }
+
+    /// 运行`app`直到`condition`成立，超时返回`false`
+    fn update_until(app: &mut App, condition: impl Fn(&World) -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            app.update();
+            if condition(&app.world) {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(2));
+        }
+        false
+    }
+
+    fn heading_near(world: &World, yaw: f32) -> bool {
+        world
+            .get_resource::<RobotHeading>()
+            .is_some_and(|heading| wrap_angle(heading.yaw - yaw).abs() < 0.01)
+    }
+
+    #[test]
+    fn script_orientation() {
+        let config = VirtualMPUConfig {
+            script: yaw_script(&[(100, 0.0), (300, 1.0)]),
+            ..Default::default()
+        };
+        let yaw = |millis| {
+            config
+                .orientation(Duration::from_millis(millis))
+                .to_euler(EulerRot::ZYX)
+                .0
+        };
+        assert_relative_eq!(yaw(0), 0.0);
+        assert_relative_eq!(yaw(200), 0.5, epsilon = 1e-5);
+        assert_relative_eq!(yaw(1000), 1.0, epsilon = 1e-5);
+        // 转动中的角速度
+        let mut rng = StdRng::seed_from_u64(0);
+        let raw = config.raw_data(
+            Duration::from_millis(150),
+            Duration::from_millis(10),
+            &mut rng,
+        );
+        let dps = raw.gyro.z as f32 / config.scale.gyro_lsb_per_dps;
+        assert_relative_eq!(dps, 5.0f32.to_degrees(), epsilon = 1.0);
+    }
+
+    #[test]
+    fn decode_heading() {
+        let mpu = VirtualMPU::spawn(VirtualMPUConfig {
+            script: yaw_script(&[(0, 0.7)]),
+            noise_lsb: 10,
+            ..Default::default()
+        })
+        .expect("Failed to create virtual MPU!");
+        let mut app = mpu_app(mpu.path());
+        assert!(
+            update_until(&mut app, |world| heading_near(world, 0.7)),
+            "sent frames: {}",
+            mpu.sent_frames()
+        );
+        assert_eq!(
+            **app.world.resource::<MPUConnectionState>(),
+            SerialConnectionState::Streaming
+        );
+        let stats = *app.world.resource::<MPUFrameStats>();
+        assert!(stats.good > 0);
+        assert_eq!(stats.bad_checksum, 0);
+    }
+
+    #[test]
+    fn scripted_turn_with_faults() {
+        let mpu = VirtualMPU::spawn(VirtualMPUConfig {
+            // 转过±PI
+            script: yaw_script(&[(0, 0.9 * PI), (300, 0.9 * PI), (500, 1.2 * PI)]),
+            noise_lsb: 20,
+            drop_byte_probability: 0.005,
+            corrupt_probability: 0.1,
+            seed: 16,
+            ..Default::default()
+        })
+        .expect("Failed to create virtual MPU!");
+        let mut app = mpu_app(mpu.path());
+        assert!(update_until(&mut app, |world| heading_near(
+            world,
+            0.9 * PI
+        )));
+        assert!(update_until(&mut app, |world| heading_near(
+            world,
+            -0.8 * PI
+        )));
+        // 等待足够多的帧，使丢字节与校验错误都出现过
+        assert!(update_until(&mut app, |world| {
+            let stats = world.resource::<MPUFrameStats>();
+            stats.good > 150 && stats.bad_checksum > 0 && stats.resync > 0
+        }));
+        assert!(heading_near(&app.world, -0.8 * PI));
+    }
+
+    #[test]
+    fn unplug() {
+        let mpu =
+            VirtualMPU::spawn(VirtualMPUConfig::default()).expect("Failed to create virtual MPU!");
+        let mut app = mpu_app(mpu.path());
+        assert!(update_until(&mut app, |world| {
+            **world.resource::<MPUConnectionState>() == SerialConnectionState::Streaming
+        }));
+        // 拔出：不再有数据，随后伪终端被关闭
+        drop(mpu);
+        assert!(update_until(&mut app, |world| {
+            matches!(
+                **world.resource::<MPUConnectionState>(),
+                SerialConnectionState::Stale | SerialConnectionState::Error(_)
+            )
+        }));
+    }
+}
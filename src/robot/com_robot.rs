@@ -1,25 +1,400 @@
 //! 来自机器人下位机的数据
 
-use bevy_ecs::prelude::*;
+pub mod lower_frame;
+
+use std::{
+    io::{self, prelude::*},
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use mio::{Events, Poll, Token};
+use mio_serial::SerialStream;
+use serde::{Deserialize, Serialize};
+use static_init::dynamic;
+
+use crate::{
+    error::BigHeroXResult, launch_args::LaunchArgs, message_channel::ChannelBackoff,
+    robot::ROBOT_CONFIG_DIR, traits::FastAccessData,
+};
+
+use self::lower_frame::{LowerCommand, LowerFrameDecoder, LowerFrameStats, LowerReport};
+
+use super::serial::{
+    available_candidates,
+    link::{SerialConnectionState, SerialLink},
+    open_serial_stream, SerialSelector,
+};
 
 pub const ADC_COUNT: usize = 5;
 pub const IO_COUNT: usize = 8;
 pub const MOTOR_COUNT: usize = 3;
-pub const ROBOT_MOTOR_ROUND_POS_DELTA: i32 = 2500;
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Resource)]
 pub struct RobotLowerData {
     pub adc: [u16; ADC_COUNT],
     pub io: [bool; IO_COUNT],
     pub motor_status: [RobotMotorStatus; MOTOR_COUNT],
+    /// 最近一次收到码盘数据的时间
+    pub encoder_time: Option<Instant>,
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Resource)]
 pub struct RobotMotorStatus {
     /// 电机码盘位置
-    /// 码盘是对电机转动角度进行计数的，这个数值变化2500表示电机转了一圈。
+    /// 码盘是对电机转动角度进行计数的，这个数值变化`ChassisConfig::encoder_ticks_per_rev`（默认2500）表示电机转了一圈。
     /// 这个值是连续变化的，参考绝对编码器的原理。
     pub rotate_pos: i32,
     // 电机电流，单位：毫安
     pub current: u16,
 }
+
+/*
+* Part: Plugin
+*/
+
+/// 下位机通信：发送`LowerCommand`事件中的指令，接收的数据写入`RobotLowerData`
+pub(super) struct RobotLowerPlugin;
+
+impl Plugin for RobotLowerPlugin {
+    fn build(&self, app: &mut App) {
+        // 读取配置文件（已插入配置资源时直接使用，如测试中），再用命令行参数覆盖
+        let mut config = app
+            .world
+            .remove_resource::<LowerConfig>()
+            .unwrap_or_else(LowerConfig::load_or_default);
+        if let Some(lower_port) = app
+            .world
+            .get_resource::<LaunchArgs>()
+            .and_then(|launch_args| launch_args.lower_port.clone())
+        {
+            config.serial_path = lower_port;
+        }
+        // 配置了串口时自动连接
+        let mut link = LowerLink::default();
+        if config.selector().is_some() {
+            link.request(None, Instant::now());
+        }
+        app.insert_resource(config)
+            .insert_resource(link)
+            .init_resource::<RobotLowerData>()
+            .init_resource::<LowerFrameDecoder>()
+            .init_resource::<LowerFrameStats>()
+            .init_resource::<LowerConnectionState>()
+            .add_systems(
+                FixedPreUpdate,
+                (
+                    connect_lower_system,
+                    read_lower_system,
+                    update_lower_state_system,
+                )
+                    .chain(),
+            )
+            .add_systems(FixedPostUpdate, send_lower_system.in_set(LowerSendSet))
+            .add_event::<LowerCommand>();
+    }
+}
+
+/// 向下位机发送指令的系统，产生`LowerCommand`的系统应在它之前运行
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemSet)]
+pub struct LowerSendSet;
+
+/*
+* Part: Config
+*/
+
+#[derive(Resource, Serialize, Deserialize)]
+#[serde(default)]
+struct LowerConfig {
+    /// USB串口的序列号
+    serial_name: String,
+    /// 串口路径，如`COM6`、`/dev/ttyUSB1`、`/dev/serial/by-id/...`
+    serial_path: String,
+    /// USB的VID:PID（十六进制），如`0483:5740`
+    usb_id: String,
+    /// 波特率
+    baud_rate: u32,
+    /// 超过此时间没有收到有效数据时，状态变为`Stale`，单位：毫秒
+    stale_timeout_ms: u64,
+    /// 打开或读写失败后重试的退避时间
+    reconnect: ChannelBackoff,
+}
+
+impl Default for LowerConfig {
+    fn default() -> Self {
+        Self {
+            serial_name: String::new(),
+            serial_path: String::new(),
+            usb_id: String::new(),
+            baud_rate: 115_200,
+            stale_timeout_ms: 500,
+            reconnect: ChannelBackoff::default(),
+        }
+    }
+}
+
+impl LowerConfig {
+    fn selector(&self) -> Option<SerialSelector> {
+        SerialSelector::from_config(&self.serial_path, &self.serial_name, &self.usb_id)
+    }
+}
+
+impl FastAccessData<'_> for LowerConfig {
+    fn file_path() -> BigHeroXResult<&'static str> {
+        #[dynamic(lazy)]
+        static FILE_PATH_STRING: BigHeroXResult<String> = ROBOT_CONFIG_DIR
+            .as_ref()
+            .map(|dir| dir.join("lower.toml").to_string_lossy().to_string())
+            .map_err(Clone::clone);
+        FILE_PATH_STRING.as_deref().map_err(Clone::clone)
+    }
+}
+
+/*
+* Part: Connect
+*/
+
+#[derive(Resource)]
+pub(super) struct LowerSerialModule {
+    poll: Poll,
+    events: Events,
+    stream: Mutex<SerialStream>,
+}
+
+const SERIAL_TOKEN: Token = Token(0);
+
+/// 下位机连接状态，供界面与逻辑部分查询
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deref, Resource)]
+pub struct LowerConnectionState(pub SerialConnectionState);
+
+/// 下位机串口的连接记录
+#[derive(Debug, Default, Deref, DerefMut, Resource)]
+struct LowerLink(SerialLink);
+
+/// 到了重试时间时打开串口，断开后按退避时间重试
+fn connect_lower_system(
+    mut commands: Commands,
+    config: Res<LowerConfig>,
+    module: Option<Res<LowerSerialModule>>,
+    mut link: ResMut<LowerLink>,
+    mut decoder: ResMut<LowerFrameDecoder>,
+) {
+    let now = Instant::now();
+    if module.is_some() || link.due(now).is_none() {
+        return;
+    }
+    let result = config
+        .selector()
+        .ok_or_else(|| "No lower serial port configured!".to_string())
+        .and_then(|selector| {
+            selector
+                .resolve(&available_candidates())
+                .ok_or_else(|| format!("No serial port matches {selector}!"))
+        })
+        .and_then(|path| open_serial_stream(&path, config.baud_rate, SERIAL_TOKEN));
+    match result {
+        Ok((poll, stream)) => {
+            commands.insert_resource(LowerSerialModule {
+                poll,
+                events: Events::with_capacity(1),
+                stream: Mutex::new(stream),
+            });
+            // 丢弃上次连接留下的半帧
+            decoder.reset();
+            link.opened(now);
+        }
+        Err(err) => {
+            warn!("{err}");
+            link.failed(err, now, &config.reconnect);
+        }
+    }
+}
+
+/// 更新`LowerConnectionState`
+fn update_lower_state_system(
+    config: Res<LowerConfig>,
+    link: Res<LowerLink>,
+    mut state: ResMut<LowerConnectionState>,
+) {
+    let new_state = link.state(
+        Instant::now(),
+        Duration::from_millis(config.stale_timeout_ms),
+    );
+    if **state != new_state {
+        info!("Lower connection: {new_state:?}");
+        *state = LowerConnectionState(new_state);
+    }
+}
+
+/*
+* Part: Read & Send
+*/
+
+fn read_lower_system(
+    mut commands: Commands,
+    config: Res<LowerConfig>,
+    module: Option<ResMut<LowerSerialModule>>,
+    mut link: ResMut<LowerLink>,
+    mut decoder: ResMut<LowerFrameDecoder>,
+    mut stats: ResMut<LowerFrameStats>,
+    mut lower_data: ResMut<RobotLowerData>,
+) {
+    let Some(mut module) = module else {
+        return;
+    };
+    let LowerSerialModule {
+        poll,
+        events,
+        stream: stream_mutex,
+    } = module.as_mut();
+    let stream = &mut *stream_mutex
+        .get_mut()
+        .unwrap_or_else(PoisonError::into_inner);
+    let mut buf = [0u8; 1024];
+
+    // Poll to check if we have events waiting for us, without blocking.
+    let mut error = poll
+        .poll(events, Some(Duration::ZERO))
+        .err()
+        .map(|err| format!("Failed to poll events on lower mio! {err}"));
+    let mut reports = Vec::new();
+    for event in events.iter() {
+        if event.token() != SERIAL_TOKEN {
+            continue;
+        }
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => break,
+                Ok(count) => reports.extend(decoder.push(&buf[..count])),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    error = Some(format!("Failed to read lower serial port! {err}"));
+                    break;
+                }
+            }
+        }
+        if error.is_none() && (event.is_error() || event.is_read_closed()) {
+            error = Some("Lower serial port closed".to_string());
+        }
+    }
+
+    let receive_time = Instant::now();
+    if !reports.is_empty() {
+        link.received(receive_time);
+    }
+    for report in reports {
+        report.apply(&mut lower_data);
+        if let LowerReport::Encoder(_) = report {
+            lower_data.encoder_time = Some(receive_time);
+        }
+    }
+    if **stats != decoder.stats() {
+        **stats = decoder.stats();
+    }
+    if let Some(error) = error {
+        warn!("{error}");
+        commands.remove_resource::<LowerSerialModule>();
+        link.failed(error, receive_time, &config.reconnect);
+    }
+}
+
+/// 发送本周期的指令：转速类指令只发送最新的一条，射门指令全部发送
+fn send_lower_system(
+    mut commands: Commands,
+    config: Res<LowerConfig>,
+    module: Option<ResMut<LowerSerialModule>>,
+    mut link: ResMut<LowerLink>,
+    mut command_events: EventReader<LowerCommand>,
+) {
+    let mut wheel_speeds = None;
+    let mut ball_handler = None;
+    let mut kicks = Vec::new();
+    for command in command_events.read() {
+        match command {
+            LowerCommand::WheelSpeeds(_) => wheel_speeds = Some(*command),
+            LowerCommand::BallHandler(_) => ball_handler = Some(*command),
+            LowerCommand::Kick { .. } => kicks.push(*command),
+        }
+    }
+    let Some(mut module) = module else {
+        return;
+    };
+    let bytes: Vec<u8> = wheel_speeds
+        .into_iter()
+        .chain(ball_handler)
+        .chain(kicks)
+        .flat_map(|command| command.encode())
+        .collect();
+    if bytes.is_empty() {
+        return;
+    }
+    let stream = &mut *module
+        .stream
+        .get_mut()
+        .unwrap_or_else(PoisonError::into_inner);
+    match stream.write_all(&bytes) {
+        Ok(()) => {}
+        // 发送缓冲区已满：丢弃本周期的指令，下个周期会发送新的指令
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+            warn!("Lower serial port is busy, dropping commands");
+        }
+        Err(err) => {
+            let error = format!("Failed to write lower serial port! {err}");
+            warn!("{error}");
+            commands.remove_resource::<LowerSerialModule>();
+            link.failed(error, Instant::now(), &config.reconnect);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::thread;
+
+    use bevy::MinimalPlugins;
+
+    use super::*;
+
+    #[test]
+    fn exchange_with_lower() {
+        let (mut master, slave) = SerialStream::pair().expect("Failed to create pseudo terminal!");
+        let path = mio_serial::SerialPort::name(&slave).expect("Failed to get path!");
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(LowerConfig {
+                serial_path: path,
+                ..Default::default()
+            })
+            .add_plugins(RobotLowerPlugin);
+
+        let report = LowerReport::Encoder([2500, -1, 7]);
+        master
+            .write_all(&report.encode())
+            .expect("Failed to write pseudo terminal!");
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut received = Vec::new();
+        let mut buf = [0u8; 256];
+        while Instant::now() < deadline && received.len() < 11 {
+            app.world
+                .send_event(LowerCommand::WheelSpeeds([100, -100, 0]));
+            app.update();
+            if let Ok(count) = master.read(&mut buf) {
+                received.extend_from_slice(&buf[..count]);
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        let lower_data = app.world.resource::<RobotLowerData>();
+        assert_eq!(lower_data.motor_status[0].rotate_pos, 2500);
+        assert_eq!(lower_data.motor_status[1].rotate_pos, -1);
+        assert_eq!(
+            **app.world.resource::<LowerConnectionState>(),
+            SerialConnectionState::Streaming
+        );
+        assert_eq!(
+            received[..11],
+            LowerCommand::WheelSpeeds([100, -100, 0]).encode()
+        );
+    }
+}
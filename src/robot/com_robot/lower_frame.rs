@@ -0,0 +1,314 @@
+//! 下位机串口协议：分帧、校验与各类数据帧的编解码
+//!
+//! 数据帧格式（多字节数值均为大端）：
+//! - 包头：`0xA5 0x5A`
+//! - 类型：1字节，上位机发出的为`0x01`~`0x7F`，下位机发出的为`0x81`~`0xFF`
+//! - 长度：1字节，数据部分的字节数，最多`LOWER_FRAME_MAX_PAYLOAD`
+//! - 数据：见`LowerCommand`与`LowerReport`
+//! - 校验：1字节，类型、长度与数据部分的CRC-8，见`lower_checksum`
+
+use bevy::prelude::*;
+
+use super::{RobotLowerData, ADC_COUNT, IO_COUNT, MOTOR_COUNT};
+use crate::robot::serial::frame::{FrameCheck, FrameDecoder, FrameFormat, FrameStats};
+
+pub const LOWER_FRAME_HEADER: [u8; 2] = [0xA5, 0x5A];
+pub const LOWER_FRAME_MAX_PAYLOAD: usize = 32;
+/// 包头、类型、长度与校验所占的字节数
+pub const LOWER_FRAME_OVERHEAD: usize = 2 + 1 + 1 + 1;
+
+/// CRC-8（多项式`0x07`，初值`0x00`，不反转）
+pub fn lower_checksum<'a>(bytes: impl IntoIterator<Item = &'a u8>) -> u8 {
+    bytes.into_iter().fold(0u8, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// 组成一帧
+pub fn encode_lower_frame(kind: u8, payload: &[u8]) -> Vec<u8> {
+    debug_assert!(payload.len() <= LOWER_FRAME_MAX_PAYLOAD);
+    let mut frame = Vec::with_capacity(LOWER_FRAME_OVERHEAD + payload.len());
+    frame.extend(LOWER_FRAME_HEADER);
+    frame.push(kind);
+    frame.push(payload.len() as u8);
+    frame.extend(payload);
+    frame.push(lower_checksum(&frame[LOWER_FRAME_HEADER.len()..]));
+    frame
+}
+
+/// 上位机发给下位机的指令
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub enum LowerCommand {
+    /// 三个底盘电机的转速，正值为顺时针，单位：转每分钟。轮子顺序：后侧、左侧、右侧
+    WheelSpeeds([i16; MOTOR_COUNT]),
+    /// 两个吸球轮的转速，单位：转每分钟
+    BallHandler([i16; 2]),
+    /// 射门：电磁铁通电时间，单位：毫秒
+    Kick { duration_ms: u16 },
+}
+
+impl LowerCommand {
+    pub const WHEEL_SPEEDS: u8 = 0x01;
+    pub const BALL_HANDLER: u8 = 0x02;
+    pub const KICK: u8 = 0x03;
+
+    pub fn kind(&self) -> u8 {
+        match self {
+            LowerCommand::WheelSpeeds(_) => Self::WHEEL_SPEEDS,
+            LowerCommand::BallHandler(_) => Self::BALL_HANDLER,
+            LowerCommand::Kick { .. } => Self::KICK,
+        }
+    }
+
+    pub fn payload(&self) -> Vec<u8> {
+        match self {
+            LowerCommand::WheelSpeeds(rpm) => rpm.iter().flat_map(|v| v.to_be_bytes()).collect(),
+            LowerCommand::BallHandler(rpm) => rpm.iter().flat_map(|v| v.to_be_bytes()).collect(),
+            LowerCommand::Kick { duration_ms } => duration_ms.to_be_bytes().to_vec(),
+        }
+    }
+
+    pub fn decode(kind: u8, payload: &[u8]) -> Option<Self> {
+        match kind {
+            Self::WHEEL_SPEEDS => Some(LowerCommand::WheelSpeeds(be_array(
+                payload,
+                i16::from_be_bytes,
+            )?)),
+            Self::BALL_HANDLER => Some(LowerCommand::BallHandler(be_array(
+                payload,
+                i16::from_be_bytes,
+            )?)),
+            Self::KICK => Some(LowerCommand::Kick {
+                duration_ms: u16::from_be_bytes(payload.try_into().ok()?),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        encode_lower_frame(self.kind(), &self.payload())
+    }
+}
+
+/// 下位机发来的数据
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LowerReport {
+    /// 各电机码盘位置，见`RobotMotorStatus::rotate_pos`
+    Encoder([i32; MOTOR_COUNT]),
+    /// 各电机电流，单位：毫安
+    Current([u16; MOTOR_COUNT]),
+    Adc([u16; ADC_COUNT]),
+    /// 第i位为第i个IO
+    Io([bool; IO_COUNT]),
+}
+
+impl LowerReport {
+    pub const ENCODER: u8 = 0x81;
+    pub const CURRENT: u8 = 0x82;
+    pub const ADC: u8 = 0x83;
+    pub const IO: u8 = 0x84;
+
+    pub fn kind(&self) -> u8 {
+        match self {
+            LowerReport::Encoder(_) => Self::ENCODER,
+            LowerReport::Current(_) => Self::CURRENT,
+            LowerReport::Adc(_) => Self::ADC,
+            LowerReport::Io(_) => Self::IO,
+        }
+    }
+
+    pub fn payload(&self) -> Vec<u8> {
+        match self {
+            LowerReport::Encoder(pos) => pos.iter().flat_map(|v| v.to_be_bytes()).collect(),
+            LowerReport::Current(current) => current.iter().flat_map(|v| v.to_be_bytes()).collect(),
+            LowerReport::Adc(adc) => adc.iter().flat_map(|v| v.to_be_bytes()).collect(),
+            LowerReport::Io(io) => {
+                vec![io
+                    .iter()
+                    .enumerate()
+                    .fold(0u8, |bits, (index, on)| bits | (u8::from(*on) << index))]
+            }
+        }
+    }
+
+    /// 解析数据部分。类型未知或长度不符时返回`None`
+    pub fn decode(kind: u8, payload: &[u8]) -> Option<Self> {
+        match kind {
+            Self::ENCODER => Some(LowerReport::Encoder(be_array(payload, i32::from_be_bytes)?)),
+            Self::CURRENT => Some(LowerReport::Current(be_array(payload, u16::from_be_bytes)?)),
+            Self::ADC => Some(LowerReport::Adc(be_array(payload, u16::from_be_bytes)?)),
+            Self::IO => {
+                let [bits] = payload else {
+                    return None;
+                };
+                Some(LowerReport::Io(std::array::from_fn(|index| {
+                    bits & (1 << index) != 0
+                })))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        encode_lower_frame(self.kind(), &self.payload())
+    }
+
+    /// 写入`RobotLowerData`的对应部分
+    pub fn apply(&self, data: &mut RobotLowerData) {
+        match self {
+            LowerReport::Encoder(pos) => {
+                for (status, pos) in data.motor_status.iter_mut().zip(pos) {
+                    status.rotate_pos = *pos;
+                }
+            }
+            LowerReport::Current(current) => {
+                for (status, current) in data.motor_status.iter_mut().zip(current) {
+                    status.current = *current;
+                }
+            }
+            LowerReport::Adc(adc) => data.adc = *adc,
+            LowerReport::Io(io) => data.io = *io,
+        }
+    }
+}
+
+/// 把数据部分按大端解析为`N`个数值，长度不符时返回`None`
+fn be_array<T, const N: usize, const SIZE: usize>(
+    payload: &[u8],
+    from_be_bytes: fn([u8; SIZE]) -> T,
+) -> Option<[T; N]> {
+    if payload.len() != N * SIZE {
+        return None;
+    }
+    let mut chunks = payload.chunks_exact(SIZE);
+    Some(std::array::from_fn(|_| {
+        let bytes = chunks.next().and_then(|chunk| chunk.try_into().ok());
+        from_be_bytes(bytes.unwrap_or([0; SIZE]))
+    }))
+}
+
+/// 下位机数据帧：长度由长度字节给出，校验通过后由`LowerReport::decode`解析
+#[derive(Debug)]
+pub struct LowerFrame;
+
+impl FrameFormat for LowerFrame {
+    type Frame = LowerReport;
+    const HEADER: [u8; 2] = LOWER_FRAME_HEADER;
+    const HEAD_LEN: usize = LOWER_FRAME_OVERHEAD;
+
+    fn frame_len(head: &[u8]) -> Option<usize> {
+        let payload_len = head[3] as usize;
+        (payload_len <= LOWER_FRAME_MAX_PAYLOAD).then_some(LOWER_FRAME_OVERHEAD + payload_len)
+    }
+
+    fn check(frame: &[u8]) -> FrameCheck<LowerReport> {
+        let (checksum, body) = frame.split_last().unwrap_or((&0, &[]));
+        if lower_checksum(&body[LOWER_FRAME_HEADER.len()..]) != *checksum {
+            return FrameCheck::BadChecksum;
+        }
+        match LowerReport::decode(body[2], &body[4..]) {
+            Some(report) => FrameCheck::Good(report),
+            None => FrameCheck::Unknown,
+        }
+    }
+}
+
+/// 解码统计，供界面显示
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deref, DerefMut, Resource)]
+pub struct LowerFrameStats(pub FrameStats);
+
+/// 流式解码器
+#[derive(Debug, Default, Deref, DerefMut, Resource)]
+pub struct LowerFrameDecoder(FrameDecoder<LowerFrame>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reports() -> Vec<LowerReport> {
+        vec![
+            LowerReport::Encoder([0, -2500, i32::MAX]),
+            LowerReport::Current([120, 0, 65535]),
+            LowerReport::Adc([1, 2, 3, 4, 4095]),
+            LowerReport::Io([true, false, false, true, false, false, false, true]),
+        ]
+    }
+
+    #[test]
+    fn golden_frames() {
+        // CRC-8的标准校验值
+        assert_eq!(lower_checksum(b"123456789"), 0xF4);
+        assert_eq!(
+            LowerCommand::WheelSpeeds([100, -100, 0]).encode(),
+            [0xA5, 0x5A, 0x01, 0x06, 0x00, 0x64, 0xFF, 0x9C, 0x00, 0x00, 0x7E]
+        );
+        assert_eq!(
+            LowerCommand::Kick { duration_ms: 20 }.encode(),
+            [0xA5, 0x5A, 0x03, 0x02, 0x00, 0x14, 0x80]
+        );
+        for command in [
+            LowerCommand::WheelSpeeds([1, 2, -3]),
+            LowerCommand::BallHandler([1500, -1500]),
+            LowerCommand::Kick { duration_ms: 20 },
+        ] {
+            let frame = command.encode();
+            assert_eq!(
+                LowerCommand::decode(frame[2], &frame[4..frame.len() - 1]),
+                Some(command)
+            );
+        }
+    }
+
+    #[test]
+    fn decode_reports() {
+        let reports = reports();
+        let bytes: Vec<u8> = reports.iter().flat_map(LowerReport::encode).collect();
+        for chunk_len in [1, 3, 7, bytes.len()] {
+            let mut decoder = LowerFrameDecoder::default();
+            let decoded: Vec<_> = bytes
+                .chunks(chunk_len)
+                .flat_map(|chunk| decoder.push(chunk))
+                .collect();
+            assert_eq!(decoded, reports, "chunk_len: {chunk_len}");
+        }
+
+        let mut data = RobotLowerData::default();
+        for report in &reports {
+            report.apply(&mut data);
+        }
+        assert_eq!(data.motor_status[1].rotate_pos, -2500);
+        assert_eq!(data.motor_status[2].current, 65535);
+        assert_eq!(data.adc[4], 4095);
+        assert!(data.io[0] && data.io[7] && !data.io[1]);
+    }
+
+    #[test]
+    fn corrupted_and_unknown() {
+        let reports = reports();
+        let mut bytes = vec![0x5A, 0xA5];
+        bytes.extend(reports[0].encode());
+        let mut corrupted = reports[1].encode();
+        corrupted[6] ^= 0x01;
+        bytes.extend(corrupted);
+        // 超长的假包头
+        bytes.extend([0xA5, 0x5A, 0x81, 0xFF]);
+        // 未知类型
+        bytes.extend(encode_lower_frame(0xF0, &[1, 2]));
+        // 长度不符
+        bytes.extend(encode_lower_frame(LowerReport::ADC, &[1, 2]));
+        bytes.extend(reports[2].encode());
+        let mut decoder = LowerFrameDecoder::default();
+        assert_eq!(decoder.push(&bytes), vec![reports[0], reports[2]]);
+        let stats = decoder.stats();
+        assert_eq!(stats.good, 2);
+        assert_eq!(stats.bad_checksum, 2);
+        assert_eq!(stats.unknown, 2);
+        assert!(stats.skipped_bytes > 0);
+    }
+}
@@ -1,11 +1,37 @@
 #![allow(unused)]
 
+pub mod behaviour;
+pub mod dispatch;
+
+use std::time::{Duration, Instant, SystemTime};
+
 use bevy::prelude::*;
+use glam::Vec2;
+
+use crate::{data_legacy::LegacyCtrl, field::world_state::RobotPose};
+
+use self::{
+    behaviour::{
+        attack, catch, defend_at, defense, face_ball, pass, receive, search_ball, BallEstimate,
+        BallTracker, BehaviourCommand, RobotWorld,
+    },
+    dispatch::{dispatch, DispatchFallback},
+};
 
 use super::{
-    com_robot::RobotLowerData,
-    motion::{RobotCtrl, RobotMotion},
+    com_mpu::orientation::{mpu_orientation_system, RobotHeading},
+    com_robot::{lower_frame::LowerCommand, RobotLowerData},
+    motion::{
+        limiter::MotionLimiter,
+        odometry::{odometry_system, RobotOdometry},
+        path_plan::PlanMap,
+        pose_control::{MotionProgress, PoseController},
+        wheel_control::{wheel_speed_control_system, WheelSpeedTarget},
+        RobotCtrl, RobotMotion,
+    },
+    network::CoachLegacyData,
     panorama_camera::PanoramaData,
+    RobotConfig, RobotRole,
 };
 
 /*
@@ -15,12 +41,24 @@ pub(super) struct RobotMotionLogicPlugin;
 
 impl Plugin for RobotMotionLogicPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(FixedUpdate, robot_motion_set_target_system)
+        app.init_resource::<BallTracker>()
+            .init_resource::<RobotWorld>()
+            .add_systems(FixedUpdate, robot_ctrl_dispatch_system)
+            .add_systems(
+                FixedUpdate,
+                robot_motion_set_target_system
+                    .after(robot_ctrl_dispatch_system)
+                    .after(odometry_system)
+                    .after(mpu_orientation_system),
+            )
             .add_systems(
                 FixedUpdate,
                 robot_motion_set_moving_system.after(robot_motion_set_target_system),
             )
-            .add_systems(FixedPostUpdate, robot_motion_activate_system);
+            .add_systems(
+                FixedPostUpdate,
+                robot_motion_activate_system.before(wheel_speed_control_system),
+            );
     }
 }
 
@@ -28,34 +66,220 @@ impl Plugin for RobotMotionLogicPlugin {
  * Part：系统
  */
 
-/// 机器人运动指令：入口
-fn robot_motion_set_target_system(mut commands: Commands) {
-    // TODO
-    let robot_motion = RobotCtrl::Defense {
-        ball_pos: glam::Vec2::new(0.0, 0.0),
+/// 教练机指令分派：每收到一个教练机数据包，按其中的指令替换`RobotCtrl`
+/// 无法执行的指令原地停下，指令变化时警告一次；超过`coach_timeout_ms`没有收到数据包时也原地停下
+fn robot_ctrl_dispatch_system(
+    mut commands: Commands,
+    config: Res<RobotConfig>,
+    coach_data: Option<Res<CoachLegacyData>>,
+    mut last_fallback: Local<Option<(LegacyCtrl, DispatchFallback)>>,
+    mut coach_lost: Local<bool>,
+) {
+    let Some(coach_data) = coach_data else {
+        return;
     };
+    let timeout = Duration::from_millis(config.network.coach_timeout_ms);
+    let timed_out = SystemTime::now()
+        .duration_since(coach_data.receive_time)
+        .is_ok_and(|duration| duration > timeout);
+    if timed_out {
+        if !*coach_lost {
+            warn!("No coach pack in {timeout:?}, stopping");
+            commands.insert_resource(RobotCtrl::Stop);
+            *coach_lost = true;
+            *last_fallback = None;
+        }
+        return;
+    }
+    if !coach_data.is_changed() {
+        return;
+    }
+    if *coach_lost {
+        info!("Coach pack received again");
+        *coach_lost = false;
+    }
+    let pack = &coach_data.pack;
+    let result = dispatch(pack, &config.network.legacy_frame);
+    let fallback = result.fallback.map(|fallback| (pack.ctrl, fallback));
+    if fallback != *last_fallback {
+        match fallback {
+            Some((ctrl, DispatchFallback::Unsupported)) => {
+                warn!("Coach ctrl {ctrl:?} is not supported, stopping")
+            }
+            Some((ctrl, DispatchFallback::NoBall)) => {
+                warn!("Coach ctrl {ctrl:?} needs the ball but coach found none, stopping")
+            }
+            None => {}
+        }
+        *last_fallback = fallback;
+    }
+    commands.insert_resource(result.ctrl);
+}
 
-    commands.insert_resource(robot_motion);
+/// 机器人运动指令：入口
+/// 更新行为所需的世界状态：自身位姿、是否持球，以及指令中的球位置
+#[allow(clippy::too_many_arguments)]
+fn robot_motion_set_target_system(
+    config: Res<RobotConfig>,
+    ctrl: Option<Res<RobotCtrl>>,
+    odometry: Option<Res<RobotOdometry>>,
+    heading: Option<Res<RobotHeading>>,
+    panorama_data: Option<Res<PanoramaData>>,
+    lower_data: Option<Res<RobotLowerData>>,
+    mut tracker: ResMut<BallTracker>,
+    mut world: ResMut<RobotWorld>,
+) {
+    // 当前位姿：有全景相机定位时使用相机的位置，有MPU朝向时使用MPU的朝向
+    let mut pose = odometry.map_or_else(RobotPose::default, |odometry| RobotPose {
+        pos: odometry.pos,
+        angle: odometry.yaw,
+    });
+    if let Some(panorama_data) = &panorama_data {
+        pose.pos = panorama_data.pos;
+    }
+    if let Some(heading) = &heading {
+        pose.angle = heading.yaw;
+    }
+    let has_ball = lower_data.is_some_and(|lower_data| {
+        lower_data
+            .io
+            .get(config.behaviour.ball_sensor_io)
+            .copied()
+            .unwrap_or(false)
+    });
+    let ball_pos = ctrl.and_then(|ctrl| ctrl.ball_pos());
+    *world = RobotWorld {
+        pose,
+        has_ball,
+        ball: ball_pos.map(|ball_pos| tracker.update(ball_pos, Instant::now())),
+    };
 }
 
 /// 机器人运动指令：已确定目标
-fn robot_motion_set_moving_system(mut commands: Commands, ctrl: Option<Res<RobotCtrl>>) {
-    let Some(_ctrl) = ctrl else {
+/// `RobotCtrl`保留到被替换或移除为止，每个周期按最新的世界状态重新计算
+#[allow(clippy::too_many_arguments)]
+fn robot_motion_set_moving_system(
+    mut commands: Commands,
+    ctrl: Option<Res<RobotCtrl>>,
+    config: Res<RobotConfig>,
+    role: Option<Res<RobotRole>>,
+    world: Res<RobotWorld>,
+    panorama_data: Option<Res<PanoramaData>>,
+    time: Res<Time>,
+    mut pose_controller: Local<PoseController>,
+) {
+    let Some(ctrl) = ctrl else {
+        *pose_controller = PoseController::default();
+        commands.remove_resource::<MotionProgress>();
         return;
     };
-    commands.remove_resource::<RobotCtrl>();
-    // 添加运动指令
-    let robot_motion = RobotMotion::default();
-    commands.insert_resource(robot_motion);
+    let is_goalkeeper = role.is_some_and(|role| *role == RobotRole::GoalKeeper);
+    let max_speed = config.pose_control.max_speed;
+    let ball_or = |ball_pos| {
+        world.ball.unwrap_or(BallEstimate {
+            pos: ball_pos,
+            velocity: Vec2::ZERO,
+        })
+    };
+    let command = match *ctrl {
+        RobotCtrl::Stop => BehaviourCommand::move_to(world.pose),
+        RobotCtrl::MoveTo { target } => BehaviourCommand::move_to(target),
+        RobotCtrl::Pass { target_pos } => pass(&world, target_pos, &config.behaviour),
+        RobotCtrl::Catch { ball_pos } => {
+            catch(&world, ball_or(ball_pos), max_speed, &config.behaviour)
+        }
+        RobotCtrl::Defense { ball_pos } => defense(
+            &world,
+            ball_pos,
+            &config.field_data,
+            &config.behaviour,
+            is_goalkeeper,
+        ),
+        RobotCtrl::DefendAt { angle, dist } => defend_at(angle, dist, &config.field_data),
+        RobotCtrl::Attack { ball_pos } => attack(
+            &world,
+            ball_or(ball_pos),
+            max_speed,
+            &config.field_data,
+            &config.behaviour,
+        ),
+        RobotCtrl::Receive { from_pos, .. } => {
+            receive(&world, from_pos, world.ball, max_speed, &config.behaviour)
+        }
+        RobotCtrl::FaceBall { ball_pos } => face_ball(&world, ball_pos),
+        RobotCtrl::SearchBall => search_ball(&world, &config.behaviour),
+    };
+
+    // 每个周期按最新的障碍物重新规划
+    let now = world.pose;
+    let target = command.target;
+    let barriers = panorama_data
+        .as_ref()
+        .map_or(&[][..], |data| &data.barriers);
+    let map = PlanMap::new(
+        &config.field_data,
+        barriers,
+        &config.path_plan,
+        is_goalkeeper,
+    );
+    let path = map.plan(now.pos, target.pos);
+    let (mut motion, mut progress) = pose_controller.update_path(
+        now,
+        path.as_deref().unwrap_or(&[]),
+        target.angle,
+        &config.pose_control,
+        time.delta_seconds(),
+    );
+    if path.is_none() {
+        warn!("No path from {} to {}, stopping", now.pos, target.pos);
+        progress.target = target;
+        progress.arrived = false;
+        progress.blocked = true;
+    }
+    motion.ball_take_wheel_speeds_rpm = command.ball_handler_rpm;
+    motion.ball_shot_prepare_ms = command.kick_ms;
+    commands.insert_resource(progress);
+    commands.insert_resource(motion);
 }
 
 /// 机器人运动指令：执行
-fn robot_motion_activate_system(mut commands: Commands, motion: Option<Res<RobotMotion>>) {
+#[allow(clippy::too_many_arguments)]
+fn robot_motion_activate_system(
+    mut commands: Commands,
+    motion: Option<Res<RobotMotion>>,
+    config: Res<RobotConfig>,
+    heading: Option<Res<RobotHeading>>,
+    time: Res<Time>,
+    mut limiter: Local<MotionLimiter>,
+    mut last_kick_time: Local<Option<Instant>>,
+    mut lower_commands: EventWriter<LowerCommand>,
+) {
     let Some(motion) = motion else {
         return;
     };
-    // TODO
-    let _speeds = motion.get_motor_speeds();
+    let mut motion = *motion;
+    // 限制加速度与加加速度后再换算成电机转速
+    limiter.apply(&mut motion, &config.motion_limits, time.delta_seconds());
+    let heading = heading.map_or(0.0, |heading| heading.yaw);
+    // 转速闭环见`wheel_control`
+    commands.insert_resource(WheelSpeedTarget {
+        rpm: motion.get_motor_speeds(heading, &config.chassis),
+        update_time: Instant::now(),
+    });
+    // 吸球轮与射门
+    lower_commands.send(LowerCommand::BallHandler(
+        motion
+            .ball_take_wheel_speeds_rpm
+            .to_array()
+            .map(|rpm| rpm.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16),
+    ));
+    if let Some(duration_ms) = motion.ball_shot_prepare_ms {
+        let cooldown = Duration::from_millis(config.behaviour.kick_cooldown_ms);
+        if !last_kick_time.is_some_and(|time| time.elapsed() < cooldown) {
+            lower_commands.send(LowerCommand::Kick { duration_ms });
+            *last_kick_time = Some(Instant::now());
+        }
+    }
     commands.remove_resource::<RobotMotion>();
 }
 
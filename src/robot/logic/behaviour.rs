@@ -0,0 +1,528 @@
+//! 行为：由当前的世界状态（自身位姿、球、是否持球）计算目标位姿、吸球轮转速与射门指令
+//!
+//! 各行为都是纯函数，每个周期重新计算，移动由路径规划与位姿控制完成。
+
+use std::time::Instant;
+
+use bevy::prelude::*;
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::field::{
+    world_state::{wrap_angle, RobotPose},
+    FieldData,
+};
+
+/// 行为参数
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BehaviourConfig {
+    /// 防守时站在球与己方球门连线上，离球门中心的距离，单位：米
+    pub defense_distance: f32,
+    /// 守门员防守时离球门中心的距离，单位：米
+    pub goalkeeper_defense_distance: f32,
+    /// 持球时球心到机器人中心的距离，单位：米
+    pub ball_hold_distance: f32,
+    /// 球速低于此值时视为静止，直接去拿球，单位：米每秒
+    pub ball_still_speed: f32,
+    /// 预测球路的时长，单位：秒
+    pub catch_horizon_s: f32,
+    /// 离球小于此距离时打开吸球轮，单位：米
+    pub ball_handler_distance: f32,
+    /// 吸球轮转速，单位：转每分钟
+    pub ball_handler_rpm: Vec2,
+    /// 瞄准的角度容差，单位：弧度
+    pub aim_tolerance: f32,
+    /// 射门电磁铁通电时间：`kick_ms_base + kick_ms_per_meter * 距离`，不超过`kick_ms_max`
+    pub kick_ms_base: f32,
+    pub kick_ms_per_meter: f32,
+    pub kick_ms_max: u16,
+    /// 两次射门之间的最短间隔，单位：毫秒
+    pub kick_cooldown_ms: u64,
+    /// 持球传感器对应的`RobotLowerData.io`序号（待与下位机核对）
+    pub ball_sensor_io: usize,
+    /// 找球时目标朝向超前当前朝向的角度，小于PI，单位：弧度
+    pub search_turn_angle: f32,
+}
+
+impl Default for BehaviourConfig {
+    fn default() -> Self {
+        Self {
+            defense_distance: 2.0,
+            goalkeeper_defense_distance: 0.6,
+            ball_hold_distance: 0.3,
+            ball_still_speed: 0.2,
+            catch_horizon_s: 3.0,
+            ball_handler_distance: 1.0,
+            ball_handler_rpm: Vec2::splat(1500.0),
+            aim_tolerance: 0.05,
+            kick_ms_base: 10.0,
+            kick_ms_per_meter: 4.0,
+            kick_ms_max: 60,
+            kick_cooldown_ms: 1000,
+            ball_sensor_io: 0,
+            search_turn_angle: std::f32::consts::FRAC_PI_2,
+        }
+    }
+}
+
+/// 球的位置与速度
+/// 坐标系：场地中心为零点，敌方球门方向为x轴正方向，正前方朝着敌方球门时，左侧为y轴正方向，单位：米
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BallEstimate {
+    pub pos: Vec2,
+    /// 单位：米每秒
+    pub velocity: Vec2,
+}
+
+impl BallEstimate {
+    /// `time`秒后的位置（匀速）
+    pub fn predict(&self, time: f32) -> Vec2 {
+        self.pos + self.velocity * time
+    }
+}
+
+/// 由连续的球位置估计球速
+#[derive(Debug, Clone, Copy, Default, Resource)]
+pub struct BallTracker {
+    last: Option<(BallEstimate, Instant)>,
+}
+
+impl BallTracker {
+    /// 新的速度与旧的速度按此比例平滑
+    const SMOOTHING: f32 = 0.5;
+    /// 球位置的更新比控制周期慢，位置不变且间隔小于此值时视为同一次观测，单位：秒
+    const SAME_OBSERVATION_S: f32 = 0.1;
+
+    pub fn update(&mut self, pos: Vec2, time: Instant) -> BallEstimate {
+        let velocity = match self.last {
+            Some((last, last_time)) => {
+                let dt = time.saturating_duration_since(last_time).as_secs_f32();
+                if dt <= 0.0 || (pos == last.pos && dt < Self::SAME_OBSERVATION_S) {
+                    return last;
+                }
+                last.velocity.lerp((pos - last.pos) / dt, Self::SMOOTHING)
+            }
+            None => Vec2::ZERO,
+        };
+        let estimate = BallEstimate { pos, velocity };
+        self.last = Some((estimate, time));
+        estimate
+    }
+}
+
+/// 行为所需的世界状态
+#[derive(Debug, Clone, Copy, Default, PartialEq, Resource)]
+pub struct RobotWorld {
+    pub pose: RobotPose,
+    pub has_ball: bool,
+    pub ball: Option<BallEstimate>,
+}
+
+/// 行为的输出
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BehaviourCommand {
+    pub target: RobotPose,
+    /// 单位：转每分钟
+    pub ball_handler_rpm: Vec2,
+    /// 射门：电磁铁通电时间，单位：毫秒
+    pub kick_ms: Option<u16>,
+}
+
+impl BehaviourCommand {
+    pub fn move_to(target: RobotPose) -> Self {
+        Self {
+            target,
+            ..Default::default()
+        }
+    }
+}
+
+/// 防守：站在球与己方球门中心的连线上，面向球
+pub fn defense(
+    world: &RobotWorld,
+    ball_pos: Vec2,
+    field: &FieldData,
+    config: &BehaviourConfig,
+    is_goalkeeper: bool,
+) -> BehaviourCommand {
+    let goal = Vec2::new(-field.field_size.x / 2.0, 0.0);
+    let to_ball = ball_pos - goal;
+    let distance = if is_goalkeeper {
+        config.goalkeeper_defense_distance
+    } else {
+        config.defense_distance
+    };
+    // 球比防守位置更靠近球门时，站在球与球门之间
+    let distance = distance.min(to_ball.length() / 2.0);
+    let pos = goal + to_ball.normalize_or_zero() * distance;
+    BehaviourCommand::move_to(RobotPose {
+        pos,
+        angle: face(pos, ball_pos, world.pose.angle),
+    })
+}
+
+/// 接球、抢球：沿预测的球路找最早能赶到的拦截点，正面迎球
+pub fn catch(
+    world: &RobotWorld,
+    ball: BallEstimate,
+    max_speed: f32,
+    config: &BehaviourConfig,
+) -> BehaviourCommand {
+    let robot_pos = world.pose.pos;
+    let mut ball_handler_rpm = Vec2::ZERO;
+    if world.has_ball || robot_pos.distance(ball.pos) <= config.ball_handler_distance {
+        ball_handler_rpm = config.ball_handler_rpm;
+    }
+    if world.has_ball {
+        return BehaviourCommand {
+            target: world.pose,
+            ball_handler_rpm,
+            kick_ms: None,
+        };
+    }
+
+    let speed = ball.velocity.length();
+    let target = if speed < config.ball_still_speed {
+        // 静止的球：从当前方向靠近
+        let angle = face(robot_pos, ball.pos, world.pose.angle);
+        RobotPose {
+            pos: ball.pos - Vec2::from_angle(angle) * config.ball_hold_distance,
+            angle,
+        }
+    } else {
+        // 运动的球：面向来球方向
+        let angle = (-ball.velocity).to_angle();
+        let hold_offset = Vec2::from_angle(angle) * config.ball_hold_distance;
+        const STEP_S: f32 = 0.05;
+        let steps = (config.catch_horizon_s / STEP_S).ceil().max(1.0) as usize;
+        let intercept = (0..=steps)
+            .map(|step| ball.predict(step as f32 * STEP_S) - hold_offset)
+            .enumerate()
+            .find(|(step, pos)| pos.distance(robot_pos) <= max_speed * *step as f32 * STEP_S)
+            .map(|(_, pos)| pos);
+        // 追不上：追向预测时长末尾的位置
+        let pos = intercept.unwrap_or(ball.predict(config.catch_horizon_s) - hold_offset);
+        RobotPose { pos, angle }
+    };
+    BehaviourCommand {
+        target,
+        ball_handler_rpm,
+        kick_ms: None,
+    }
+}
+
+/// 传球：原地转向目标，持球且瞄准后射门，通电时间随距离增加
+pub fn pass(world: &RobotWorld, target_pos: Vec2, config: &BehaviourConfig) -> BehaviourCommand {
+    let angle = face(world.pose.pos, target_pos, world.pose.angle);
+    let aimed = wrap_angle(angle - world.pose.angle).abs() <= config.aim_tolerance;
+    let kick_ms = (world.has_ball && aimed).then(|| {
+        let distance = world.pose.pos.distance(target_pos);
+        (config.kick_ms_base + config.kick_ms_per_meter * distance)
+            .round()
+            .clamp(0.0, config.kick_ms_max as f32) as u16
+    });
+    BehaviourCommand {
+        target: RobotPose {
+            pos: world.pose.pos,
+            angle,
+        },
+        // 转向时吸住球，射门时松开
+        ball_handler_rpm: if world.has_ball && kick_ms.is_none() {
+            config.ball_handler_rpm
+        } else {
+            Vec2::ZERO
+        },
+        kick_ms,
+    }
+}
+
+/// 进攻：没有球时去拿球，持球后射向敌方球门中心
+pub fn attack(
+    world: &RobotWorld,
+    ball: BallEstimate,
+    max_speed: f32,
+    field: &FieldData,
+    config: &BehaviourConfig,
+) -> BehaviourCommand {
+    if world.has_ball {
+        pass(world, Vec2::new(field.field_size.x / 2.0, 0.0), config)
+    } else {
+        catch(world, ball, max_speed, config)
+    }
+}
+
+/// 防守指定位置：站在己方球门中心沿`angle`方向`dist`米处，面向外侧
+pub fn defend_at(angle: f32, dist: f32, field: &FieldData) -> BehaviourCommand {
+    let goal = Vec2::new(-field.field_size.x / 2.0, 0.0);
+    BehaviourCommand::move_to(RobotPose {
+        pos: goal + Vec2::from_angle(angle) * dist,
+        angle: wrap_angle(angle),
+    })
+}
+
+/// 接传球：球没有传来时原地面向`from_pos`，打开吸球轮等待；球传来或已持球时同接球
+pub fn receive(
+    world: &RobotWorld,
+    from_pos: Vec2,
+    ball: Option<BallEstimate>,
+    max_speed: f32,
+    config: &BehaviourConfig,
+) -> BehaviourCommand {
+    match ball {
+        Some(ball) if world.has_ball || ball.velocity.length() >= config.ball_still_speed => {
+            catch(world, ball, max_speed, config)
+        }
+        _ => BehaviourCommand {
+            target: RobotPose {
+                pos: world.pose.pos,
+                angle: face(world.pose.pos, from_pos, world.pose.angle),
+            },
+            ball_handler_rpm: config.ball_handler_rpm,
+            kick_ms: None,
+        },
+    }
+}
+
+/// 原地转向球
+pub fn face_ball(world: &RobotWorld, ball_pos: Vec2) -> BehaviourCommand {
+    BehaviourCommand::move_to(RobotPose {
+        pos: world.pose.pos,
+        angle: face(world.pose.pos, ball_pos, world.pose.angle),
+    })
+}
+
+/// 找球：原地逆时针转圈
+pub fn search_ball(world: &RobotWorld, config: &BehaviourConfig) -> BehaviourCommand {
+    BehaviourCommand::move_to(RobotPose {
+        pos: world.pose.pos,
+        angle: wrap_angle(world.pose.angle + config.search_turn_angle),
+    })
+}
+
+/// 从`from`看向`to`的角度，两点重合时保持`current`
+fn face(from: Vec2, to: Vec2, current: f32) -> f32 {
+    (to - from).try_normalize().map_or(current, Vec2::to_angle)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{f32::consts::PI, time::Duration};
+
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    fn world_at(x: f32, y: f32, angle: f32, has_ball: bool) -> RobotWorld {
+        RobotWorld {
+            pose: RobotPose {
+                pos: Vec2::new(x, y),
+                angle,
+            },
+            has_ball,
+            ball: None,
+        }
+    }
+
+    /// 点`point`到直线`a`→`b`的距离
+    fn line_distance(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+        (b - a).normalize().perp_dot(point - a).abs()
+    }
+
+    #[test]
+    fn defense_on_ball_goal_line() {
+        let field = FieldData::default();
+        let config = BehaviourConfig::default();
+        let goal = Vec2::new(-field.field_size.x / 2.0, 0.0);
+        let world = world_at(0.0, 0.0, 0.0, false);
+        let ball_pos = Vec2::new(-2.0, 3.0);
+        let command = defense(&world, ball_pos, &field, &config, false);
+        let target = command.target;
+        assert_relative_eq!(
+            line_distance(target.pos, goal, ball_pos),
+            0.0,
+            epsilon = 1e-4
+        );
+        assert_relative_eq!(
+            target.pos.distance(goal),
+            config.defense_distance,
+            epsilon = 1e-4
+        );
+        // 面向球
+        assert_relative_eq!(
+            target.angle,
+            (ball_pos - target.pos).to_angle(),
+            epsilon = 1e-4
+        );
+        assert_eq!(command.kick_ms, None);
+
+        // 守门员站得更靠近球门
+        let keeper = defense(&world, ball_pos, &field, &config, true);
+        assert_relative_eq!(
+            keeper.target.pos.distance(goal),
+            config.goalkeeper_defense_distance,
+            epsilon = 1e-4
+        );
+
+        // 球已很靠近球门：站在两者之间
+        let ball_pos = goal + Vec2::new(1.0, 0.5);
+        let command = defense(&world, ball_pos, &field, &config, false);
+        assert_relative_eq!(command.target.pos, (goal + ball_pos) / 2.0, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn catch_still_and_moving_ball() {
+        let config = BehaviourConfig::default();
+        // 静止的球：停在球前方，面向球
+        let world = world_at(0.0, 0.0, 0.0, false);
+        let ball = BallEstimate {
+            pos: Vec2::new(0.0, 2.0),
+            velocity: Vec2::ZERO,
+        };
+        let command = catch(&world, ball, 2.0, &config);
+        assert_relative_eq!(command.target.angle, PI / 2.0, epsilon = 1e-4);
+        assert_relative_eq!(
+            command.target.pos,
+            Vec2::new(0.0, 2.0 - config.ball_hold_distance),
+            epsilon = 1e-4
+        );
+        assert_eq!(command.ball_handler_rpm, Vec2::ZERO);
+
+        // 横穿的球：拦截点在球路上，且能及时赶到
+        let ball = BallEstimate {
+            pos: Vec2::new(-4.0, 1.0),
+            velocity: Vec2::new(2.0, 0.0),
+        };
+        let max_speed = 1.5;
+        let command = catch(&world, ball, max_speed, &config);
+        let target = command.target;
+        // 面向来球
+        assert_relative_eq!(wrap_angle(target.angle - PI), 0.0, epsilon = 1e-4);
+        let ball_at_catch = target.pos + Vec2::from_angle(target.angle) * config.ball_hold_distance;
+        assert_relative_eq!(ball_at_catch.y, 1.0, epsilon = 1e-4);
+        let ball_time = (ball_at_catch.x - ball.pos.x) / ball.velocity.x;
+        assert!(target.pos.length() / max_speed <= ball_time + 1e-3);
+        // 越早越好：再早0.1秒就赶不到
+        let earlier = ball.predict(ball_time - 0.1) - (ball_at_catch - target.pos);
+        assert!(earlier.length() / max_speed > ball_time - 0.1);
+
+        // 离球很近时打开吸球轮，持球后原地停下
+        let ball = BallEstimate {
+            pos: Vec2::new(0.5, 0.0),
+            velocity: Vec2::ZERO,
+        };
+        assert_eq!(
+            catch(&world, ball, max_speed, &config).ball_handler_rpm,
+            config.ball_handler_rpm
+        );
+        let holding = world_at(0.2, 0.0, 0.0, true);
+        assert_eq!(
+            catch(&holding, ball, max_speed, &config).target,
+            holding.pose
+        );
+    }
+
+    #[test]
+    fn pass_aim_then_kick() {
+        let config = BehaviourConfig::default();
+        let target_pos = Vec2::new(3.0, 4.0);
+        // 未瞄准：原地转向，吸住球
+        let world = world_at(0.0, 0.0, 0.0, true);
+        let command = pass(&world, target_pos, &config);
+        assert_eq!(command.target.pos, Vec2::ZERO);
+        assert_relative_eq!(command.target.angle, target_pos.to_angle());
+        assert_eq!(command.kick_ms, None);
+        assert_eq!(command.ball_handler_rpm, config.ball_handler_rpm);
+
+        // 已瞄准：射门，通电时间随距离增加
+        let world = world_at(0.0, 0.0, target_pos.to_angle() + 0.01, true);
+        let command = pass(&world, target_pos, &config);
+        assert_eq!(command.kick_ms, Some(30));
+        assert_eq!(command.ball_handler_rpm, Vec2::ZERO);
+        let far = pass(&world, target_pos * 100.0, &config);
+        assert_eq!(far.kick_ms, Some(config.kick_ms_max));
+
+        // 没有球时不射门
+        let world = RobotWorld {
+            has_ball: false,
+            ..world
+        };
+        assert_eq!(pass(&world, target_pos, &config).kick_ms, None);
+    }
+
+    #[test]
+    fn attack_defend_at_and_receive() {
+        let field = FieldData::default();
+        let config = BehaviourConfig::default();
+        let enemy_goal = Vec2::new(field.field_size.x / 2.0, 0.0);
+        let ball = BallEstimate {
+            pos: Vec2::new(2.0, 0.0),
+            velocity: Vec2::ZERO,
+        };
+        // 进攻：没有球时去拿球，持球后转向敌方球门
+        let world = world_at(0.0, 1.0, 0.0, false);
+        assert_eq!(
+            attack(&world, ball, 2.0, &field, &config),
+            catch(&world, ball, 2.0, &config)
+        );
+        let holding = world_at(0.0, 1.0, 0.0, true);
+        let command = attack(&holding, ball, 2.0, &field, &config);
+        assert_relative_eq!(
+            command.target.angle,
+            (enemy_goal - holding.pose.pos).to_angle()
+        );
+
+        // 防守指定位置：以己方球门中心为原点
+        let command = defend_at(PI / 4.0, 2.0, &field);
+        let goal = -enemy_goal;
+        assert_relative_eq!(
+            command.target.pos,
+            goal + Vec2::new(2.0_f32.sqrt(), 2.0_f32.sqrt()),
+            epsilon = 1e-4
+        );
+        assert_relative_eq!(command.target.angle, PI / 4.0);
+
+        // 接传球：球没有传来时面向传球位置等待
+        let from_pos = Vec2::new(0.0, 4.0);
+        let command = receive(&world, from_pos, Some(ball), 2.0, &config);
+        assert_eq!(command.target.pos, world.pose.pos);
+        assert_relative_eq!(command.target.angle, PI / 2.0);
+        assert_eq!(command.ball_handler_rpm, config.ball_handler_rpm);
+        assert_eq!(receive(&world, from_pos, None, 2.0, &config), command);
+        // 球传来后迎球
+        let passed = BallEstimate {
+            pos: from_pos,
+            velocity: Vec2::new(0.0, -3.0),
+        };
+        assert_eq!(
+            receive(&world, from_pos, Some(passed), 2.0, &config),
+            catch(&world, passed, 2.0, &config)
+        );
+    }
+
+    #[test]
+    fn search_ball_turns_in_place() {
+        let config = BehaviourConfig::default();
+        let world = world_at(1.0, -1.0, 3.0 * PI / 4.0, false);
+        let command = search_ball(&world, &config);
+        assert_eq!(command.target.pos, world.pose.pos);
+        // 超过PI后回绕
+        assert_relative_eq!(command.target.angle, -3.0 * PI / 4.0);
+        assert_eq!(command.ball_handler_rpm, Vec2::ZERO);
+        assert_eq!(command.kick_ms, None);
+    }
+
+    #[test]
+    fn track_ball_velocity() {
+        let mut tracker = BallTracker::default();
+        let start = Instant::now();
+        assert_eq!(tracker.update(Vec2::ZERO, start).velocity, Vec2::ZERO);
+        let mut estimate = BallEstimate::default();
+        for step in 1..=20 {
+            let time = start + Duration::from_millis(50 * step);
+            estimate = tracker.update(Vec2::new(0.1 * step as f32, 0.0), time);
+        }
+        assert_relative_eq!(estimate.velocity, Vec2::new(2.0, 0.0), epsilon = 1e-3);
+        assert_relative_eq!(estimate.predict(0.5), Vec2::new(3.0, 0.0), epsilon = 1e-3);
+    }
+}
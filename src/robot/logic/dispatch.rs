@@ -0,0 +1,381 @@
+//! 教练机指令分派：把旧版教练机数据包中的`LegacyCtrl`及其参数换算为`RobotCtrl`
+//!
+//! 需要球的指令使用教练机合并得到的球位置，找球类指令在没有看到球时原地转圈。
+//! 无法执行的指令（没有实现，或需要球但没有看到球）一律原地停下，并给出原因。
+
+use glam::Vec2;
+
+use crate::{
+    data_legacy::{convert::LegacyFrame, LegacyCtrl, LegacyPackFromCoach},
+    data_modern::ModernIntent,
+    field::world_state::RobotPose,
+    robot::motion::RobotCtrl,
+};
+
+/// 指令无法执行的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DispatchFallback {
+    /// 没有实现的指令
+    Unsupported,
+    /// 需要球的位置，但教练机没有看到球
+    NoBall,
+}
+
+/// 分派结果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dispatch {
+    pub ctrl: RobotCtrl,
+    /// 无法执行时为`Some`，此时`ctrl`为`RobotCtrl::Stop`
+    pub fallback: Option<DispatchFallback>,
+}
+
+impl Dispatch {
+    fn ok(ctrl: RobotCtrl) -> Self {
+        Self {
+            ctrl,
+            fallback: None,
+        }
+    }
+
+    fn fallback(fallback: DispatchFallback) -> Self {
+        Self {
+            ctrl: RobotCtrl::Stop,
+            fallback: Some(fallback),
+        }
+    }
+}
+
+/// 由教练机数据包得到机器人的控制指令
+pub fn dispatch(pack: &LegacyPackFromCoach, frame: &LegacyFrame) -> Dispatch {
+    let ball_pos = pack.ball(frame).map(|ball| ball.pos);
+    match ModernIntent::from_legacy(pack, frame) {
+        // 速度档位暂不使用，速度上限见`[pose_control]`
+        ModernIntent::MoveTo { target, .. } => Dispatch::ok(RobotCtrl::MoveTo { target }),
+        ModernIntent::Defence { angle, dist } => Dispatch::ok(RobotCtrl::DefendAt { angle, dist }),
+        ModernIntent::Pass { target_pos } => Dispatch::ok(RobotCtrl::Pass { target_pos }),
+        ModernIntent::Catch { from_pos } => Dispatch::ok(RobotCtrl::Receive { from_pos, ball_pos }),
+        ModernIntent::Ctrl(ctrl) => {
+            dispatch_ctrl(ctrl, frame.to_field_pos(pack.setup_pos), ball_pos)
+        }
+    }
+}
+
+/// 没有额外参数的指令
+fn dispatch_ctrl(ctrl: LegacyCtrl, setup_pos: Vec2, ball_pos: Option<Vec2>) -> Dispatch {
+    // 需要球的指令
+    let with_ball = |ctrl: fn(Vec2) -> RobotCtrl| {
+        ball_pos.map_or(Dispatch::fallback(DispatchFallback::NoBall), |ball_pos| {
+            Dispatch::ok(ctrl(ball_pos))
+        })
+    };
+    match ctrl {
+        LegacyCtrl::Stop | LegacyCtrl::Offline | LegacyCtrl::Idle => Dispatch::ok(RobotCtrl::Stop),
+        // 进攻、主罚定位球与点球：拿球后射门
+        LegacyCtrl::Attack
+        | LegacyCtrl::ShiftAtk
+        | LegacyCtrl::LSAtk
+        | LegacyCtrl::KickOffPrime
+        | LegacyCtrl::FreeKickPrime
+        | LegacyCtrl::GoalKickPrime
+        | LegacyCtrl::ThrowInPrime
+        | LegacyCtrl::CornerKickPrime
+        | LegacyCtrl::Penalty => with_ball(|ball_pos| RobotCtrl::Attack { ball_pos }),
+        // 站在球与己方球门之间
+        LegacyCtrl::Goalkeep
+        | LegacyCtrl::Block
+        | LegacyCtrl::ProDef
+        | LegacyCtrl::ZoneDef
+        | LegacyCtrl::DefBall
+        | LegacyCtrl::DefGoal
+        | LegacyCtrl::AtkCover
+        | LegacyCtrl::LSAtkCover
+        | LegacyCtrl::Follow => with_ball(|ball_pos| RobotCtrl::Defense { ball_pos }),
+        LegacyCtrl::FocusOnBall | LegacyCtrl::CatchFocus => {
+            with_ball(|ball_pos| RobotCtrl::FaceBall { ball_pos })
+        }
+        // 找球：没有看到球时原地转圈，看到球后去拿球射门
+        LegacyCtrl::SearchBall | LegacyCtrl::TechCompFindBall => Dispatch::ok(
+            ball_pos.map_or(RobotCtrl::SearchBall, |ball_pos| RobotCtrl::Attack {
+                ball_pos,
+            }),
+        ),
+        // 绕到球旁：靠近球并面向球，没有看到球时原地转圈
+        LegacyCtrl::AroundBall => Dispatch::ok(
+            ball_pos.map_or(RobotCtrl::SearchBall, |ball_pos| RobotCtrl::Catch {
+                ball_pos,
+            }),
+        ),
+        // 配合定位球：接主罚球员的传球
+        LegacyCtrl::KickOffSlave
+        | LegacyCtrl::FreeKickSlave
+        | LegacyCtrl::GoalKickSlave
+        | LegacyCtrl::ThrowInSlave
+        | LegacyCtrl::CornerKickSlave => with_ball(|ball_pos| RobotCtrl::Receive {
+            from_pos: ball_pos,
+            ball_pos: Some(ball_pos),
+        }),
+        // 定位球准备与退回：移动到教练机指定的站位，面向球（没有看到球时面向敌方球门）
+        LegacyCtrl::KickOffPrimeReady
+        | LegacyCtrl::KickOffSlaveReady
+        | LegacyCtrl::FreeKickPrimeReady
+        | LegacyCtrl::FreeKickSlaveReady
+        | LegacyCtrl::GoalKickPrimeReady
+        | LegacyCtrl::GoalKickSlaveReady
+        | LegacyCtrl::ThrowInPrimeReady
+        | LegacyCtrl::ThrowInSlaveReady
+        | LegacyCtrl::CornerKickPrimeReady
+        | LegacyCtrl::CornerKickSlaveReady
+        | LegacyCtrl::PenaltyReady
+        | LegacyCtrl::AntiKickOff
+        | LegacyCtrl::BackPos => {
+            let angle = ball_pos
+                .and_then(|ball_pos| (ball_pos - setup_pos).try_normalize())
+                .map_or(0.0, Vec2::to_angle);
+            Dispatch::ok(RobotCtrl::MoveTo {
+                target: RobotPose {
+                    pos: setup_pos,
+                    angle,
+                },
+            })
+        }
+        // 手动与遥控由其他模块处理；其余指令的参数在旧协议中没有定义
+        LegacyCtrl::Manual
+        | LegacyCtrl::RemoteCtrl
+        | LegacyCtrl::PassMove
+        | LegacyCtrl::CatchMove
+        | LegacyCtrl::Test
+        | LegacyCtrl::RobstNew
+        | LegacyCtrl::RobstErr
+        | LegacyCtrl::RobstProbe
+        | LegacyCtrl::UndefinedVal
+        // 带参数的指令由`ModernIntent`换算，不会到达此处
+        | LegacyCtrl::MoveTo
+        | LegacyCtrl::Defence
+        | LegacyCtrl::Pass
+        | LegacyCtrl::Catch => Dispatch::fallback(DispatchFallback::Unsupported),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        f32::consts::PI,
+        net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+        time::SystemTime,
+    };
+
+    use approx::assert_relative_eq;
+    use glam::I16Vec2;
+
+    use crate::{
+        coach::network::{CoachLegacyCommand, CoachLegacyInfo, RobotLegacyState},
+        data_legacy::LegacyPackFromRobot,
+        data_modern::PeerProtocol,
+    };
+
+    use super::*;
+
+    fn pack_with(ctrl: LegacyCtrl) -> LegacyPackFromCoach {
+        LegacyPackFromCoach {
+            ctrl,
+            found_ball: true,
+            ball_pos_from_coach: I16Vec2::new(100, -200),
+            setup_pos: I16Vec2::new(-300, 0),
+            target_pos: I16Vec2::new(250, 50),
+            target_angle: 90,
+            def_angle: 45,
+            def_dist: 150,
+            pass_target_pos: I16Vec2::new(-100, 300),
+            catch_from_pos: I16Vec2::new(400, 0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn dispatch_ctrl_with_params() {
+        let frame = LegacyFrame::default();
+        let Dispatch {
+            ctrl: RobotCtrl::MoveTo { target },
+            fallback: None,
+        } = dispatch(&pack_with(LegacyCtrl::MoveTo), &frame)
+        else {
+            panic!("MoveTo should be dispatched to MoveTo!");
+        };
+        assert_eq!(target.pos, Vec2::new(2.5, 0.5));
+        assert_relative_eq!(target.angle, PI / 2.0);
+        let Dispatch {
+            ctrl: RobotCtrl::DefendAt { angle, dist },
+            fallback: None,
+        } = dispatch(&pack_with(LegacyCtrl::Defence), &frame)
+        else {
+            panic!("Defence should be dispatched to DefendAt!");
+        };
+        assert_relative_eq!(angle, PI / 4.0);
+        assert_relative_eq!(dist, 1.5);
+        assert_eq!(
+            dispatch(&pack_with(LegacyCtrl::Pass), &frame),
+            Dispatch::ok(RobotCtrl::Pass {
+                target_pos: Vec2::new(-1.0, 3.0),
+            })
+        );
+        assert_eq!(
+            dispatch(&pack_with(LegacyCtrl::Catch), &frame),
+            Dispatch::ok(RobotCtrl::Receive {
+                from_pos: Vec2::new(4.0, 0.0),
+                ball_pos: Some(Vec2::new(1.0, -2.0)),
+            })
+        );
+        // 换边后位置旋转180°
+        let mirrored = LegacyFrame {
+            mirror: true,
+            ..frame
+        };
+        assert_eq!(
+            dispatch(&pack_with(LegacyCtrl::Pass), &mirrored),
+            Dispatch::ok(RobotCtrl::Pass {
+                target_pos: Vec2::new(1.0, -3.0),
+            })
+        );
+    }
+
+    #[test]
+    fn dispatch_ctrl_with_ball() {
+        let frame = LegacyFrame::default();
+        let ball_pos = Vec2::new(1.0, -2.0);
+        assert_eq!(
+            dispatch(&pack_with(LegacyCtrl::KickOffPrime), &frame),
+            Dispatch::ok(RobotCtrl::Attack { ball_pos })
+        );
+        assert_eq!(
+            dispatch(&pack_with(LegacyCtrl::ZoneDef), &frame),
+            Dispatch::ok(RobotCtrl::Defense { ball_pos })
+        );
+        assert_eq!(
+            dispatch(&pack_with(LegacyCtrl::SearchBall), &frame),
+            Dispatch::ok(RobotCtrl::Attack { ball_pos })
+        );
+        assert_eq!(
+            dispatch(&pack_with(LegacyCtrl::AroundBall), &frame),
+            Dispatch::ok(RobotCtrl::Catch { ball_pos })
+        );
+        // 定位球准备：站位面向球
+        let setup_pos = Vec2::new(-3.0, 0.0);
+        assert_eq!(
+            dispatch(&pack_with(LegacyCtrl::PenaltyReady), &frame),
+            Dispatch::ok(RobotCtrl::MoveTo {
+                target: RobotPose {
+                    pos: setup_pos,
+                    angle: (ball_pos - setup_pos).to_angle(),
+                },
+            })
+        );
+        // 没有看到球：需要球的指令停下，站位面向敌方球门
+        let no_ball = LegacyPackFromCoach {
+            found_ball: false,
+            ..pack_with(LegacyCtrl::Attack)
+        };
+        assert_eq!(
+            dispatch(&no_ball, &frame),
+            Dispatch::fallback(DispatchFallback::NoBall)
+        );
+        // 没有看到球：找球类指令原地转圈
+        for ctrl in [
+            LegacyCtrl::SearchBall,
+            LegacyCtrl::TechCompFindBall,
+            LegacyCtrl::AroundBall,
+        ] {
+            let no_ball = LegacyPackFromCoach { ctrl, ..no_ball };
+            assert_eq!(
+                dispatch(&no_ball, &frame),
+                Dispatch::ok(RobotCtrl::SearchBall)
+            );
+        }
+        let no_ball = LegacyPackFromCoach {
+            ctrl: LegacyCtrl::KickOffSlaveReady,
+            ..no_ball
+        };
+        assert_eq!(
+            dispatch(&no_ball, &frame),
+            Dispatch::ok(RobotCtrl::MoveTo {
+                target: RobotPose {
+                    pos: setup_pos,
+                    angle: 0.0,
+                },
+            })
+        );
+    }
+
+    /// 机器人数据包经教练机合并后，编码为教练机数据包再解码，与实际收到的相同
+    fn coach_pack_from_robots(balls: &[Option<I16Vec2>], ctrl: LegacyCtrl) -> LegacyPackFromCoach {
+        let robots: Vec<_> = (1u8..)
+            .zip(balls)
+            .map(|(id, ball)| {
+                let pack = LegacyPackFromRobot {
+                    id,
+                    pos: I16Vec2::new(-100 * i16::from(id), 0),
+                    has_ball: false,
+                    found_ball: ball.is_some(),
+                    found_ball_pos: ball.unwrap_or_default(),
+                    ..Default::default()
+                };
+                RobotLegacyState {
+                    pack: LegacyPackFromRobot::try_from_bytes(&pack.to_bytes())
+                        .expect("Failed to read data from bytes!"),
+                    addr: SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 20091)),
+                    receive_time: SystemTime::now(),
+                    protocol: PeerProtocol::Legacy,
+                    modern: None,
+                    last_header: None,
+                }
+            })
+            .collect();
+        let info = CoachLegacyInfo::merge(&robots.iter().collect::<Vec<_>>(), 50);
+        let command = CoachLegacyCommand {
+            ctrl,
+            ..Default::default()
+        };
+        LegacyPackFromCoach::try_from_bytes(&info.coach_pack(2, &command).to_bytes())
+            .expect("Failed to read data from bytes!")
+    }
+
+    #[test]
+    fn dispatch_merged_coach_pack() {
+        let frame = LegacyFrame::default();
+        // 只有1号机器人看到球
+        let pack =
+            coach_pack_from_robots(&[Some(I16Vec2::new(150, -50)), None], LegacyCtrl::Attack);
+        assert_eq!(
+            dispatch(&pack, &frame),
+            Dispatch::ok(RobotCtrl::Attack {
+                ball_pos: Vec2::new(1.5, -0.5),
+            })
+        );
+        // 没有机器人看到球
+        let pack = coach_pack_from_robots(&[None, None], LegacyCtrl::Attack);
+        assert_eq!(
+            dispatch(&pack, &frame),
+            Dispatch::fallback(DispatchFallback::NoBall)
+        );
+    }
+
+    #[test]
+    fn every_ctrl_dispatched() {
+        let frame = LegacyFrame::default();
+        for value in 0..=u8::MAX {
+            let ctrl = LegacyCtrl::from(value);
+            let result = dispatch(&pack_with(ctrl), &frame);
+            match result.fallback {
+                Some(_) => assert_eq!(result.ctrl, RobotCtrl::Stop),
+                None => assert!(ctrl != LegacyCtrl::UndefinedVal),
+            }
+        }
+        assert_eq!(
+            dispatch(&pack_with(LegacyCtrl::RemoteCtrl), &frame),
+            Dispatch::fallback(DispatchFallback::Unsupported)
+        );
+        assert_eq!(
+            dispatch(&pack_with(LegacyCtrl::Offline), &frame),
+            Dispatch::ok(RobotCtrl::Stop)
+        );
+    }
+}
@@ -1,8 +1,17 @@
-use std::f32::consts::PI;
+pub mod chassis;
+pub mod limiter;
+pub mod odometry;
+pub mod path_plan;
+pub mod pose_control;
+pub mod wheel_control;
 
 use bevy_ecs::prelude::*;
 use glam::{Vec2, Vec3};
 
+use crate::field::world_state::RobotPose;
+
+use self::chassis::ChassisConfig;
+
 /// 坐标系：场地中心为零点，敌方球门方向为x轴正方向，正前方朝着敌方球门时，左侧为y轴正方向，单位：米
 #[derive(Debug, Clone, Copy, Default, Resource)]
 pub struct RobotMotion {
@@ -14,6 +23,9 @@ pub struct RobotMotion {
     pub speed_angle: f32,
     /// 单位：米每秒
     pub speed_mps: f32,
+    /// 角速度
+    /// 单位：弧度每秒（逆时针为正）
+    pub yaw_rate: f32,
     /// 单位：转每分钟
     pub ball_take_wheel_speeds_rpm: Vec2,
     /// 吸球器触发指令
@@ -21,33 +33,47 @@ pub struct RobotMotion {
 }
 
 impl RobotMotion {
-    /// 底盘轮子半径
-    /// 单位：米
-    pub const BUTTOM_WHEEL_RADIUS: f32 = 0.005;
-    /// 底盘轮子安装位置相对于机器人正前方的角度
-    /// 轮子顺序：后侧、左侧、右侧
-    pub const WHEEL_INSTALL_PLACE_ANGLES: [f32; 3] = [-PI / 2.0, PI / 6.0, PI * 5.0 / 6.0];
-    /// 底盘轮子的顺时针转动（相对于马达）相对于机器人正前方的角度
-    /// 轮子顺序：后侧、左侧、右侧
-    pub const WHEEL_ROLL_ANGLES: [f32; 3] = [-PI, PI / 3.0, -PI / 3.0];
+    /// 机器人坐标系下的速度：正前方为x轴正方向，左侧为y轴正方向，单位：米每秒
+    /// `heading`为机器人正前方在场地坐标系中的角度
+    pub fn body_velocity(&self, heading: f32) -> Vec2 {
+        Vec2::from_angle(self.speed_angle - heading) * self.speed_mps
+    }
+
+    /// 各轮沿滚动方向的速度见`ChassisConfig::inverse_kinematics`
+    /// `heading`为机器人正前方在场地坐标系中的角度
+    /// 有电机超过`motor_max_rpm`时三个电机等比例减速，运动方向与转弯半径不变
     /// 轮子顺序：后侧、左侧、右侧
     /// 正值为顺时针
     /// 单位：转每分钟
-    pub fn get_motor_speeds(&self) -> Vec3 {
-        // 这两个相减顺序不影响cos函数结果
-        Vec3::from(
-            Self::WHEEL_ROLL_ANGLES
-                .map(|roll_angle| self.speed_mps * f32::cos(self.speed_angle - roll_angle))
-                .map(|roll_mps| roll_mps * 60.0)
-                .map(|roll_mpm| roll_mpm / (2.0 * PI * Self::BUTTOM_WHEEL_RADIUS)),
+    pub fn get_motor_speeds(&self, heading: f32, chassis: &ChassisConfig) -> Vec3 {
+        let wheel_mps = chassis.inverse_kinematics(self.body_velocity(heading), self.yaw_rate);
+        saturate_motor_speeds(
+            wheel_mps * chassis.motor_rpm_per_mps(),
+            chassis.motor_max_rpm,
         )
     }
 }
 
+/// 有电机超过`max_rpm`时三个电机等比例减速
+pub fn saturate_motor_speeds(rpm: Vec3, max_rpm: f32) -> Vec3 {
+    let peak_rpm = rpm.abs().max_element();
+    if peak_rpm > max_rpm {
+        rpm * (max_rpm / peak_rpm)
+    } else {
+        rpm
+    }
+}
+
 /// 机器人的控制指令
 /// 坐标系：场地中心为零点，敌方球门方向为x轴正方向，正前方朝着敌方球门时，左侧为y轴正方向，单位：米
 #[derive(Debug, Clone, Copy, PartialEq, Resource)]
 pub enum RobotCtrl {
+    /// 原地停下
+    Stop,
+    /// 移动到指定位置并转向指定朝向
+    MoveTo {
+        target: RobotPose,
+    },
     Pass {
         target_pos: Vec2,
     },
@@ -62,23 +88,137 @@ pub enum RobotCtrl {
     Defense {
         ball_pos: Vec2,
     },
+    /// 站在己方球门中心沿`angle`方向`dist`米处，面向外侧
+    DefendAt {
+        /// 单位：弧度
+        angle: f32,
+        /// 单位：米
+        dist: f32,
+    },
+    /// 进攻：拿球后射向敌方球门
+    Attack {
+        ball_pos: Vec2,
+    },
+    /// 接传球：面向传球的位置等待，球传来后迎球
+    Receive {
+        from_pos: Vec2,
+        /// 球的位置，没有看到球时为`None`
+        ball_pos: Option<Vec2>,
+    },
+    /// 原地转向球
+    FaceBall {
+        ball_pos: Vec2,
+    },
+    /// 找球：没有看到球时原地转圈
+    SearchBall,
+}
+
+impl RobotCtrl {
+    /// 指令中的球位置
+    pub fn ball_pos(&self) -> Option<Vec2> {
+        match *self {
+            RobotCtrl::Catch { ball_pos }
+            | RobotCtrl::Defense { ball_pos }
+            | RobotCtrl::Attack { ball_pos }
+            | RobotCtrl::FaceBall { ball_pos } => Some(ball_pos),
+            RobotCtrl::Receive { ball_pos, .. } => ball_pos,
+            RobotCtrl::Stop
+            | RobotCtrl::MoveTo { .. }
+            | RobotCtrl::Pass { .. }
+            | RobotCtrl::DefendAt { .. }
+            | RobotCtrl::SearchBall => None,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::f32::consts::PI;
+
+    use approx::assert_relative_eq;
+
     use super::*;
 
     #[test]
     fn test_wheel_speeds() {
+        let chassis = ChassisConfig::default();
         let robot_motion = RobotMotion {
             speed_angle: 0.0,
-            speed_mps: (2.0 * PI * RobotMotion::BUTTOM_WHEEL_RADIUS) / 60.0,
+            speed_mps: 1.0 / chassis.motor_rpm_per_mps(),
             ..Default::default()
         };
         let (back_rpm, left_rpm, right_rpm) =
-            <(f32, f32, f32)>::from(robot_motion.get_motor_speeds());
-        assert!(back_rpm - (-1.0) <= f32::EPSILON);
-        assert!(left_rpm - (0.5) <= f32::EPSILON);
-        assert!(right_rpm - (0.5) <= f32::EPSILON);
+            <(f32, f32, f32)>::from(robot_motion.get_motor_speeds(0.0, &chassis));
+        assert_relative_eq!(back_rpm, -1.0, epsilon = 1e-5);
+        assert_relative_eq!(left_rpm, 0.5, epsilon = 1e-5);
+        assert_relative_eq!(right_rpm, 0.5, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn body_frame_and_rotation() {
+        let chassis = ChassisConfig::default();
+        let rpm_per_mps = chassis.motor_rpm_per_mps();
+        // 朝向为PI/2时，向场地y轴正方向运动即向正前方运动
+        let forward = RobotMotion {
+            speed_angle: PI / 2.0,
+            speed_mps: 0.1,
+            ..Default::default()
+        };
+        let expected = Vec3::new(-0.1, 0.05, 0.05) * rpm_per_mps;
+        assert_relative_eq!(
+            forward.get_motor_speeds(PI / 2.0, &chassis),
+            expected,
+            epsilon = 1e-3
+        );
+        assert_relative_eq!(
+            forward.body_velocity(PI / 2.0),
+            Vec2::new(0.1, 0.0),
+            epsilon = 1e-6
+        );
+
+        // 原地逆时针旋转：三个轮子转速相同
+        let turn = RobotMotion {
+            yaw_rate: 0.5,
+            ..Default::default()
+        };
+        let wheel_rpm = -chassis.wheel_center_distance * 0.5 * rpm_per_mps;
+        assert_relative_eq!(
+            turn.get_motor_speeds(1.0, &chassis),
+            Vec3::splat(wheel_rpm),
+            epsilon = 1e-3
+        );
+
+        // 边走边转：平移与旋转叠加
+        let both = RobotMotion {
+            yaw_rate: 0.5,
+            ..forward
+        };
+        assert_relative_eq!(
+            both.get_motor_speeds(PI / 2.0, &chassis),
+            expected + Vec3::splat(wheel_rpm),
+            epsilon = 1e-3
+        );
+    }
+
+    #[test]
+    fn saturation_keeps_direction() {
+        let chassis = ChassisConfig::default();
+        let fast = RobotMotion {
+            speed_angle: 0.3,
+            speed_mps: 50.0,
+            yaw_rate: 10.0,
+            ..Default::default()
+        };
+        let rpm = fast.get_motor_speeds(0.0, &chassis);
+        assert_relative_eq!(rpm.abs().max_element(), chassis.motor_max_rpm);
+        let raw = RobotMotion {
+            speed_mps: 50.0 / 1000.0,
+            yaw_rate: 10.0 / 1000.0,
+            ..fast
+        }
+        .get_motor_speeds(0.0, &chassis);
+        // 各轮转速的比例不变
+        assert_relative_eq!(rpm.normalize(), raw.normalize(), epsilon = 1e-5);
+        assert_relative_eq!(saturate_motor_speeds(raw, chassis.motor_max_rpm), raw);
     }
 }
@@ -0,0 +1,193 @@
+//! 底盘参数与运动学，参数保存在`robot_config/config.toml`的`[chassis]`中，每台机器人可单独标定
+
+use std::f32::consts::PI;
+
+use glam::{Mat3, Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::robot::com_robot::MOTOR_COUNT;
+
+/// 两次码盘读数之间电机转动圈数的上限，超过时视为下位机重启
+pub const ODOMETRY_MAX_MOTOR_ROUNDS: i32 = 20;
+
+/// 底盘参数
+/// 机器人坐标系：正前方为x轴正方向，左侧为y轴正方向
+/// 轮子顺序：后侧、左侧、右侧
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChassisConfig {
+    /// 底盘轮子半径
+    /// 单位：米
+    pub wheel_radius: f32,
+    /// 底盘轮子到机器人中心的距离
+    /// 单位：米
+    pub wheel_center_distance: f32,
+    /// 底盘轮子的顺时针转动（相对于马达）相对于机器人正前方的角度
+    /// 单位：弧度
+    pub wheel_roll_angles: [f32; MOTOR_COUNT],
+    /// 减速比：电机转一圈时轮子转`1 / gear_ratio`圈
+    pub gear_ratio: f32,
+    /// 电机转一圈码盘的变化量
+    pub encoder_ticks_per_rev: i32,
+    /// 电机转速上限
+    /// 单位：转每分钟
+    pub motor_max_rpm: f32,
+    /// 电机电流上限
+    /// 单位：毫安
+    pub motor_max_current_ma: u16,
+}
+
+impl Default for ChassisConfig {
+    fn default() -> Self {
+        Self {
+            wheel_radius: 0.05,
+            wheel_center_distance: 0.2,
+            wheel_roll_angles: [-PI, PI / 3.0, -PI / 3.0],
+            gear_ratio: 1.0,
+            encoder_ticks_per_rev: 2500,
+            motor_max_rpm: 6000.0,
+            motor_max_current_ma: 5000,
+        }
+    }
+}
+
+impl ChassisConfig {
+    /// 检查参数：轮子半径、减速比与码盘变化量必须大于0，否则使用默认值
+    pub fn validate(&mut self) {
+        let default = Self::default();
+        if !(self.wheel_radius.is_finite() && self.wheel_radius > 0.0) {
+            bevy::log::warn!(
+                "Invalid chassis wheel_radius {}, use default {}",
+                self.wheel_radius,
+                default.wheel_radius
+            );
+            self.wheel_radius = default.wheel_radius;
+        }
+        if !(self.gear_ratio.is_finite() && self.gear_ratio > 0.0) {
+            bevy::log::warn!(
+                "Invalid chassis gear_ratio {}, use default {}",
+                self.gear_ratio,
+                default.gear_ratio
+            );
+            self.gear_ratio = default.gear_ratio;
+        }
+        if self.encoder_ticks_per_rev <= 0 {
+            bevy::log::warn!(
+                "Invalid chassis encoder_ticks_per_rev {}, use default {}",
+                self.encoder_ticks_per_rev,
+                default.encoder_ticks_per_rev
+            );
+            self.encoder_ticks_per_rev = default.encoder_ticks_per_rev;
+        }
+    }
+
+    /// 轮子滚动1米每秒对应的电机转速
+    /// 单位：转每分钟
+    pub fn motor_rpm_per_mps(&self) -> f32 {
+        60.0 * self.gear_ratio / (2.0 * PI * self.wheel_radius)
+    }
+
+    /// 码盘变化量对应的轮子滚动距离，单位：米
+    pub fn wheel_distance(&self, pos_delta: i32) -> f32 {
+        let motor_rounds = pos_delta as f32 / self.encoder_ticks_per_rev as f32;
+        motor_rounds / self.gear_ratio * 2.0 * PI * self.wheel_radius
+    }
+
+    /// 两次码盘读数之间变化量的上限
+    pub fn max_encoder_delta(&self) -> i32 {
+        self.encoder_ticks_per_rev
+            .saturating_mul(ODOMETRY_MAX_MOTOR_ROUNDS)
+    }
+
+    /// 逆运动学：机器人坐标系下的速度与角速度 → 各轮沿滚动方向的速度
+    /// 轮子`i`沿滚动方向的速度为`vx * cos(φi) + vy * sin(φi) - L * ω`，
+    /// 其中`φi`为`wheel_roll_angles`，`L`为`wheel_center_distance`
+    pub fn inverse_kinematics(&self, body_velocity: Vec2, yaw_rate: f32) -> Vec3 {
+        Vec3::from(self.wheel_roll_angles.map(|roll_angle| {
+            body_velocity.dot(Vec2::from_angle(roll_angle)) - self.wheel_center_distance * yaw_rate
+        }))
+    }
+
+    /// 正运动学：各轮沿滚动方向的位移（或速度）→ 机器人坐标系下的位移（或速度）与转角（或角速度）
+    pub fn forward_kinematics(&self, wheel: Vec3) -> (Vec2, f32) {
+        let [back, left, right] = self
+            .wheel_roll_angles
+            .map(|angle| Vec3::new(angle.cos(), angle.sin(), -self.wheel_center_distance));
+        let body = Mat3::from_cols(back, left, right).transpose().inverse() * wheel;
+        (body.truncate(), body.z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn kinematics_round_trip() {
+        let chassis = ChassisConfig::default();
+        for (body, yaw_rate) in [
+            (Vec2::new(1.0, 0.0), 0.0),
+            (Vec2::new(-0.3, 0.8), 0.0),
+            (Vec2::ZERO, 2.0),
+            (Vec2::new(0.5, -0.5), -1.0),
+        ] {
+            let wheel = chassis.inverse_kinematics(body, yaw_rate);
+            let (fk_body, fk_yaw_rate) = chassis.forward_kinematics(wheel);
+            assert_relative_eq!(fk_body, body, epsilon = 1e-5);
+            assert_relative_eq!(fk_yaw_rate, yaw_rate, epsilon = 1e-5);
+        }
+    }
+
+    #[test]
+    fn gear_ratio() {
+        let chassis = ChassisConfig {
+            gear_ratio: 4.0,
+            ..Default::default()
+        };
+        // 电机转4圈，轮子转1圈
+        assert_relative_eq!(
+            chassis.wheel_distance(4 * chassis.encoder_ticks_per_rev),
+            2.0 * PI * chassis.wheel_radius
+        );
+        assert_relative_eq!(
+            chassis.motor_rpm_per_mps() * 2.0 * PI * chassis.wheel_radius,
+            4.0 * 60.0
+        );
+    }
+
+    #[test]
+    fn invalid_chassis_use_default() {
+        let mut config: crate::robot::RobotConfig = toml::from_str(
+            "[chassis]\nwheel_radius = 0.0\ngear_ratio = -2.0\nencoder_ticks_per_rev = 0\nwheel_center_distance = 0.25\n",
+        )
+        .expect("Failed to parse config!");
+        config.chassis.validate();
+        let default = ChassisConfig::default();
+        assert_eq!(config.chassis.wheel_radius, default.wheel_radius);
+        assert_eq!(config.chassis.gear_ratio, default.gear_ratio);
+        assert_eq!(
+            config.chassis.encoder_ticks_per_rev,
+            default.encoder_ticks_per_rev
+        );
+        // 有效的参数保留
+        assert_eq!(config.chassis.wheel_center_distance, 0.25);
+    }
+
+    #[test]
+    fn load_chassis_table() {
+        let config: crate::robot::RobotConfig = toml::from_str(
+            "[chassis]\nwheel_radius = 0.048\ngear_ratio = 3.0\nencoder_ticks_per_rev = 4096\n",
+        )
+        .expect("Failed to parse config!");
+        assert_eq!(config.chassis.wheel_radius, 0.048);
+        assert_eq!(config.chassis.gear_ratio, 3.0);
+        assert_eq!(config.chassis.encoder_ticks_per_rev, 4096);
+        // 未写出的参数使用默认值
+        assert_eq!(
+            config.chassis.wheel_roll_angles,
+            ChassisConfig::default().wheel_roll_angles
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! 速度指令的加速度与加加速度限制
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use super::RobotMotion;
+
+/// 加速度与加加速度上限，均须大于0
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MotionLimits {
+    /// 单位：米每二次方秒
+    pub max_acc: f32,
+    /// 单位：米每三次方秒
+    pub max_jerk: f32,
+    /// 单位：弧度每二次方秒
+    pub max_yaw_acc: f32,
+    /// 单位：弧度每三次方秒
+    pub max_yaw_jerk: f32,
+}
+
+impl Default for MotionLimits {
+    fn default() -> Self {
+        Self {
+            max_acc: 3.0,
+            max_jerk: 30.0,
+            max_yaw_acc: 12.0,
+            max_yaw_jerk: 120.0,
+        }
+    }
+}
+
+/// 记录上一次输出的速度与加速度，使速度指令平滑变化
+/// 坐标系：场地坐标系
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MotionLimiter {
+    /// 单位：米每秒
+    pub velocity: Vec2,
+    /// 单位：米每二次方秒
+    pub acc: Vec2,
+    /// 单位：弧度每秒
+    pub yaw_rate: f32,
+    /// 单位：弧度每二次方秒
+    pub yaw_acc: f32,
+}
+
+impl MotionLimiter {
+    /// 经过`dt`秒，向目标速度与角速度靠近一步
+    pub fn step(
+        &mut self,
+        target_velocity: Vec2,
+        target_yaw_rate: f32,
+        limits: &MotionLimits,
+        dt: f32,
+    ) {
+        if dt <= 0.0 {
+            return;
+        }
+        (self.velocity, self.acc) = step_towards(
+            self.velocity,
+            self.acc,
+            target_velocity,
+            limits.max_acc,
+            limits.max_jerk,
+            dt,
+        );
+        let (yaw_rate, yaw_acc) = step_towards(
+            Vec2::new(self.yaw_rate, 0.0),
+            Vec2::new(self.yaw_acc, 0.0),
+            Vec2::new(target_yaw_rate, 0.0),
+            limits.max_yaw_acc,
+            limits.max_yaw_jerk,
+            dt,
+        );
+        (self.yaw_rate, self.yaw_acc) = (yaw_rate.x, yaw_acc.x);
+    }
+
+    /// 把`motion`中的速度与角速度替换为限制后的值
+    pub fn apply(&mut self, motion: &mut RobotMotion, limits: &MotionLimits, dt: f32) {
+        let target_velocity = Vec2::from_angle(motion.speed_angle) * motion.speed_mps;
+        self.step(target_velocity, motion.yaw_rate, limits, dt);
+        motion.speed_mps = self.velocity.length();
+        if motion.speed_mps > 0.0 {
+            motion.speed_angle = self.velocity.to_angle();
+        }
+        motion.yaw_rate = self.yaw_rate;
+    }
+}
+
+/// 加速度朝着期望值变化，变化率不超过`max_jerk`。
+/// 期望的加速度不超过`max_acc`，并在接近目标时按`sqrt(2 * max_jerk * 误差)`减小，以免超调
+fn step_towards(
+    velocity: Vec2,
+    acc: Vec2,
+    target: Vec2,
+    max_acc: f32,
+    max_jerk: f32,
+    dt: f32,
+) -> (Vec2, Vec2) {
+    let error = target - velocity;
+    let distance = error.length();
+    let desired_acc = if distance > 0.0 {
+        let acc_len = max_acc
+            .min((2.0 * max_jerk * distance).sqrt())
+            .min(distance / dt);
+        error / distance * acc_len
+    } else {
+        Vec2::ZERO
+    };
+    let acc = acc + (desired_acc - acc).clamp_length_max(max_jerk * dt);
+    (velocity + acc * dt, acc)
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    const DT: f32 = 0.002;
+
+    #[test]
+    fn accelerate_within_limits() {
+        let limits = MotionLimits::default();
+        let target = Vec2::new(2.0, -1.0);
+        let mut limiter = MotionLimiter::default();
+        let mut last_acc = Vec2::ZERO;
+        for _ in 0..2000 {
+            limiter.step(target, 0.0, &limits, DT);
+            assert!(limiter.acc.length() <= limits.max_acc + 1e-4);
+            assert!((limiter.acc - last_acc).length() <= limits.max_jerk * DT + 1e-4);
+            // 不超调
+            assert!(limiter.velocity.length() <= target.length() + 1e-2);
+            last_acc = limiter.acc;
+        }
+        assert_relative_eq!(limiter.velocity, target, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn apply_to_motion() {
+        let limits = MotionLimits::default();
+        let mut limiter = MotionLimiter::default();
+        let target = RobotMotion {
+            speed_angle: 1.0,
+            speed_mps: 1.5,
+            yaw_rate: -2.0,
+            ..Default::default()
+        };
+        let mut motion = target;
+        limiter.apply(&mut motion, &limits, DT);
+        // 第一步只能加速一点点
+        assert!(motion.speed_mps < 1e-3 && motion.yaw_rate.abs() < 1e-3);
+        for _ in 0..2000 {
+            motion = target;
+            limiter.apply(&mut motion, &limits, DT);
+        }
+        assert_relative_eq!(motion.speed_mps, 1.5, epsilon = 1e-3);
+        assert_relative_eq!(motion.speed_angle, 1.0, epsilon = 1e-3);
+        assert_relative_eq!(motion.yaw_rate, -2.0, epsilon = 1e-3);
+    }
+}
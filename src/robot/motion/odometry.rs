@@ -0,0 +1,215 @@
+//! 轮式里程计：由码盘读数推算机器人的速度与位姿变化
+
+use std::time::Instant;
+
+use bevy::prelude::*;
+use glam::{Vec2, Vec3};
+
+use crate::{
+    field::world_state::wrap_angle,
+    robot::{
+        com_mpu::orientation::RobotHeading,
+        com_robot::{RobotLowerData, MOTOR_COUNT},
+        RobotConfig,
+    },
+};
+
+use super::chassis::ChassisConfig;
+
+pub(in crate::robot) struct RobotOdometryPlugin;
+
+impl Plugin for RobotOdometryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RobotOdometry>()
+            .add_systems(FixedUpdate, odometry_system);
+    }
+}
+
+/// 码盘变化量，计数器溢出时按回绕处理。变化超过`max_delta`时返回`None`
+pub fn encoder_delta(last_pos: i32, now_pos: i32, max_delta: i32) -> Option<i32> {
+    let delta = now_pos.wrapping_sub(last_pos);
+    (delta.unsigned_abs() <= max_delta.unsigned_abs()).then_some(delta)
+}
+
+/// 里程计结果
+/// 坐标系：场地中心为零点，敌方球门方向为x轴正方向，正前方朝着敌方球门时，左侧为y轴正方向，单位：米
+#[derive(Debug, Clone, Copy, Default, PartialEq, Resource)]
+pub struct RobotOdometry {
+    /// 机器人坐标系下的速度（正前方为x轴正方向），单位：米每秒
+    pub body_velocity: Vec2,
+    /// 由轮子推算的角速度，单位：弧度每秒（逆时针为正）
+    pub yaw_rate: f32,
+    /// 最近一次读数的位置变化，场地坐标系
+    pub delta_pos: Vec2,
+    /// 最近一次读数的朝向变化，单位：弧度
+    pub delta_yaw: f32,
+    /// 自启动以来累计的位置，仅由里程计推算，会有累积误差
+    pub pos: Vec2,
+    /// 当前朝向，有MPU数据时使用MPU的朝向，单位：弧度，范围(-PI, PI]
+    pub yaw: f32,
+    /// 最近一次码盘读数的接收时间
+    pub update_time: Option<Instant>,
+    last_rotate_pos: Option<[i32; MOTOR_COUNT]>,
+}
+
+impl RobotOdometry {
+    /// 加入一次码盘读数，`heading`为MPU给出的场地朝向。
+    /// 第一次读数或下位机重启后只记录读数，返回`false`
+    pub fn update(
+        &mut self,
+        rotate_pos: [i32; MOTOR_COUNT],
+        time: Instant,
+        heading: Option<f32>,
+        chassis: &ChassisConfig,
+    ) -> bool {
+        let last = self.last_rotate_pos.replace(rotate_pos);
+        let last_time = self.update_time.replace(time);
+        let (Some(last), Some(last_time)) = (last, last_time) else {
+            self.yaw = heading.unwrap_or(self.yaw);
+            return false;
+        };
+        let dt = time.saturating_duration_since(last_time).as_secs_f32();
+        let mut wheel = Vec3::ZERO;
+        for (index, (last_pos, now_pos)) in last.iter().zip(rotate_pos).enumerate() {
+            match encoder_delta(*last_pos, now_pos, chassis.max_encoder_delta()) {
+                Some(delta) if dt > 0.0 => wheel[index] = chassis.wheel_distance(delta),
+                _ => {
+                    warn!("Odometry: encoder jumped from {last:?} to {rotate_pos:?}, restarting");
+                    self.yaw = heading.unwrap_or(self.yaw);
+                    return false;
+                }
+            }
+        }
+        let (body_delta, wheel_delta_yaw) = chassis.forward_kinematics(wheel);
+        let last_yaw = self.yaw;
+        self.yaw = heading.unwrap_or(wrap_angle(last_yaw + wheel_delta_yaw));
+        self.delta_yaw = wrap_angle(self.yaw - last_yaw);
+        // 用中间时刻的朝向把位移转到场地坐标系
+        let mid_yaw = last_yaw + self.delta_yaw / 2.0;
+        self.delta_pos = Vec2::from_angle(mid_yaw).rotate(body_delta);
+        self.pos += self.delta_pos;
+        self.body_velocity = body_delta / dt;
+        self.yaw_rate = wheel_delta_yaw / dt;
+        true
+    }
+}
+
+pub(in crate::robot) fn odometry_system(
+    config: Res<RobotConfig>,
+    lower_data: Res<RobotLowerData>,
+    heading: Option<Res<RobotHeading>>,
+    mut odometry: ResMut<RobotOdometry>,
+) {
+    let Some(encoder_time) = lower_data.encoder_time else {
+        return;
+    };
+    if odometry.update_time == Some(encoder_time) {
+        return;
+    }
+    let rotate_pos = lower_data.motor_status.map(|status| status.rotate_pos);
+    odometry.update(
+        rotate_pos,
+        encoder_time,
+        heading.map(|heading| heading.yaw),
+        &config.chassis,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{f32::consts::PI, time::Duration};
+
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    /// 逆运动学：机器人坐标系下的位移与转角 → 各轮码盘变化量
+    fn pos_deltas(body: Vec2, yaw: f32) -> [i32; MOTOR_COUNT] {
+        let chassis = ChassisConfig::default();
+        let ticks_per_meter = chassis.wheel_distance(1).recip();
+        chassis
+            .inverse_kinematics(body, yaw)
+            .to_array()
+            .map(|distance| (distance * ticks_per_meter).round() as i32)
+    }
+
+    /// 以固定速度运行`steps`个10毫秒
+    fn run(
+        odometry: &mut RobotOdometry,
+        start_pos: [i32; MOTOR_COUNT],
+        body: Vec2,
+        yaw_rate: f32,
+        steps: u32,
+    ) {
+        let start = Instant::now();
+        let step_deltas = pos_deltas(body * 0.01, yaw_rate * 0.01);
+        let mut rotate_pos = start_pos;
+        odometry.update(rotate_pos, start, None, &ChassisConfig::default());
+        for step in 1..=steps {
+            for (pos, delta) in rotate_pos.iter_mut().zip(step_deltas) {
+                *pos = pos.wrapping_add(delta);
+            }
+            assert!(odometry.update(
+                rotate_pos,
+                start + Duration::from_millis(10 * step as u64),
+                None,
+                &ChassisConfig::default()
+            ));
+        }
+    }
+
+    #[test]
+    fn drive_forward_across_wraparound() {
+        let mut odometry = RobotOdometry::default();
+        // 起点靠近i32上限，行驶中计数器溢出
+        run(
+            &mut odometry,
+            [i32::MAX - 1000; 3],
+            Vec2::new(1.0, 0.0),
+            0.0,
+            100,
+        );
+        assert_relative_eq!(odometry.pos, Vec2::new(1.0, 0.0), epsilon = 1e-2);
+        assert_relative_eq!(odometry.body_velocity, Vec2::new(1.0, 0.0), epsilon = 1e-2);
+        assert_relative_eq!(odometry.yaw, 0.0, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn turn_and_heading() {
+        let mut odometry = RobotOdometry::default();
+        // 原地转半圈
+        run(&mut odometry, [0; 3], Vec2::ZERO, PI, 100);
+        assert_relative_eq!(odometry.yaw.abs(), PI, epsilon = 1e-2);
+        assert_relative_eq!(odometry.yaw_rate, PI, epsilon = 1e-2);
+        assert_relative_eq!(odometry.pos, Vec2::ZERO, epsilon = 1e-3);
+
+        // 有MPU朝向时，位移按MPU朝向转到场地坐标系
+        let mut odometry = RobotOdometry::default();
+        let start = Instant::now();
+        odometry.update([0; 3], start, Some(PI / 2.0), &ChassisConfig::default());
+        odometry.update(
+            pos_deltas(Vec2::new(0.1, 0.0), 0.0),
+            start + Duration::from_millis(100),
+            Some(PI / 2.0),
+            &ChassisConfig::default(),
+        );
+        assert_relative_eq!(odometry.delta_pos, Vec2::new(0.0, 0.1), epsilon = 1e-3);
+    }
+
+    #[test]
+    fn lower_restart() {
+        let chassis = ChassisConfig::default();
+        let mut odometry = RobotOdometry::default();
+        let start = Instant::now();
+        assert!(!odometry.update([500_000; 3], start, None, &chassis));
+        // 下位机重启，计数归零
+        assert!(!odometry.update([0; 3], start + Duration::from_millis(10), None, &chassis));
+        assert!(odometry.update(
+            [10, 10, 10],
+            start + Duration::from_millis(20),
+            None,
+            &chassis
+        ));
+        assert_eq!(odometry.pos, odometry.delta_pos);
+    }
+}
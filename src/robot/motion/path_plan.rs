@@ -0,0 +1,458 @@
+//! 路径规划：由场地边界、禁区与障碍物建立地图，用可见图求无碰撞的路径点
+//!
+//! 障碍物按机器人半径与安全距离膨胀，机器人视为一个点。
+//! 圆形障碍物用外接正多边形的顶点作为可见图的节点，矩形区域用四个角作为节点，
+//! 连线只需不穿过各区域的内部，每个周期按最新的障碍物重新规划。
+
+use std::{cmp::Ordering, collections::BinaryHeap, f32::consts::TAU};
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::{field::FieldData, robot::panorama_camera::PanoramaBarrier};
+
+/// 判断点在区域内或连线穿过区域时的容差，单位：米
+const PLAN_EPSILON: f32 = 1e-3;
+
+/// 路径规划参数
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PathPlanConfig {
+    /// 机器人半径，单位：米
+    pub robot_radius: f32,
+    /// 与障碍物之间额外保留的距离，单位：米
+    pub clearance: f32,
+    /// 允许越出边线的距离，单位：米
+    pub boundary_margin: f32,
+    /// 圆形障碍物近似为正多边形的边数
+    pub circle_segments: usize,
+    /// 不进入对方大禁区
+    pub avoid_opponent_penalty_area: bool,
+    /// 不进入己方小禁区（守门员除外）
+    pub avoid_own_goal_area: bool,
+}
+
+impl Default for PathPlanConfig {
+    fn default() -> Self {
+        Self {
+            robot_radius: 0.26,
+            clearance: 0.1,
+            boundary_margin: 0.5,
+            circle_segments: 12,
+            avoid_opponent_penalty_area: true,
+            avoid_own_goal_area: true,
+        }
+    }
+}
+
+/// 地图中的不可进入区域，已按机器人半径膨胀
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlanArea {
+    Circle { center: Vec2, radius: f32 },
+    Rect { min: Vec2, max: Vec2 },
+}
+
+impl PlanArea {
+    /// 点在区域内部（不含边界附近`PLAN_EPSILON`以内）
+    pub fn contains(&self, point: Vec2) -> bool {
+        match *self {
+            PlanArea::Circle { center, radius } => point.distance(center) < radius - PLAN_EPSILON,
+            PlanArea::Rect { min, max } => {
+                point.cmpgt(min + PLAN_EPSILON).all() && point.cmplt(max - PLAN_EPSILON).all()
+            }
+        }
+    }
+
+    /// 线段穿过区域内部
+    pub fn blocks(&self, start: Vec2, end: Vec2) -> bool {
+        match *self {
+            PlanArea::Circle { center, radius } => {
+                segment_distance(center, start, end) < radius - PLAN_EPSILON
+            }
+            PlanArea::Rect { min, max } => {
+                // Liang–Barsky裁剪：线段与缩小后的矩形有交集即穿过内部
+                let (min, max) = (min + PLAN_EPSILON, max - PLAN_EPSILON);
+                let delta = end - start;
+                let (mut t_enter, mut t_exit) = (0.0f32, 1.0f32);
+                for axis in 0..2 {
+                    if delta[axis].abs() < f32::EPSILON {
+                        if start[axis] <= min[axis] || start[axis] >= max[axis] {
+                            return false;
+                        }
+                        continue;
+                    }
+                    let t0 = (min[axis] - start[axis]) / delta[axis];
+                    let t1 = (max[axis] - start[axis]) / delta[axis];
+                    t_enter = t_enter.max(t0.min(t1));
+                    t_exit = t_exit.min(t0.max(t1));
+                }
+                t_enter < t_exit
+            }
+        }
+    }
+
+    /// 可见图的节点：区域外侧的顶点
+    fn vertices(&self, circle_segments: usize) -> Vec<Vec2> {
+        match *self {
+            PlanArea::Circle { center, radius } => {
+                let segments = circle_segments.max(3);
+                let step = TAU / segments as f32;
+                // 外接正多边形，各边都在圆外
+                let vertex_radius = radius / (step / 2.0).cos() + 2.0 * PLAN_EPSILON;
+                (0..segments)
+                    .map(|index| center + Vec2::from_angle(step * index as f32) * vertex_radius)
+                    .collect()
+            }
+            PlanArea::Rect { min, max } => {
+                let (min, max) = (min - 2.0 * PLAN_EPSILON, max + 2.0 * PLAN_EPSILON);
+                vec![min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)]
+            }
+        }
+    }
+
+    /// 区域外靠近`point`的点，按距离从近到远排列
+    fn outside_candidates(&self, point: Vec2) -> Vec<Vec2> {
+        match *self {
+            PlanArea::Circle { center, radius } => {
+                let direction = (point - center).try_normalize().unwrap_or(Vec2::X);
+                vec![center + direction * (radius + 2.0 * PLAN_EPSILON)]
+            }
+            PlanArea::Rect { min, max } => {
+                let mut candidates = vec![
+                    Vec2::new(min.x - 2.0 * PLAN_EPSILON, point.y),
+                    Vec2::new(max.x + 2.0 * PLAN_EPSILON, point.y),
+                    Vec2::new(point.x, min.y - 2.0 * PLAN_EPSILON),
+                    Vec2::new(point.x, max.y + 2.0 * PLAN_EPSILON),
+                ];
+                candidates.sort_by(|a, b| a.distance(point).total_cmp(&b.distance(point)));
+                candidates
+            }
+        }
+    }
+}
+
+/// 点到线段的距离
+fn segment_distance(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+    let delta = end - start;
+    let length_squared = delta.length_squared();
+    if length_squared <= f32::EPSILON {
+        return point.distance(start);
+    }
+    let t = ((point - start).dot(delta) / length_squared).clamp(0.0, 1.0);
+    point.distance(start + delta * t)
+}
+
+/// 规划用的地图
+/// 坐标系：场地中心为零点，敌方球门方向为x轴正方向，正前方朝着敌方球门时，左侧为y轴正方向，单位：米
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlanMap {
+    /// 机器人中心可到达的范围
+    pub bounds_min: Vec2,
+    pub bounds_max: Vec2,
+    pub areas: Vec<PlanArea>,
+    circle_segments: usize,
+}
+
+impl PlanMap {
+    /// 由场地与障碍物建立地图。`is_goalkeeper`为真时可以进入己方小禁区。
+    /// 障碍物的`size`视为直径
+    pub fn new(
+        field: &FieldData,
+        barriers: &[PanoramaBarrier],
+        config: &PathPlanConfig,
+        is_goalkeeper: bool,
+    ) -> Self {
+        let inflate = config.robot_radius + config.clearance;
+        let half_field = field.field_size / 2.0;
+        let bounds = half_field + config.boundary_margin - config.robot_radius;
+        let mut areas = Vec::new();
+        // 禁区从底线向场内延伸，y方向居中
+        let goal_line_area = |size: Vec2, opponent: bool| {
+            let (inner_x, outer_x) = if opponent {
+                (half_field.x - size.x, half_field.x + config.boundary_margin)
+            } else {
+                (
+                    -half_field.x - config.boundary_margin,
+                    -half_field.x + size.x,
+                )
+            };
+            PlanArea::Rect {
+                min: Vec2::new(inner_x, -size.y / 2.0) - inflate,
+                max: Vec2::new(outer_x, size.y / 2.0) + inflate,
+            }
+        };
+        if config.avoid_opponent_penalty_area {
+            areas.push(goal_line_area(field.penalty_area_size, true));
+        }
+        if config.avoid_own_goal_area && !is_goalkeeper {
+            areas.push(goal_line_area(field.goal_area_size, false));
+        }
+        areas.extend(barriers.iter().map(|barrier| PlanArea::Circle {
+            center: barrier.pos,
+            radius: barrier.size / 2.0 + inflate,
+        }));
+        Self {
+            bounds_min: -bounds,
+            bounds_max: bounds,
+            areas,
+            circle_segments: config.circle_segments,
+        }
+    }
+
+    fn in_bounds(&self, point: Vec2) -> bool {
+        point.cmpge(self.bounds_min).all() && point.cmple(self.bounds_max).all()
+    }
+
+    /// 点可以到达：在边界内且不在任何区域内
+    pub fn is_free(&self, point: Vec2) -> bool {
+        self.in_bounds(point) && !self.areas.iter().any(|area| area.contains(point))
+    }
+
+    /// 两点之间的连线不穿过任何区域。边界为凸区域，两端在边界内即可
+    fn visible(&self, start: Vec2, end: Vec2, ignored: &[bool]) -> bool {
+        self.areas
+            .iter()
+            .zip(ignored)
+            .all(|(area, ignored)| *ignored || !area.blocks(start, end))
+    }
+
+    /// 从`start`到`goal`的路径点，不含`start`，最后一个为（可能被移出区域的）终点。
+    /// 起点所在的区域不参与规划，以便离开；终点在区域内时改为区域外最近的可到达点；
+    /// 找不到路径时返回`None`
+    pub fn plan(&self, start: Vec2, goal: Vec2) -> Option<Vec<Vec2>> {
+        let ignored: Vec<bool> = self.areas.iter().map(|area| area.contains(start)).collect();
+        let goal = self.free_goal(goal.clamp(self.bounds_min, self.bounds_max), &ignored)?;
+        if self.visible(start, goal, &ignored) {
+            return Some(vec![goal]);
+        }
+
+        // 节点：0为起点，1为终点，其余为各区域的顶点
+        let mut nodes = vec![start, goal];
+        nodes.extend(
+            self.areas
+                .iter()
+                .zip(&ignored)
+                .filter(|(_, ignored)| !**ignored)
+                .flat_map(|(area, _)| area.vertices(self.circle_segments))
+                .filter(|vertex| self.in_bounds(*vertex))
+                .filter(|vertex| {
+                    self.areas
+                        .iter()
+                        .zip(&ignored)
+                        .all(|(area, ignored)| *ignored || !area.contains(*vertex))
+                }),
+        );
+
+        // A*：启发函数为到终点的直线距离
+        let mut cost = vec![f32::INFINITY; nodes.len()];
+        let mut previous = vec![None; nodes.len()];
+        let mut closed = vec![false; nodes.len()];
+        let mut open = BinaryHeap::new();
+        cost[0] = 0.0;
+        open.push(OpenNode {
+            estimate: start.distance(goal),
+            index: 0,
+        });
+        while let Some(OpenNode { index, .. }) = open.pop() {
+            if index == 1 {
+                let mut path = vec![nodes[1]];
+                let mut current = 1;
+                while let Some(prev) = previous[current] {
+                    if prev != 0 {
+                        path.push(nodes[prev]);
+                    }
+                    current = prev;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if std::mem::replace(&mut closed[index], true) {
+                continue;
+            }
+            for next in 1..nodes.len() {
+                if closed[next] || next == index {
+                    continue;
+                }
+                let next_cost = cost[index] + nodes[index].distance(nodes[next]);
+                if next_cost >= cost[next] || !self.visible(nodes[index], nodes[next], &ignored) {
+                    continue;
+                }
+                cost[next] = next_cost;
+                previous[next] = Some(index);
+                open.push(OpenNode {
+                    estimate: next_cost + nodes[next].distance(goal),
+                    index: next,
+                });
+            }
+        }
+        None
+    }
+
+    /// 终点在区域内时移到区域外最近的可到达点
+    fn free_goal(&self, goal: Vec2, ignored: &[bool]) -> Option<Vec2> {
+        let blocking = |point: Vec2| {
+            self.areas
+                .iter()
+                .zip(ignored)
+                .find(|(area, ignored)| !**ignored && area.contains(point))
+                .map(|(area, _)| area)
+        };
+        match blocking(goal) {
+            None => Some(goal),
+            Some(area) => area
+                .outside_candidates(goal)
+                .into_iter()
+                .find(|moved| self.in_bounds(*moved) && blocking(*moved).is_none()),
+        }
+    }
+}
+
+/// A*的待访问节点，按估计总代价从小到大出队
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct OpenNode {
+    estimate: f32,
+    index: usize,
+}
+
+impl Eq for OpenNode {}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then_with(|| self.index.cmp(&other.index))
+    }
+}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// 路径长度
+pub fn path_length(start: Vec2, path: &[Vec2]) -> f32 {
+    path.iter()
+        .fold((start, 0.0), |(last, length), point| {
+            (*point, length + last.distance(*point))
+        })
+        .1
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    fn barrier(x: f32, y: f32) -> PanoramaBarrier {
+        PanoramaBarrier {
+            pos: Vec2::new(x, y),
+            size: 0.5,
+        }
+    }
+
+    /// 路径上的每一段都不穿过任何区域
+    fn assert_collision_free(map: &PlanMap, start: Vec2, path: &[Vec2]) {
+        let mut last = start;
+        for point in path {
+            assert!(map.in_bounds(*point), "{point} out of bounds");
+            for area in &map.areas {
+                if !area.contains(start) {
+                    assert!(
+                        !area.blocks(last, *point),
+                        "{last} -> {point} hits {area:?}"
+                    );
+                }
+            }
+            last = *point;
+        }
+    }
+
+    #[test]
+    fn straight_when_clear() {
+        let map = PlanMap::new(
+            &FieldData::default(),
+            &[],
+            &PathPlanConfig::default(),
+            false,
+        );
+        let path = map
+            .plan(Vec2::new(-3.0, 0.0), Vec2::new(2.0, 1.0))
+            .expect("Failed to plan!");
+        assert_eq!(path, vec![Vec2::new(2.0, 1.0)]);
+    }
+
+    #[test]
+    fn around_barriers() {
+        let config = PathPlanConfig::default();
+        // 一排障碍物挡在正前方，只能从两端绕过
+        let barriers: Vec<_> = (-2..=2).map(|y| barrier(0.0, y as f32 * 0.6)).collect();
+        let map = PlanMap::new(&FieldData::default(), &barriers, &config, false);
+        let start = Vec2::new(-3.0, 0.0);
+        let goal = Vec2::new(3.0, 0.0);
+        let path = map.plan(start, goal).expect("Failed to plan!");
+        assert!(path.len() >= 2);
+        assert_eq!(*path.last().unwrap(), goal);
+        assert_collision_free(&map, start, &path);
+        // 绕行的距离不超过绕过障碍物两端的折线
+        let inflate = 0.25 + config.robot_radius + config.clearance;
+        let corner = Vec2::new(0.0, 1.2 + inflate);
+        let detour = start.distance(corner) + corner.distance(goal);
+        let length = path_length(start, &path);
+        assert!(length > start.distance(goal) && length < detour * 1.05);
+    }
+
+    #[test]
+    fn keep_out_of_penalty_area() {
+        let field = FieldData::default();
+        let config = PathPlanConfig::default();
+        let map = PlanMap::new(&field, &[], &config, false);
+        let start = Vec2::new(5.0, 5.0);
+        // 对方大禁区内的目标移到禁区外
+        let path = map
+            .plan(start, Vec2::new(8.0, 0.0))
+            .expect("Failed to plan!");
+        let goal = *path.last().unwrap();
+        let inflate = config.robot_radius + config.clearance;
+        assert_relative_eq!(
+            goal.x,
+            field.field_size.x / 2.0 - field.penalty_area_size.x - inflate,
+            epsilon = 1e-2
+        );
+        assert!(map.is_free(goal));
+        // 从禁区一侧到另一侧：绕过禁区
+        let start = Vec2::new(7.0, 5.0);
+        let goal = Vec2::new(7.0, -5.0);
+        let path = map.plan(start, goal).expect("Failed to plan!");
+        assert!(path.len() >= 3);
+        assert_collision_free(&map, start, &path);
+
+        // 守门员可以进入己方小禁区，其他球员不可以
+        let own_goal = Vec2::new(-8.5, 0.0);
+        assert!(!map.is_free(own_goal));
+        let keeper_map = PlanMap::new(&field, &[], &config, true);
+        assert!(keeper_map.is_free(own_goal));
+    }
+
+    #[test]
+    fn leave_area_and_unreachable() {
+        let config = PathPlanConfig::default();
+        let map = PlanMap::new(&FieldData::default(), &[barrier(0.0, 0.0)], &config, false);
+        // 起点贴着障碍物：先离开
+        let start = Vec2::new(0.2, 0.0);
+        assert!(map.plan(start, Vec2::new(3.0, 0.0)).is_some());
+
+        // 终点被障碍物完全包围，移出后仍在其他障碍物内
+        let ring: Vec<_> = (0..12)
+            .map(|index| {
+                let pos = Vec2::from_angle(TAU * index as f32 / 12.0) * 0.8;
+                barrier(pos.x, pos.y)
+            })
+            .chain([barrier(0.0, 0.0)])
+            .collect();
+        let map = PlanMap::new(&FieldData::default(), &ring, &config, false);
+        assert!(map.plan(Vec2::new(-4.0, 0.0), Vec2::ZERO).is_none());
+    }
+}
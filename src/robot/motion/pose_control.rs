@@ -0,0 +1,314 @@
+//! 位姿控制：把目标位置与朝向换算为速度、速度方向与角速度指令
+
+use bevy_ecs::prelude::*;
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::field::world_state::{wrap_angle, RobotPose};
+
+use super::{path_plan::path_length, RobotMotion};
+
+/// 位姿控制参数
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PoseControlConfig {
+    /// 单位：米每秒
+    pub max_speed: f32,
+    /// 梯形速度曲线的加速度与减速度，单位：米每二次方秒
+    pub max_acc: f32,
+    /// 单位：弧度每秒
+    pub max_yaw_rate: f32,
+    /// 单位：弧度每二次方秒
+    pub max_yaw_acc: f32,
+    /// 接近目标时速度不超过`kp_pos * 剩余距离`，单位：每秒
+    pub kp_pos: f32,
+    /// 接近目标朝向时角速度不超过`kp_yaw * 剩余角度`，单位：每秒
+    pub kp_yaw: f32,
+    /// 到达判定的距离，单位：米
+    pub pos_tolerance: f32,
+    /// 到达判定的角度，单位：弧度
+    pub angle_tolerance: f32,
+}
+
+impl Default for PoseControlConfig {
+    fn default() -> Self {
+        Self {
+            max_speed: 2.0,
+            max_acc: 2.0,
+            max_yaw_rate: 4.0,
+            max_yaw_acc: 8.0,
+            kp_pos: 3.0,
+            kp_yaw: 4.0,
+            pos_tolerance: 0.03,
+            angle_tolerance: 0.05,
+        }
+    }
+}
+
+/// 位姿控制的进度，供上层逻辑判断是否执行下一个动作
+#[derive(Debug, Clone, Copy, Default, PartialEq, Resource)]
+pub struct MotionProgress {
+    pub target: RobotPose,
+    /// 剩余距离，单位：米
+    pub distance: f32,
+    /// 剩余角度，单位：弧度，范围(-PI, PI]
+    pub angle_error: f32,
+    /// 位置与朝向均在到达判定范围内
+    pub arrived: bool,
+    /// 找不到通往目标的路径，已原地停下
+    pub blocked: bool,
+}
+
+/// 梯形速度曲线：加速段每秒最多增加`max_acc`，减速段保证能以`max_acc`在剩余距离内停下，
+/// 末段速度不超过`kp * distance`，避免在目标附近来回振荡
+pub fn trapezoid_speed(
+    last_speed: f32,
+    distance: f32,
+    max_speed: f32,
+    max_acc: f32,
+    kp: f32,
+    dt: f32,
+) -> f32 {
+    (last_speed + max_acc * dt)
+        .min(max_speed)
+        .min((2.0 * max_acc * distance).sqrt())
+        .min(kp * distance)
+        .max(0.0)
+}
+
+/// 位姿控制器，记录上一次的速度用于加速段
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PoseController {
+    /// 单位：米每秒
+    pub speed: f32,
+    /// 单位：弧度每秒
+    pub yaw_rate: f32,
+}
+
+impl PoseController {
+    /// 由当前位姿与目标位姿计算一个周期的运动指令
+    pub fn update(
+        &mut self,
+        now: RobotPose,
+        target: RobotPose,
+        config: &PoseControlConfig,
+        dt: f32,
+    ) -> (RobotMotion, MotionProgress) {
+        self.update_path(now, &[target.pos], target.angle, config, dt)
+    }
+
+    /// 沿路径点移动，朝向最后转到`target_angle`。
+    /// 速度朝着第一个路径点，按剩余路径的总长度减速，中间的路径点处不减速
+    pub fn update_path(
+        &mut self,
+        now: RobotPose,
+        path: &[Vec2],
+        target_angle: f32,
+        config: &PoseControlConfig,
+        dt: f32,
+    ) -> (RobotMotion, MotionProgress) {
+        let target = RobotPose {
+            pos: path.last().copied().unwrap_or(now.pos),
+            angle: target_angle,
+        };
+        let offset = path
+            .first()
+            .map_or(Vec2::ZERO, |waypoint| *waypoint - now.pos);
+        let distance = path_length(now.pos, path);
+        let angle_error = wrap_angle(target.angle - now.angle);
+        let progress = MotionProgress {
+            target,
+            distance,
+            angle_error,
+            arrived: distance <= config.pos_tolerance
+                && angle_error.abs() <= config.angle_tolerance,
+            blocked: false,
+        };
+
+        self.speed = if distance > config.pos_tolerance {
+            trapezoid_speed(
+                self.speed,
+                distance,
+                config.max_speed,
+                config.max_acc,
+                config.kp_pos,
+                dt,
+            )
+        } else {
+            0.0
+        };
+        let yaw_speed = if angle_error.abs() > config.angle_tolerance {
+            trapezoid_speed(
+                self.yaw_rate.abs(),
+                angle_error.abs(),
+                config.max_yaw_rate,
+                config.max_yaw_acc,
+                config.kp_yaw,
+                dt,
+            )
+        } else {
+            0.0
+        };
+        self.yaw_rate = yaw_speed.copysign(angle_error);
+
+        let motion = RobotMotion {
+            now_pos: now.pos,
+            target_pos: target.pos,
+            speed_angle: if offset != Vec2::ZERO {
+                offset.to_angle()
+            } else {
+                0.0
+            },
+            speed_mps: self.speed,
+            yaw_rate: self.yaw_rate,
+            ..Default::default()
+        };
+        (motion, progress)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    const DT: f32 = 0.01;
+
+    /// 理想底盘：按指令移动，直到到达或超时，返回到达所用的步数
+    fn drive(
+        controller: &mut PoseController,
+        pose: &mut RobotPose,
+        target: RobotPose,
+        config: &PoseControlConfig,
+        mut check: impl FnMut(&RobotMotion, &MotionProgress),
+    ) -> Option<usize> {
+        for step in 0..2000 {
+            let (motion, progress) = controller.update(*pose, target, config, DT);
+            check(&motion, &progress);
+            if progress.arrived {
+                return Some(step);
+            }
+            pose.pos += Vec2::from_angle(motion.speed_angle) * motion.speed_mps * DT;
+            pose.angle = wrap_angle(pose.angle + motion.yaw_rate * DT);
+        }
+        None
+    }
+
+    #[test]
+    fn trapezoid_profile() {
+        let config = PoseControlConfig::default();
+        let mut controller = PoseController::default();
+        let mut pose = RobotPose::default();
+        let target = RobotPose {
+            pos: Vec2::new(4.0, -3.0),
+            angle: PI / 2.0,
+        };
+        let mut last_speed = 0.0;
+        let mut peak_speed: f32 = 0.0;
+        let steps = drive(
+            &mut controller,
+            &mut pose,
+            target,
+            &config,
+            |motion, progress| {
+                // 加速段不超过加速度上限（末段按比例减速，减速度可能更大）
+                assert!(motion.speed_mps - last_speed <= config.max_acc * DT + 1e-4);
+                assert!(motion.speed_mps <= config.max_speed);
+                // 速度方向指向目标
+                if progress.distance > config.pos_tolerance {
+                    assert_relative_eq!(
+                        motion.speed_angle,
+                        (target.pos - motion.now_pos).to_angle(),
+                        epsilon = 1e-3
+                    );
+                }
+                last_speed = motion.speed_mps;
+                peak_speed = peak_speed.max(motion.speed_mps);
+            },
+        )
+        .expect("Failed to arrive!");
+        // 5米：加速1秒、匀速1.5秒、减速1秒，加上末段的比例控制
+        assert_relative_eq!(peak_speed, config.max_speed);
+        assert!((350..450).contains(&steps), "steps: {steps}");
+        assert!(pose.pos.distance(target.pos) <= config.pos_tolerance);
+        assert!(wrap_angle(pose.angle - target.angle).abs() <= config.angle_tolerance);
+    }
+
+    #[test]
+    fn turn_in_place_across_pi() {
+        let config = PoseControlConfig::default();
+        let mut controller = PoseController::default();
+        let mut pose = RobotPose {
+            pos: Vec2::new(1.0, 1.0),
+            angle: 0.9 * PI,
+        };
+        let target = RobotPose {
+            pos: pose.pos,
+            angle: -0.9 * PI,
+        };
+        drive(
+            &mut controller,
+            &mut pose,
+            target,
+            &config,
+            |motion, progress| {
+                // 经过±PI转过较小的角度：逆时针
+                assert!(motion.yaw_rate >= 0.0);
+                assert_eq!(motion.speed_mps, 0.0);
+                assert!(progress.angle_error.abs() <= 0.2 * PI + 1e-4);
+            },
+        )
+        .expect("Failed to arrive!");
+        assert_eq!(pose.pos, Vec2::new(1.0, 1.0));
+    }
+
+    #[test]
+    fn follow_path_without_stopping() {
+        let config = PoseControlConfig::default();
+        let mut controller = PoseController::default();
+        let mut pose = RobotPose::default();
+        let path = [Vec2::new(2.0, 0.0), Vec2::new(2.0, 3.0)];
+        let mut corner_speed = None;
+        for _ in 0..1000 {
+            // 经过第一个路径点后只剩终点
+            let remaining = if pose.pos.x < 2.0 - config.pos_tolerance {
+                &path[..]
+            } else {
+                corner_speed.get_or_insert(controller.speed);
+                &path[1..]
+            };
+            let (motion, progress) = controller.update_path(pose, remaining, 0.0, &config, DT);
+            if progress.arrived {
+                break;
+            }
+            pose.pos += Vec2::from_angle(motion.speed_angle) * motion.speed_mps * DT;
+        }
+        // 中间的路径点处不减速
+        assert_relative_eq!(corner_speed.unwrap(), config.max_speed);
+        assert!(pose.pos.distance(path[1]) <= config.pos_tolerance);
+    }
+
+    #[test]
+    fn arrived_within_tolerance() {
+        let config = PoseControlConfig::default();
+        let mut controller = PoseController {
+            speed: 1.0,
+            yaw_rate: 1.0,
+        };
+        let target = RobotPose {
+            pos: Vec2::new(1.0, 0.0),
+            angle: 0.0,
+        };
+        let now = RobotPose {
+            pos: Vec2::new(0.99, 0.01),
+            angle: 0.02,
+        };
+        let (motion, progress) = controller.update(now, target, &config, DT);
+        assert!(progress.arrived);
+        assert_eq!(motion.speed_mps, 0.0);
+        assert_eq!(motion.yaw_rate, 0.0);
+    }
+}
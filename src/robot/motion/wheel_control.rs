@@ -0,0 +1,337 @@
+//! 底盘电机转速闭环：由码盘读数求实际转速，按PID与前馈修正后发给下位机
+
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::robot::{
+    com_robot::{lower_frame::LowerCommand, LowerSendSet, RobotLowerData, MOTOR_COUNT},
+    RobotConfig,
+};
+
+use super::{chassis::ChassisConfig, odometry::encoder_delta};
+
+pub(in crate::robot) struct WheelControlPlugin;
+
+impl Plugin for WheelControlPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WheelSpeedController>().add_systems(
+            FixedPostUpdate,
+            wheel_speed_control_system.before(LowerSendSet),
+        );
+    }
+}
+
+/// 转速闭环参数
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WheelControlConfig {
+    /// 比例系数
+    pub kp: f32,
+    /// 积分系数，单位：每秒
+    pub ki: f32,
+    /// 微分系数，单位：秒
+    pub kd: f32,
+    /// 前馈系数：输出中直接加上`kf * 目标转速`
+    pub kf: f32,
+    /// 积分项的上限，单位：转每分钟
+    pub max_integral_rpm: f32,
+    /// 超过此时间没有码盘数据时只使用前馈，单位：毫秒
+    pub feedback_timeout_ms: u64,
+    /// 超过此时间没有新的目标转速时停车，单位：毫秒
+    pub command_timeout_ms: u64,
+}
+
+impl Default for WheelControlConfig {
+    fn default() -> Self {
+        Self {
+            kp: 0.5,
+            ki: 4.0,
+            kd: 0.0,
+            kf: 1.0,
+            max_integral_rpm: 1500.0,
+            feedback_timeout_ms: 100,
+            command_timeout_ms: 300,
+        }
+    }
+}
+
+/// 目标电机转速，由运动逻辑写入
+/// 轮子顺序：后侧、左侧、右侧，正值为顺时针，单位：转每分钟
+#[derive(Debug, Clone, Copy, PartialEq, Resource)]
+pub struct WheelSpeedTarget {
+    pub rpm: Vec3,
+    pub update_time: Instant,
+}
+
+/// 单个电机的PID状态
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WheelPid {
+    /// 积分项，单位：转每分钟
+    pub integral: f32,
+    last_measured: Option<f32>,
+}
+
+impl WheelPid {
+    /// 计算一个周期的输出。`over_current`为电流超过上限的比例（未超过时为`None`）
+    pub fn update(
+        &mut self,
+        target: f32,
+        measured: Option<f32>,
+        over_current: Option<f32>,
+        config: &WheelControlConfig,
+        max_rpm: f32,
+        dt: f32,
+    ) -> f32 {
+        let feed_forward = config.kf * target;
+        let Some(measured) = measured else {
+            // 没有反馈：开环
+            *self = Self::default();
+            return feed_forward.clamp(-max_rpm, max_rpm);
+        };
+        let error = target - measured;
+        // 微分作用于测量值，避免目标突变时的冲击
+        let derivative = match self.last_measured.replace(measured) {
+            Some(last_measured) if dt > 0.0 => -(measured - last_measured) / dt,
+            _ => 0.0,
+        };
+        let unsaturated = feed_forward + config.kp * error + self.integral + config.kd * derivative;
+        let output = unsaturated.clamp(-max_rpm, max_rpm);
+        match over_current {
+            Some(ratio) => {
+                // 过流：积分衰减，输出向实际转速收拢
+                self.integral /= ratio;
+                measured + (output - measured) / ratio
+            }
+            None => {
+                // 抗积分饱和：输出已饱和且误差会加深饱和时不再积分
+                let winding_up = unsaturated != output && error.signum() == unsaturated.signum();
+                if !winding_up {
+                    self.integral = (self.integral + config.ki * error * dt)
+                        .clamp(-config.max_integral_rpm, config.max_integral_rpm);
+                }
+                output
+            }
+        }
+    }
+}
+
+/// 各电机的转速闭环
+#[derive(Debug, Clone, Default, Resource)]
+pub struct WheelSpeedController {
+    pub wheels: [WheelPid; MOTOR_COUNT],
+    /// 由码盘读数求得的实际转速，单位：转每分钟
+    pub measured_rpm: Vec3,
+    /// 最近一次有效转速的码盘读数时间
+    pub feedback_time: Option<Instant>,
+    /// 最近一次发出的转速
+    pub output_rpm: Vec3,
+    last_rotate_pos: Option<([i32; MOTOR_COUNT], Instant)>,
+}
+
+impl WheelSpeedController {
+    /// 加入一次码盘读数，更新实际转速
+    pub fn measure(
+        &mut self,
+        rotate_pos: [i32; MOTOR_COUNT],
+        time: Instant,
+        chassis: &ChassisConfig,
+    ) {
+        let Some((last_pos, last_time)) = self.last_rotate_pos.replace((rotate_pos, time)) else {
+            return;
+        };
+        let dt = time.saturating_duration_since(last_time).as_secs_f32();
+        if dt <= 0.0 {
+            return;
+        }
+        let mut measured_rpm = Vec3::ZERO;
+        for (index, (last_pos, now_pos)) in last_pos.iter().zip(rotate_pos).enumerate() {
+            // 下位机重启：本次读数不可用
+            let Some(delta) = encoder_delta(*last_pos, now_pos, chassis.max_encoder_delta()) else {
+                return;
+            };
+            measured_rpm[index] = delta as f32 / chassis.encoder_ticks_per_rev as f32 / dt * 60.0;
+        }
+        self.measured_rpm = measured_rpm;
+        self.feedback_time = Some(time);
+    }
+
+    /// 计算一个周期的输出转速
+    pub fn update(
+        &mut self,
+        target_rpm: Vec3,
+        currents_ma: [u16; MOTOR_COUNT],
+        now: Instant,
+        config: &WheelControlConfig,
+        chassis: &ChassisConfig,
+        dt: f32,
+    ) -> Vec3 {
+        let feedback_timeout = Duration::from_millis(config.feedback_timeout_ms);
+        let has_feedback = self
+            .feedback_time
+            .is_some_and(|time| now.saturating_duration_since(time) <= feedback_timeout);
+        let max_current = chassis.motor_max_current_ma.max(1) as f32;
+        for index in 0..MOTOR_COUNT {
+            let over_current = (currents_ma[index] > chassis.motor_max_current_ma)
+                .then(|| currents_ma[index] as f32 / max_current);
+            self.output_rpm[index] = self.wheels[index].update(
+                target_rpm[index],
+                has_feedback.then_some(self.measured_rpm[index]),
+                over_current,
+                config,
+                chassis.motor_max_rpm,
+                dt,
+            );
+        }
+        self.output_rpm
+    }
+}
+
+/// 按目标转速与码盘反馈计算电机转速，发给下位机
+pub(in crate::robot) fn wheel_speed_control_system(
+    config: Res<RobotConfig>,
+    time: Res<Time>,
+    lower_data: Res<RobotLowerData>,
+    target: Option<Res<WheelSpeedTarget>>,
+    mut controller: ResMut<WheelSpeedController>,
+    mut lower_commands: EventWriter<LowerCommand>,
+) {
+    let Some(target) = target else {
+        return;
+    };
+    let now = Instant::now();
+    if let Some(encoder_time) = lower_data.encoder_time {
+        if controller.feedback_time != Some(encoder_time) {
+            let rotate_pos = lower_data.motor_status.map(|status| status.rotate_pos);
+            controller.measure(rotate_pos, encoder_time, &config.chassis);
+        }
+    }
+    // 目标过期时停车
+    let command_timeout = Duration::from_millis(config.wheel_control.command_timeout_ms);
+    let target_rpm = if now.saturating_duration_since(target.update_time) <= command_timeout {
+        target.rpm
+    } else {
+        Vec3::ZERO
+    };
+    let currents = lower_data.motor_status.map(|status| status.current);
+    let output = controller.update(
+        target_rpm,
+        currents,
+        now,
+        &config.wheel_control,
+        &config.chassis,
+        time.delta_seconds(),
+    );
+    lower_commands.send(LowerCommand::WheelSpeeds(
+        output
+            .to_array()
+            .map(|rpm| rpm.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16),
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    const DT: f32 = 0.01;
+
+    /// 简单的电机模型：转速以一阶惯性跟随指令，负载使稳态转速只有指令的80%
+    struct MotorModel {
+        rpm: f32,
+    }
+
+    impl MotorModel {
+        fn step(&mut self, command: f32) -> f32 {
+            self.rpm += (0.8 * command - self.rpm) * 0.2;
+            self.rpm
+        }
+    }
+
+    fn run(config: &WheelControlConfig, target: f32, steps: usize) -> (WheelPid, f32) {
+        let mut pid = WheelPid::default();
+        let mut motor = MotorModel { rpm: 0.0 };
+        for _ in 0..steps {
+            let command = pid.update(target, Some(motor.rpm), None, config, 6000.0, DT);
+            motor.step(command);
+        }
+        (pid, motor.rpm)
+    }
+
+    #[test]
+    fn track_under_load() {
+        let config = WheelControlConfig::default();
+        // 只有前馈时有稳态误差
+        let open_loop = WheelControlConfig {
+            kp: 0.0,
+            ki: 0.0,
+            ..config
+        };
+        let (_, rpm) = run(&open_loop, 1000.0, 500);
+        assert_relative_eq!(rpm, 800.0, epsilon = 1.0);
+        // 闭环消除稳态误差
+        let (_, rpm) = run(&config, 1000.0, 500);
+        assert_relative_eq!(rpm, 1000.0, epsilon = 5.0);
+    }
+
+    #[test]
+    fn anti_windup() {
+        let config = WheelControlConfig::default();
+        let mut pid = WheelPid::default();
+        let mut motor = MotorModel { rpm: 0.0 };
+        // 目标超过上限，输出饱和，积分不应继续累积
+        for _ in 0..500 {
+            let command = pid.update(8000.0, Some(motor.rpm), None, &config, 6000.0, DT);
+            assert!(command <= 6000.0);
+            motor.step(command);
+        }
+        assert!(pid.integral < config.max_integral_rpm);
+        // 目标降低后很快跟上，没有长时间的超调
+        for _ in 0..100 {
+            let command = pid.update(1000.0, Some(motor.rpm), None, &config, 6000.0, DT);
+            motor.step(command);
+        }
+        assert!((motor.rpm - 1000.0).abs() < 100.0, "rpm: {}", motor.rpm);
+    }
+
+    #[test]
+    fn current_limit_and_feedback() {
+        let config = WheelControlConfig::default();
+        let chassis = ChassisConfig::default();
+        let mut controller = WheelSpeedController::default();
+        let start = Instant::now();
+        // 没有反馈时只使用前馈
+        let target = Vec3::new(1000.0, -500.0, 0.0);
+        let output = controller.update(target, [0; 3], start, &config, &chassis, DT);
+        assert_eq!(output, target);
+
+        // 由码盘读数求实际转速：10毫秒转过25个计数，即60转每分钟
+        controller.measure([0; 3], start, &chassis);
+        controller.measure([25, -25, 0], start + Duration::from_millis(10), &chassis);
+        assert_relative_eq!(
+            controller.measured_rpm,
+            Vec3::new(60.0, -60.0, 0.0),
+            epsilon = 1e-2
+        );
+
+        // 后侧电机堵转过流：输出向实际转速收拢
+        let now = start + Duration::from_millis(12);
+        let currents = [chassis.motor_max_current_ma * 2, 0, 0];
+        let limited = controller.update(target, currents, now, &config, &chassis, DT);
+        let mut normal = WheelSpeedController {
+            wheels: Default::default(),
+            ..controller.clone()
+        };
+        let unlimited = normal.update(target, [0; 3], now, &config, &chassis, DT);
+        assert!(limited.x < unlimited.x && limited.x > 60.0);
+        assert_relative_eq!(limited.y, unlimited.y);
+
+        // 反馈超时后回到开环
+        let later = now + Duration::from_millis(config.feedback_timeout_ms + 1);
+        let output = controller.update(target, [0; 3], later, &config, &chassis, DT);
+        assert_eq!(output, target);
+    }
+}
@@ -0,0 +1,615 @@
+//! 与教练机通信：旧版UDP协议与新版协议
+//!
+//! 两种数据包共用同一个端口。按`RobotNetworkConfig::protocol`决定是否发起协商，
+//! 协商成功后发送新版数据包，收到的数据包不论新旧都会处理。
+
+use std::{
+    io,
+    net::{SocketAddr, SocketAddrV4},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bevy::prelude::*;
+use glam::{I16Vec2, Vec2};
+use mio::{net::UdpSocket, Events, Interest, Poll, Token};
+
+use crate::{
+    data_legacy::{
+        convert::LegacyFrame, LegacyCtrl, LegacyDecodeStats, LegacyMsgType, LegacyPackFromCoach,
+        LegacyPackFromRobot, LEGACY_NO_BALL_POS,
+    },
+    data_modern::{
+        is_newer_packet, ModernBody, ModernCoachCommand, ModernHeader, ModernIntent, ModernPacket,
+        ModernRobotState, PeerProtocol, ProtocolMode, MODERN_MIN_PROTOCOL_VERSION,
+        MODERN_PROTOCOL_VERSION,
+    },
+    field::world_state::{Obstacle, ObstacleList, RobotPose},
+};
+
+use super::{
+    com_mpu::orientation::RobotHeading, com_robot::RobotLowerData, motion::odometry::RobotOdometry,
+    panorama_camera::PanoramaData, RobotConfig,
+};
+
+/*
+ * Part: Plugin
+ */
+
+pub(super) struct RobotNetworkPlugin;
+
+impl Plugin for RobotNetworkPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CoachPackReceiveEvent>()
+            .init_resource::<LegacyDecodeStats>()
+            .add_systems(Startup, network_bind_system)
+            .add_systems(FixedPreUpdate, network_receive_system)
+            .add_systems(FixedPostUpdate, network_send_system);
+    }
+}
+
+/*
+ * Part: Event & Resource
+ */
+
+/// 收到教练机数据包。新版数据包也会转为旧版格式发送此事件
+#[derive(Debug, Clone, Copy, Event)]
+pub struct CoachPackReceiveEvent {
+    pub pack: LegacyPackFromCoach,
+    pub from: SocketAddr,
+}
+
+/// 最近一次收到的教练机数据包。新版数据包也会转为旧版格式记录于此
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct CoachLegacyData {
+    pub pack: LegacyPackFromCoach,
+    pub receive_time: SystemTime,
+}
+
+/// 最近一次收到的新版教练机指令
+#[derive(Debug, Clone, Resource)]
+pub struct CoachModernData {
+    pub header: ModernHeader,
+    pub command: ModernCoachCommand,
+    pub receive_time: SystemTime,
+}
+
+#[derive(Resource)]
+pub(super) struct RobotNetworkModule {
+    poll: Poll,
+    events: Events,
+    socket: UdpSocket,
+    coach_addr: SocketAddr,
+    send_interval: Duration,
+    last_send_time: Option<SystemTime>,
+    /// 配置的协议
+    protocol_mode: ProtocolMode,
+    /// 与教练机协商的结果
+    coach_protocol: PeerProtocol,
+    /// 教练机拒绝了新版协议，不再发起协商
+    hello_rejected: bool,
+    last_hello_time: Option<SystemTime>,
+    /// 最近一次收到新版数据包的时间
+    last_modern_receive_time: Option<SystemTime>,
+    /// 发送序号
+    seq: u32,
+    /// 最近一次收到的教练机包头，用于丢弃乱序的数据包
+    last_coach_header: Option<ModernHeader>,
+}
+
+const UDP_TOKEN: Token = Token(0);
+/// 协商数据包的发送间隔
+const HELLO_INTERVAL: Duration = Duration::from_secs(1);
+/// 新版协议下超过此时间只收到旧版数据包时，回退到旧版协议
+const MODERN_FALLBACK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 当前Unix时间戳，单位：毫秒
+fn unix_time_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
+/// 距离`last_time`是否已超过`interval`，`last_time`为`None`时视为已超过
+fn elapsed(now_time: SystemTime, last_time: Option<SystemTime>, interval: Duration) -> bool {
+    !last_time.is_some_and(|last_time| {
+        now_time
+            .duration_since(last_time)
+            .is_ok_and(|duration| duration < interval)
+    })
+}
+
+impl RobotNetworkModule {
+    /// 生成下一个新版数据包
+    fn modern_packet(&mut self, robot_id: u8, version: u16, body: ModernBody) -> ModernPacket {
+        self.seq = self.seq.wrapping_add(1);
+        ModernPacket {
+            header: ModernHeader {
+                version,
+                robot_id,
+                seq: self.seq,
+                send_time_ms: unix_time_ms(SystemTime::now()),
+            },
+            body,
+        }
+    }
+
+    fn send_bytes(&self, bytes: &[u8]) {
+        let coach_addr = self.coach_addr;
+        match self.socket.send_to(bytes, coach_addr) {
+            Ok(_) => {}
+            // 发送缓冲区满：丢弃这一帧，下次再发
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {}
+            Err(err) => warn!("Failed to send to coach {coach_addr}: {err}"),
+        }
+    }
+}
+
+/*
+ * Part: System
+ */
+
+fn network_bind_system(mut commands: Commands, config: Res<RobotConfig>) {
+    let network = config.network;
+    // Create a poll instance.
+    let poll = match Poll::new() {
+        Ok(poll) => poll,
+        Err(err) => {
+            warn!("Failed to create poll using mio! {err:?}");
+            return;
+        }
+    };
+    let bind_addr = SocketAddr::V4(SocketAddrV4::new(network.bind_ip, network.bind_port));
+    let mut socket = match UdpSocket::bind(bind_addr) {
+        Ok(socket) => socket,
+        Err(err) => {
+            warn!("Failed to bind {bind_addr}! {err:?}");
+            return;
+        }
+    };
+    if let Some(group) = network.multicast_group {
+        if let Err(err) = socket.join_multicast_v4(&group, &network.bind_ip) {
+            warn!("Failed to join multicast group {group}! {err:?}");
+        }
+    }
+    if let Err(err) = poll
+        .registry()
+        .register(&mut socket, UDP_TOKEN, Interest::READABLE)
+    {
+        warn!("Failed to register udp socket on mio! {err:?}");
+        return;
+    }
+    let coach_addr = SocketAddr::V4(SocketAddrV4::new(network.coach_ip, network.coach_port));
+    info!(
+        "Coach network: bind {bind_addr}, coach {coach_addr}, protocol {:?}",
+        network.protocol
+    );
+    commands.insert_resource(RobotNetworkModule {
+        poll,
+        events: Events::with_capacity(8),
+        socket,
+        coach_addr,
+        send_interval: Duration::from_millis(network.send_interval_ms),
+        last_send_time: None,
+        protocol_mode: network.protocol,
+        coach_protocol: PeerProtocol::Legacy,
+        hello_rejected: false,
+        last_hello_time: None,
+        last_modern_receive_time: None,
+        seq: 0,
+        last_coach_header: None,
+    });
+}
+
+fn network_receive_system(
+    mut commands: Commands,
+    module: Option<ResMut<RobotNetworkModule>>,
+    config: Res<RobotConfig>,
+    mut decode_stats: ResMut<LegacyDecodeStats>,
+    mut receive_event_writer: EventWriter<CoachPackReceiveEvent>,
+) {
+    let Some(mut module) = module else {
+        return;
+    };
+    let module = module.as_mut();
+    // 不阻塞固定时间系统
+    if let Err(err) = module.poll.poll(&mut module.events, Some(Duration::ZERO)) {
+        warn!("Failed to poll events on coach network mio! {err:?}");
+        return;
+    }
+    if !module.events.iter().any(|event| event.token() == UDP_TOKEN) {
+        return;
+    }
+    let frame = &config.network.legacy_frame;
+    let mut buf = [0u8; 2048];
+    // 读取所有已到达的数据包
+    loop {
+        let (count, from) = match module.socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+            Err(err) => {
+                warn!("Failed to receive from coach: {err}");
+                break;
+            }
+        };
+        let now_time = SystemTime::now();
+        let bytes = &buf[..count];
+        let pack = if ModernPacket::is_modern(bytes) {
+            let packet = match ModernPacket::try_from_bytes(bytes) {
+                Ok(packet) => packet,
+                Err(err) => {
+                    warn!("Drop malformed modern packet from {from}: {err}");
+                    continue;
+                }
+            };
+            match receive_modern(module, config.robot_id, packet, now_time) {
+                Some(modern_data) => {
+                    let pack = modern_data.command.to_legacy_pack(config.robot_id, frame);
+                    commands.insert_resource(modern_data);
+                    pack
+                }
+                None => continue,
+            }
+        } else {
+            let Some(pack) = decode_legacy(bytes, config.robot_id, from, &mut decode_stats) else {
+                continue;
+            };
+            // 教练机长时间只发送旧版数据包：回退并重新协商
+            if module.coach_protocol != PeerProtocol::Legacy
+                && module.protocol_mode == ProtocolMode::Auto
+                && elapsed(
+                    now_time,
+                    module.last_modern_receive_time,
+                    MODERN_FALLBACK_TIMEOUT,
+                )
+            {
+                info!("Coach only sends legacy packs, fall back to legacy protocol");
+                module.coach_protocol = PeerProtocol::Legacy;
+                module.last_coach_header = None;
+            }
+            pack
+        };
+        commands.insert_resource(CoachLegacyData {
+            pack,
+            receive_time: now_time,
+        });
+        receive_event_writer.send(CoachPackReceiveEvent { pack, from });
+    }
+}
+
+/// 解码旧版教练机数据包。教练机广播给所有机器人，只返回发给本机的数据包
+fn decode_legacy(
+    bytes: &[u8],
+    robot_id: u8,
+    from: SocketAddr,
+    decode_stats: &mut LegacyDecodeStats,
+) -> Option<LegacyPackFromCoach> {
+    let decode_result = LegacyPackFromCoach::try_from_bytes(bytes);
+    decode_stats.record(&decode_result);
+    match decode_result {
+        Ok(pack) => (pack.id == robot_id).then_some(pack),
+        Err(err) => {
+            warn!("Drop malformed pack from {from}: {err}");
+            None
+        }
+    }
+}
+
+/// 处理新版数据包，收到指令时返回
+fn receive_modern(
+    module: &mut RobotNetworkModule,
+    robot_id: u8,
+    packet: ModernPacket,
+    now_time: SystemTime,
+) -> Option<CoachModernData> {
+    let ModernPacket { header, body } = packet;
+    if header.robot_id != robot_id {
+        return None;
+    }
+    module.last_modern_receive_time = Some(now_time);
+    match body {
+        ModernBody::HelloAck {
+            version: Some(version),
+        } => {
+            if module.coach_protocol != (PeerProtocol::Modern { version }) {
+                info!("Coach accepts modern protocol v{version}");
+                module.coach_protocol = PeerProtocol::Modern { version };
+            }
+            // 协商后教练机可能已经重启，序号从头开始
+            module.last_coach_header = None;
+            None
+        }
+        ModernBody::HelloAck { version: None } => {
+            warn!("Coach rejects modern protocol, keep legacy protocol");
+            module.coach_protocol = PeerProtocol::Legacy;
+            module.hello_rejected = true;
+            None
+        }
+        ModernBody::CoachCommand(command) => {
+            if !is_newer_packet(&header, module.last_coach_header.as_ref()) {
+                return None;
+            }
+            module.last_coach_header = Some(header);
+            // 教练机已按新版协议发送，说明协商已完成（如机器人重启前协商过）
+            if module.coach_protocol == PeerProtocol::Legacy
+                && module.protocol_mode != ProtocolMode::Legacy
+            {
+                module.coach_protocol = PeerProtocol::Modern {
+                    version: header.version,
+                };
+            }
+            Some(CoachModernData {
+                header,
+                command,
+                receive_time: now_time,
+            })
+        }
+        ModernBody::Hello { .. } | ModernBody::RobotState(_) => None,
+    }
+}
+
+fn network_send_system(
+    module: Option<ResMut<RobotNetworkModule>>,
+    config: Res<RobotConfig>,
+    coach_data: Option<Res<CoachLegacyData>>,
+    panorama_data: Option<Res<PanoramaData>>,
+    odometry: Option<Res<RobotOdometry>>,
+    heading: Option<Res<RobotHeading>>,
+    lower_data: Option<Res<RobotLowerData>>,
+) {
+    let Some(mut module) = module else {
+        return;
+    };
+    // 按固定间隔发送
+    let now_time = SystemTime::now();
+    if !elapsed(now_time, module.last_send_time, module.send_interval) {
+        return;
+    }
+    module.last_send_time = Some(now_time);
+    let frame = &config.network.legacy_frame;
+    let robot = RobotSelfState::new(
+        &config,
+        panorama_data.as_deref(),
+        odometry.as_deref(),
+        heading.as_deref(),
+        lower_data.as_deref(),
+    );
+    match (module.protocol_mode, module.coach_protocol) {
+        (ProtocolMode::Legacy, _) | (ProtocolMode::Auto, PeerProtocol::Legacy) => {
+            let pack = robot_pack(
+                config.robot_id,
+                frame,
+                module.send_interval,
+                coach_data.as_deref(),
+                panorama_data.as_deref(),
+                &robot,
+            );
+            module.send_bytes(&pack.to_bytes());
+        }
+        (_, PeerProtocol::Modern { version }) => {
+            let state = robot_state(
+                frame,
+                coach_data.as_deref(),
+                panorama_data.as_deref(),
+                &robot,
+            );
+            let packet =
+                module.modern_packet(config.robot_id, version, ModernBody::RobotState(state));
+            module.send_bytes(&packet.to_bytes());
+        }
+        // 协商成功之前不发送状态
+        (ProtocolMode::Modern, PeerProtocol::Legacy) => {}
+    }
+    // 发起协商
+    if module.protocol_mode != ProtocolMode::Legacy
+        && module.coach_protocol == PeerProtocol::Legacy
+        && !module.hello_rejected
+        && elapsed(now_time, module.last_hello_time, HELLO_INTERVAL)
+    {
+        module.last_hello_time = Some(now_time);
+        let packet = module.modern_packet(
+            config.robot_id,
+            MODERN_PROTOCOL_VERSION,
+            ModernBody::Hello {
+                min_version: MODERN_MIN_PROTOCOL_VERSION,
+                max_version: MODERN_PROTOCOL_VERSION,
+            },
+        );
+        module.send_bytes(&packet.to_bytes());
+    }
+}
+
+/*
+ * Part: Pack
+ */
+
+/// 发送给教练机的自身状态
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct RobotSelfState {
+    pose: RobotPose,
+    /// 场地坐标系下的速度，单位：米每秒
+    velocity: Vec2,
+    has_ball: bool,
+}
+
+impl RobotSelfState {
+    /// 位置：有全景相机定位时使用相机的位置，否则使用里程计；朝向：有MPU数据时使用MPU的朝向；
+    /// 速度：里程计推算的速度；持球：持球传感器
+    fn new(
+        config: &RobotConfig,
+        panorama_data: Option<&PanoramaData>,
+        odometry: Option<&RobotOdometry>,
+        heading: Option<&RobotHeading>,
+        lower_data: Option<&RobotLowerData>,
+    ) -> Self {
+        let mut state = odometry.map_or_else(Self::default, |odometry| Self {
+            pose: RobotPose {
+                pos: odometry.pos,
+                angle: odometry.yaw,
+            },
+            velocity: Vec2::from_angle(odometry.yaw).rotate(odometry.body_velocity),
+            has_ball: false,
+        });
+        if let Some(panorama_data) = panorama_data {
+            state.pose.pos = panorama_data.pos;
+        }
+        if let Some(heading) = heading {
+            state.pose.angle = heading.yaw;
+            if let Some(odometry) = odometry {
+                state.velocity = Vec2::from_angle(heading.yaw).rotate(odometry.body_velocity);
+            }
+        }
+        state.has_ball = lower_data.is_some_and(|lower_data| {
+            lower_data
+                .io
+                .get(config.behaviour.ball_sensor_io)
+                .copied()
+                .unwrap_or(false)
+        });
+        state
+    }
+}
+
+/// 根据机器人当前状态生成数据包
+fn robot_pack(
+    robot_id: u8,
+    frame: &LegacyFrame,
+    send_interval: Duration,
+    coach_data: Option<&CoachLegacyData>,
+    panorama_data: Option<&PanoramaData>,
+    robot: &RobotSelfState,
+) -> LegacyPackFromRobot {
+    let (pos, angle) = robot.pose.to_legacy(frame);
+    let mut pack = LegacyPackFromRobot {
+        id: robot_id,
+        msg_type: LegacyMsgType::Teammate,
+        pos,
+        angle,
+        // 当前执行的是教练机最近一次下发的指令
+        ctrl: coach_data.map_or(LegacyCtrl::Stop, |data| data.pack.ctrl),
+        has_ball: robot.has_ball,
+        // 机器人自身不识别球
+        found_ball: false,
+        found_ball_pos: LEGACY_NO_BALL_POS,
+        velocity: frame.to_legacy_len(robot.velocity.length()),
+        velocity_angle: robot
+            .velocity
+            .try_normalize()
+            .map_or(0, |direction| frame.to_legacy_angle(direction.to_angle())),
+        pass_kick: false,
+        pass_target_pos: I16Vec2::ZERO,
+        barriers: [Default::default(); 10],
+        computer_ac: 0,
+        computer_battery_flag: 0,
+        computer_battery_percent: 0,
+        computer_working_second_count: 0,
+        computer_cpu_percent: 0,
+        computer_cpu_frequency_mhz: 0.0,
+        soft_version: env!("CARGO_PKG_VERSION")
+            .rsplit_once('.')
+            .and_then(|(major_minor, _)| major_minor.parse().ok())
+            .unwrap_or_default(),
+        robot_power_volt: 0,
+        robot_charge: false,
+        video_fps: 0,
+        multicast_fps: (1.0 / send_interval.as_secs_f32()).min(u8::MAX as f32) as u8,
+    };
+    if let Some(panorama_data) = panorama_data {
+        pack.barriers = panorama_obstacles(panorama_data).to_legacy(frame);
+    }
+    pack
+}
+
+/// 根据机器人当前状态生成新版协议的状态
+fn robot_state(
+    frame: &LegacyFrame,
+    coach_data: Option<&CoachLegacyData>,
+    panorama_data: Option<&PanoramaData>,
+    robot: &RobotSelfState,
+) -> ModernRobotState {
+    let mut state = ModernRobotState {
+        pose: robot.pose,
+        velocity: robot.velocity,
+        has_ball: robot.has_ball,
+        // 当前执行的是教练机最近一次下发的指令
+        intent: coach_data.map_or_else(Default::default, |data| {
+            ModernIntent::from_legacy(&data.pack, frame)
+        }),
+        ..Default::default()
+    };
+    if let Some(panorama_data) = panorama_data {
+        state.obstacles = panorama_obstacles(panorama_data);
+    }
+    state
+}
+
+/// 全景相机看到的障碍物
+fn panorama_obstacles(panorama_data: &PanoramaData) -> ObstacleList {
+    ObstacleList {
+        obstacles: panorama_data
+            .barriers
+            .iter()
+            .map(|barrier| Obstacle {
+                pos: barrier.pos,
+                size: barrier.size,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    #[test]
+    fn robot_pack_from_sensors() {
+        let config = RobotConfig::default();
+        let frame = LegacyFrame::default();
+        let mut odometry = RobotOdometry::default();
+        odometry.pos = Vec2::new(1.0, -2.0);
+        odometry.yaw = std::f32::consts::FRAC_PI_2;
+        odometry.body_velocity = Vec2::new(0.5, 0.0);
+        let mut lower_data = RobotLowerData::default();
+        lower_data.io[config.behaviour.ball_sensor_io] = true;
+        let robot = RobotSelfState::new(&config, None, Some(&odometry), None, Some(&lower_data));
+        let pack = robot_pack(
+            config.robot_id,
+            &frame,
+            Duration::from_millis(30),
+            None,
+            None,
+            &robot,
+        );
+        assert!(pack.has_ball);
+        assert_eq!(pack.pos, I16Vec2::new(100, -200));
+        assert_eq!(pack.angle, 90);
+        // 机器人坐标系下向前，场地坐标系下向左
+        assert_eq!(pack.velocity, 50);
+        assert_eq!(pack.velocity_angle, 90);
+    }
+
+    #[test]
+    fn drop_legacy_pack_for_teammate() {
+        let from = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 20090));
+        let mut decode_stats = LegacyDecodeStats::default();
+        let pack = LegacyPackFromCoach {
+            id: 2,
+            ctrl: LegacyCtrl::Attack,
+            ..Default::default()
+        };
+        let bytes = pack.to_bytes();
+        assert_eq!(
+            decode_legacy(&bytes, 2, from, &mut decode_stats).map(|pack| pack.ctrl),
+            Some(LegacyCtrl::Attack)
+        );
+        // 发给队友的数据包
+        assert_eq!(decode_legacy(&bytes, 3, from, &mut decode_stats), None);
+        // 损坏的数据包
+        assert_eq!(
+            decode_legacy(&bytes[..10], 2, from, &mut decode_stats),
+            None
+        );
+        assert_eq!(decode_stats.decoded, 2);
+        assert_eq!(decode_stats.wrong_length, 1);
+    }
+}
@@ -0,0 +1,254 @@
+//! 串口：按路径、USB序列号或VID:PID查找串口、连接状态以及分帧，MPU与下位机共用
+
+pub mod frame;
+pub mod link;
+
+use mio::{Interest, Poll, Token};
+use mio_serial::{SerialPortBuilderExt, SerialPortInfo, SerialPortType, SerialStream};
+
+/// 串口选择方式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SerialSelector {
+    /// 直接指定路径，如`COM5`、`/dev/ttyUSB0`、`/dev/serial/by-id/...`
+    Path(String),
+    /// USB串口的序列号
+    SerialNumber(String),
+    /// USB的VID与PID
+    UsbId { vid: u16, pid: u16 },
+}
+
+impl std::fmt::Display for SerialSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SerialSelector::Path(path) => write!(f, "path {path}"),
+            SerialSelector::SerialNumber(serial_number) => {
+                write!(f, "serial number {serial_number}")
+            }
+            SerialSelector::UsbId { vid, pid } => write!(f, "USB id {vid:04x}:{pid:04x}"),
+        }
+    }
+}
+
+/// 解析`1a86:7523`形式的VID:PID（十六进制）
+pub fn parse_usb_id(usb_id: &str) -> Option<(u16, u16)> {
+    let (vid, pid) = usb_id.trim().split_once(':')?;
+    Some((
+        u16::from_str_radix(vid, 16).ok()?,
+        u16::from_str_radix(pid, 16).ok()?,
+    ))
+}
+
+/// 枚举到的串口，只保留选择所需的信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerialCandidate {
+    pub path: String,
+    /// USB串口的VID、PID与序列号
+    pub usb: Option<(u16, u16, Option<String>)>,
+}
+
+impl From<SerialPortInfo> for SerialCandidate {
+    fn from(info: SerialPortInfo) -> Self {
+        let usb = match info.port_type {
+            SerialPortType::UsbPort(usb_info) => {
+                Some((usb_info.vid, usb_info.pid, usb_info.serial_number))
+            }
+            _ => None,
+        };
+        Self {
+            path: info.port_name,
+            usb,
+        }
+    }
+}
+
+impl std::fmt::Display for SerialCandidate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.usb {
+            Some((vid, pid, serial_number)) => write!(
+                f,
+                "{} (USB {vid:04x}:{pid:04x}, serial number {})",
+                self.path,
+                serial_number.as_deref().unwrap_or("-")
+            ),
+            None => f.write_str(&self.path),
+        }
+    }
+}
+
+impl SerialSelector {
+    /// 按配置选择串口，优先级：路径 > 序列号 > VID:PID。均未设置或VID:PID格式错误时返回`None`
+    pub fn from_config(serial_path: &str, serial_name: &str, usb_id: &str) -> Option<Self> {
+        if !serial_path.is_empty() {
+            return Some(SerialSelector::Path(serial_path.to_string()));
+        }
+        if !serial_name.is_empty() {
+            return Some(SerialSelector::SerialNumber(serial_name.to_string()));
+        }
+        if usb_id.is_empty() {
+            return None;
+        }
+        match parse_usb_id(usb_id) {
+            Some((vid, pid)) => Some(SerialSelector::UsbId { vid, pid }),
+            None => {
+                bevy::log::warn!("Invalid usb_id: {usb_id}");
+                None
+            }
+        }
+    }
+
+    /// 在枚举到的串口中查找，返回串口路径。
+    /// 指定路径时不检查是否存在，因为`/dev/serial/by-id/...`等链接不会出现在枚举结果中。
+    /// 有多个串口符合VID:PID时取路径排序后的第一个。
+    pub fn resolve(&self, candidates: &[SerialCandidate]) -> Option<String> {
+        match self {
+            SerialSelector::Path(path) => Some(path.clone()),
+            SerialSelector::SerialNumber(serial_number) => candidates
+                .iter()
+                .find(|candidate| {
+                    candidate
+                        .usb
+                        .as_ref()
+                        .and_then(|(_, _, candidate_serial)| candidate_serial.as_deref())
+                        .is_some_and(|candidate_serial| candidate_serial == serial_number)
+                })
+                .map(|candidate| candidate.path.clone()),
+            SerialSelector::UsbId { vid, pid } => candidates
+                .iter()
+                .filter(|candidate| {
+                    candidate
+                        .usb
+                        .as_ref()
+                        .is_some_and(|(candidate_vid, candidate_pid, _)| {
+                            candidate_vid == vid && candidate_pid == pid
+                        })
+                })
+                .map(|candidate| candidate.path.clone())
+                .min(),
+        }
+    }
+}
+
+/// 枚举当前的串口
+pub fn available_candidates() -> Vec<SerialCandidate> {
+    match mio_serial::available_ports() {
+        Ok(ports) => ports.into_iter().map(SerialCandidate::from).collect(),
+        Err(err) => {
+            bevy::log::warn!("Failed to enumerate serial ports! {err}");
+            Vec::new()
+        }
+    }
+}
+
+/// 以8位数据位、1位停止位、无校验打开串口（非阻塞），并注册到新建的`Poll`中
+pub fn open_serial_stream(
+    path: &str,
+    baud_rate: u32,
+    token: Token,
+) -> Result<(Poll, SerialStream), String> {
+    // Create a poll instance.
+    let poll = Poll::new().map_err(|err| format!("Failed to create pull using mio! {err:?}"))?;
+
+    // Create the serial port
+    bevy::log::info!("Opening {path} at {baud_rate} baud");
+    let mut stream = mio_serial::new(path, baud_rate)
+        .data_bits(mio_serial::DataBits::Eight)
+        .stop_bits(mio_serial::StopBits::One)
+        .parity(mio_serial::Parity::None)
+        .open_native_async()
+        .map_err(|err| format!("Failed to open serial device {path} using mio! {err:?}"))?;
+
+    poll.registry()
+        .register(&mut stream, token, Interest::READABLE)
+        .map_err(|err| format!("Failed to register serial device using mio! {err:?}"))?;
+    Ok((poll, stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates() -> Vec<SerialCandidate> {
+        vec![
+            SerialCandidate {
+                path: "/dev/ttyS0".to_string(),
+                usb: None,
+            },
+            SerialCandidate {
+                path: "/dev/ttyUSB1".to_string(),
+                usb: Some((0x1a86, 0x7523, Some("MPU-B".to_string()))),
+            },
+            SerialCandidate {
+                path: "/dev/ttyUSB0".to_string(),
+                usb: Some((0x1a86, 0x7523, Some("MPU-A".to_string()))),
+            },
+            SerialCandidate {
+                path: "/dev/ttyACM0".to_string(),
+                usb: Some((0x0483, 0x5740, None)),
+            },
+        ]
+    }
+
+    #[test]
+    fn select_port() {
+        let candidates = candidates();
+        assert_eq!(
+            SerialSelector::SerialNumber("MPU-B".to_string()).resolve(&candidates),
+            Some("/dev/ttyUSB1".to_string())
+        );
+        assert_eq!(
+            SerialSelector::SerialNumber("MPU-C".to_string()).resolve(&candidates),
+            None
+        );
+        // 多个符合时取第一个
+        assert_eq!(
+            SerialSelector::UsbId {
+                vid: 0x1a86,
+                pid: 0x7523
+            }
+            .resolve(&candidates),
+            Some("/dev/ttyUSB0".to_string())
+        );
+        assert_eq!(
+            SerialSelector::UsbId {
+                vid: 0x1a86,
+                pid: 0x0000
+            }
+            .resolve(&candidates),
+            None
+        );
+        // 指定路径时不需要出现在枚举结果中
+        assert_eq!(
+            SerialSelector::Path("/dev/serial/by-id/usb-MPU".to_string()).resolve(&[]),
+            Some("/dev/serial/by-id/usb-MPU".to_string())
+        );
+    }
+
+    #[test]
+    fn config_priority() {
+        assert_eq!(
+            SerialSelector::from_config("/dev/ttyUSB0", "MPU-A", "1a86:7523"),
+            Some(SerialSelector::Path("/dev/ttyUSB0".to_string()))
+        );
+        assert_eq!(
+            SerialSelector::from_config("", "MPU-A", "1a86:7523"),
+            Some(SerialSelector::SerialNumber("MPU-A".to_string()))
+        );
+        assert_eq!(
+            SerialSelector::from_config("", "", "1a86:7523"),
+            Some(SerialSelector::UsbId {
+                vid: 0x1a86,
+                pid: 0x7523
+            })
+        );
+        assert_eq!(SerialSelector::from_config("", "", ""), None);
+        assert_eq!(SerialSelector::from_config("", "", "1a86"), None);
+    }
+
+    #[test]
+    fn usb_id() {
+        assert_eq!(parse_usb_id("1a86:7523"), Some((0x1a86, 0x7523)));
+        assert_eq!(parse_usb_id(" 0483:5740 "), Some((0x0483, 0x5740)));
+        assert_eq!(parse_usb_id("1a86"), None);
+        assert_eq!(parse_usb_id("xyz:7523"), None);
+    }
+}
@@ -0,0 +1,126 @@
+//! 串口数据流分帧：寻找包头、校验，并保留跨越两次读取的半帧
+
+use std::{collections::VecDeque, marker::PhantomData};
+
+/// 解码统计，供界面显示
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// 校验通过且能解析的帧数
+    pub good: u64,
+    /// 有包头、但校验失败或长度超出范围的帧数
+    pub bad_checksum: u64,
+    /// 校验通过、但无法解析的帧数
+    pub unknown: u64,
+    /// 为寻找包头而丢弃数据的次数
+    pub resync: u64,
+    /// 为寻找包头而丢弃的字节数
+    pub skipped_bytes: u64,
+}
+
+/// 校验与解析一帧的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameCheck<T> {
+    Good(T),
+    BadChecksum,
+    Unknown,
+}
+
+/// 帧格式：包头、帧长与校验
+pub trait FrameFormat {
+    type Frame;
+    /// 包头
+    const HEADER: [u8; 2];
+    /// 确定帧长所需的字节数（含包头）
+    const HEAD_LEN: usize;
+
+    /// 由帧的前`HEAD_LEN`个字节得到整帧长度，不合法（假包头）时返回`None`
+    fn frame_len(head: &[u8]) -> Option<usize>;
+
+    /// 校验并解析一帧，`frame`由包头开始、长度为`frame_len`的返回值
+    fn check(frame: &[u8]) -> FrameCheck<Self::Frame>;
+}
+
+/// 流式解码器
+#[derive(Debug)]
+pub struct FrameDecoder<F> {
+    buf: VecDeque<u8>,
+    stats: FrameStats,
+    format: PhantomData<F>,
+}
+
+impl<F> Default for FrameDecoder<F> {
+    fn default() -> Self {
+        Self {
+            buf: VecDeque::new(),
+            stats: FrameStats::default(),
+            format: PhantomData,
+        }
+    }
+}
+
+impl<F: FrameFormat> FrameDecoder<F> {
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+
+    /// 清空缓冲区，用于重新连接后。统计保留
+    pub fn reset(&mut self) {
+        self.buf.clear();
+    }
+
+    /// 加入新读到的数据，返回其中完整、校验通过且能解析的帧
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<F::Frame> {
+        self.buf.extend(bytes);
+        let mut frames = Vec::new();
+        loop {
+            // 寻找包头
+            let header_pos = self
+                .buf
+                .iter()
+                .zip(self.buf.iter().skip(1))
+                .position(|(first, second)| [*first, *second] == F::HEADER);
+            let skip_len = match header_pos {
+                Some(pos) => pos,
+                // 没有包头：最后一个字节可能是下一个包头的前半
+                None if self.buf.back() == Some(&F::HEADER[0]) => self.buf.len() - 1,
+                None => self.buf.len(),
+            };
+            if skip_len > 0 {
+                self.buf.drain(..skip_len);
+                self.stats.resync += 1;
+                self.stats.skipped_bytes += skip_len as u64;
+            }
+            if header_pos.is_none() || self.buf.len() < F::HEAD_LEN {
+                // 等待后续数据
+                break;
+            }
+            let buf = self.buf.make_contiguous();
+            let Some(frame_len) = F::frame_len(&buf[..F::HEAD_LEN]) else {
+                // 假包头
+                self.buf.pop_front();
+                self.stats.bad_checksum += 1;
+                continue;
+            };
+            if buf.len() < frame_len {
+                break;
+            }
+            match F::check(&buf[..frame_len]) {
+                FrameCheck::Good(frame) => {
+                    self.buf.drain(..frame_len);
+                    self.stats.good += 1;
+                    frames.push(frame);
+                }
+                FrameCheck::BadChecksum => {
+                    // 可能是数据中恰好出现的假包头：只跳过包头的第一个字节，从下一个位置重新寻找
+                    self.buf.pop_front();
+                    self.stats.bad_checksum += 1;
+                }
+                FrameCheck::Unknown => {
+                    self.buf.drain(..frame_len);
+                    self.stats.unknown += 1;
+                }
+            }
+        }
+        frames
+    }
+}
@@ -0,0 +1,228 @@
+//! 串口连接状态：自动重连与数据超时检测
+
+use std::time::{Duration, Instant};
+
+use crate::message_channel::ChannelBackoff;
+
+/// 串口连接状态
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum SerialConnectionState {
+    /// 未请求连接
+    #[default]
+    Disconnected,
+    /// 正在打开串口，或已打开、等待第一帧有效数据
+    Connecting,
+    /// 正常接收数据
+    Streaming,
+    /// 串口已打开，但超时没有收到有效数据
+    Stale,
+    /// 打开或读取失败，等待重试
+    Error(String),
+}
+
+/// 连接的记录，由此推算`SerialConnectionState`
+#[derive(Debug)]
+pub struct SerialLink {
+    /// 请求连接的串口：外层为`None`时未请求连接，内层为`None`时按配置文件选择
+    request: Option<Option<String>>,
+    /// 串口是否已打开
+    open: bool,
+    /// 连续失败的次数
+    failures: u32,
+    last_error: Option<String>,
+    retry_time: Instant,
+    open_time: Instant,
+    last_frame_time: Option<Instant>,
+}
+
+impl Default for SerialLink {
+    fn default() -> Self {
+        let now = Instant::now();
+        Self {
+            request: None,
+            open: false,
+            failures: 0,
+            last_error: None,
+            retry_time: now,
+            open_time: now,
+            last_frame_time: None,
+        }
+    }
+}
+
+impl SerialLink {
+    /// 请求连接，立即尝试
+    pub fn request(&mut self, path: Option<String>, now: Instant) {
+        self.request = Some(path);
+        self.open = false;
+        self.failures = 0;
+        self.last_error = None;
+        self.retry_time = now;
+    }
+
+    /// 取消连接，不再重试
+    pub fn cancel(&mut self) {
+        self.request = None;
+        self.open = false;
+        self.last_error = None;
+    }
+
+    /// 到了尝试打开串口的时间时，返回请求连接的串口
+    pub fn due(&self, now: Instant) -> Option<&Option<String>> {
+        match &self.request {
+            Some(path) if !self.open && now >= self.retry_time => Some(path),
+            _ => None,
+        }
+    }
+
+    pub fn opened(&mut self, now: Instant) {
+        self.open = true;
+        self.last_error = None;
+        self.open_time = now;
+        self.last_frame_time = None;
+    }
+
+    /// 打开或读取失败，按退避时间安排重试
+    pub fn failed(&mut self, error: String, now: Instant, backoff: &ChannelBackoff) {
+        self.open = false;
+        self.last_error = Some(error);
+        self.retry_time = now + backoff.delay(self.failures);
+        self.failures = self.failures.saturating_add(1);
+    }
+
+    pub fn received(&mut self, now: Instant) {
+        self.failures = 0;
+        self.last_frame_time = Some(now);
+    }
+
+    /// 串口已打开时，距离上一帧有效数据（或打开串口）的时间
+    pub fn silence(&self, now: Instant) -> Option<Duration> {
+        self.open
+            .then(|| now.saturating_duration_since(self.last_frame_time.unwrap_or(self.open_time)))
+    }
+
+    pub fn state(&self, now: Instant, stale_timeout: Duration) -> SerialConnectionState {
+        if self.request.is_none() {
+            return SerialConnectionState::Disconnected;
+        }
+        let Some(silence) = self.silence(now) else {
+            return match &self.last_error {
+                Some(error) => SerialConnectionState::Error(error.clone()),
+                None => SerialConnectionState::Connecting,
+            };
+        };
+        match self.last_frame_time {
+            _ if silence >= stale_timeout => SerialConnectionState::Stale,
+            Some(_) => SerialConnectionState::Streaming,
+            None => SerialConnectionState::Connecting,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STALE_TIMEOUT: Duration = Duration::from_millis(200);
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn connect_and_stale() {
+        let start = Instant::now();
+        let mut link = SerialLink::default();
+        assert_eq!(
+            link.state(start, STALE_TIMEOUT),
+            SerialConnectionState::Disconnected
+        );
+        assert_eq!(link.due(start), None);
+
+        link.request(None, start);
+        assert_eq!(link.due(start), Some(&None));
+        assert_eq!(
+            link.state(start, STALE_TIMEOUT),
+            SerialConnectionState::Connecting
+        );
+
+        link.opened(start);
+        assert_eq!(link.due(start), None);
+        assert_eq!(
+            link.state(start + ms(100), STALE_TIMEOUT),
+            SerialConnectionState::Connecting
+        );
+        // 打开后一直没有数据
+        assert_eq!(
+            link.state(start + ms(200), STALE_TIMEOUT),
+            SerialConnectionState::Stale
+        );
+
+        link.received(start + ms(250));
+        assert_eq!(
+            link.state(start + ms(300), STALE_TIMEOUT),
+            SerialConnectionState::Streaming
+        );
+        assert_eq!(link.silence(start + ms(300)), Some(ms(50)));
+        assert_eq!(
+            link.state(start + ms(450), STALE_TIMEOUT),
+            SerialConnectionState::Stale
+        );
+        // 数据恢复
+        link.received(start + ms(500));
+        assert_eq!(
+            link.state(start + ms(500), STALE_TIMEOUT),
+            SerialConnectionState::Streaming
+        );
+
+        link.cancel();
+        assert_eq!(
+            link.state(start + ms(500), STALE_TIMEOUT),
+            SerialConnectionState::Disconnected
+        );
+        assert_eq!(link.silence(start + ms(500)), None);
+    }
+
+    #[test]
+    fn retry_with_backoff() {
+        let backoff = ChannelBackoff {
+            initial_ms: 100,
+            max_ms: 300,
+        };
+        let start = Instant::now();
+        let mut link = SerialLink::default();
+        link.request(Some("/dev/ttyUSB0".to_string()), start);
+
+        link.failed("unplugged".to_string(), start, &backoff);
+        assert_eq!(
+            link.state(start, STALE_TIMEOUT),
+            SerialConnectionState::Error("unplugged".to_string())
+        );
+        assert_eq!(link.due(start + ms(99)), None);
+        assert!(link.due(start + ms(100)).is_some());
+
+        // 退避时间翻倍，最多`max_ms`
+        let mut now = start + ms(100);
+        for delay in [200, 300, 300] {
+            link.failed("unplugged".to_string(), now, &backoff);
+            assert_eq!(link.due(now + ms(delay - 1)), None);
+            now += ms(delay);
+            assert_eq!(link.due(now), Some(&Some("/dev/ttyUSB0".to_string())));
+        }
+
+        // 重新插入后收到数据，退避时间复位
+        link.opened(now);
+        link.received(now);
+        assert_eq!(
+            link.state(now, STALE_TIMEOUT),
+            SerialConnectionState::Streaming
+        );
+        link.failed("unplugged".to_string(), now, &backoff);
+        assert!(link.due(now + ms(100)).is_some());
+
+        // 重新请求连接时立即尝试
+        link.failed("unplugged".to_string(), now, &backoff);
+        link.request(None, now);
+        assert_eq!(link.due(now), Some(&None));
+    }
+}
@@ -1,18 +1,19 @@
 mod function;
 
-use bevy::{asset::AssetPath, prelude::*};
+use bevy::prelude::*;
 use bevy_mod_picking::prelude::*;
 
 use crate::{
+    load_font,
     ui_components::{
         button_effect::ButtonColorCollection,
         input_area::{InputArea, InputAreaType},
     },
-    FONT_PATH,
 };
 
 use self::function::{
-    ConnectCoachActivator, ConnectCoachInputArea, ConnectMPUActivator, ConnectMPUInputArea,
+    ConnectCoachActivator, ConnectCoachInputArea, ConnectMPUActivator, ConnectMPUActivatorText,
+    ConnectMPUInputArea, LowerStatusText, MPUConnectionStateText, MPUFrameStatsText,
     RobotUiFunctionPlugin, ToggleCppInputActivator, ToggleRustInputActivator,
 };
 
@@ -50,10 +51,8 @@ const INPUT_AREA_COLOR_COLLECTION: ButtonColorCollection =
  */
 
 fn ui_robot_startup_system(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let font_asset_path = AssetPath::from_path(&FONT_PATH);
-
     let text_style = TextStyle {
-        font: asset_server.load::<Font>(font_asset_path),
+        font: load_font(&asset_server),
         font_size: FONT_SIZE,
         color: Color::BLACK,
     };
@@ -299,7 +298,8 @@ fn on_com_robot_area(node_parent: &mut ChildBuilder<'_>, text_style: TextStyle)
         .insert(Style {
             height: Val::Px(30.0),
             ..default()
-        });
+        })
+        .insert(LowerStatusText);
 }
 
 fn on_com_mpu_area(node_parent: &mut ChildBuilder<'_>, text_style: TextStyle) {
@@ -318,8 +318,9 @@ fn on_com_mpu_area(node_parent: &mut ChildBuilder<'_>, text_style: TextStyle) {
             height: Val::Px(30.0),
             grid_column: GridPlacement::span(2),
             ..default()
-        });
-    // MPU Com口输入框
+        })
+        .insert(MPUConnectionStateText);
+    // MPU串口输入框：串口编号或路径，空白时按配置文件选择
     node_parent
         .spawn(ButtonBundle {
             style: Style {
@@ -336,7 +337,7 @@ fn on_com_mpu_area(node_parent: &mut ChildBuilder<'_>, text_style: TextStyle) {
             ..default()
         })
         .insert(InputArea {
-            area_type: InputAreaType::Number,
+            area_type: InputAreaType::All,
         })
         .insert(ConnectMPUInputArea)
         .with_children(|parent| {
@@ -363,8 +364,24 @@ fn on_com_mpu_area(node_parent: &mut ChildBuilder<'_>, text_style: TextStyle) {
         .insert(BUTTON_COLOR_COLLECTION)
         .insert(ConnectMPUActivator)
         .with_children(|parent| {
-            parent.spawn(TextBundle::from_section("连接", text_style));
+            parent
+                .spawn(TextBundle::from_section("连接", text_style.clone()))
+                .insert(ConnectMPUActivatorText);
         });
+    // 解码统计
+    node_parent
+        .spawn(TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: FONT_SIZE * 0.6,
+                ..text_style
+            },
+        ))
+        .insert(Style {
+            grid_column: GridPlacement::span(2),
+            ..default()
+        })
+        .insert(MPUFrameStatsText);
 }
 
 /*
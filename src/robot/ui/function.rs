@@ -4,7 +4,11 @@ use bevy::prelude::*;
 
 use crate::{
     robot::{
-        com_mpu::MPUConnectEvent,
+        com_mpu::{
+            mpu_decoder::MPUFrameStats, MPUConnectEvent, MPUConnectionState, MPUDisConnectEvent,
+        },
+        com_robot::{lower_frame::LowerFrameStats, LowerConnectionState},
+        serial::link::SerialConnectionState,
         test_cpp::{TestCppInputData, TestCppInputModule},
         test_rust::{TestRustInputData, TestRustInputModule},
     },
@@ -24,7 +28,10 @@ impl Plugin for RobotUiFunctionPlugin {
             .add_systems(Update, show_value_cpp_system)
             .add_systems(Update, activate_toggle_rust_system)
             .add_systems(Update, show_value_rust_system)
-            .add_systems(Update, activate_connect_mpu_system);
+            .add_systems(Update, activate_connect_mpu_system)
+            .add_systems(Update, show_mpu_frame_stats_system)
+            .add_systems(Update, show_mpu_connection_state_system)
+            .add_systems(Update, show_lower_status_system);
     }
 }
 
@@ -156,7 +163,9 @@ fn activate_connect_mpu_system(
     button_query: Query<&Interaction, (With<ConnectMPUActivator>, Changed<Interaction>)>,
     input_area_query: Query<&Children, With<ConnectMPUInputArea>>,
     text_query: Query<&Text>,
+    state: Res<MPUConnectionState>,
     mut connect_mpu_plugin_event: EventWriter<MPUConnectEvent>,
+    mut disconnect_mpu_plugin_event: EventWriter<MPUDisConnectEvent>,
 ) {
     let Some(_) = button_query
         .into_iter()
@@ -164,6 +173,11 @@ fn activate_connect_mpu_system(
     else {
         return;
     };
+    // 已请求连接时，按钮用于断开
+    if **state != SerialConnectionState::Disconnected {
+        disconnect_mpu_plugin_event.send(MPUDisConnectEvent);
+        return;
+    }
     let Some(children) = input_area_query.into_iter().next() else {
         return;
     };
@@ -171,13 +185,99 @@ fn activate_connect_mpu_system(
         let Ok(text_sections) = text_query.get(*child) else {
             continue;
         };
-        let text = &text_sections.sections[0].value;
-        let Ok(com_id) = text.parse::<u32>() else {
-            continue;
+        let text = text_sections.sections[0].value.trim();
+        // 空白时按配置文件选择；只输入数字时视为串口编号
+        let path = match text.parse::<u32>() {
+            _ if text.is_empty() => None,
+            Ok(com_id) if cfg!(windows) => Some(format!("COM{com_id}")),
+            Ok(com_id) => Some(format!("/dev/ttyUSB{com_id}")),
+            Err(_) => Some(text.to_string()),
         };
-        connect_mpu_plugin_event.send(MPUConnectEvent {
-            com: com_id as usize,
-        });
+        connect_mpu_plugin_event.send(MPUConnectEvent { path });
         break;
     }
 }
+
+#[derive(Debug, Clone, Copy, Component)]
+pub(super) struct MPUFrameStatsText;
+
+fn show_mpu_frame_stats_system(
+    stats: Res<MPUFrameStats>,
+    mut text_query: Query<&mut Text, With<MPUFrameStatsText>>,
+) {
+    if !stats.is_changed() {
+        return;
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = format!(
+            "正常 {} 校验错误 {} 重同步 {}",
+            stats.good, stats.bad_checksum, stats.resync
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, Component)]
+pub(super) struct MPUConnectionStateText;
+
+#[derive(Debug, Clone, Copy, Component)]
+pub(super) struct ConnectMPUActivatorText;
+
+fn show_mpu_connection_state_system(
+    state: Res<MPUConnectionState>,
+    mut state_text_query: Query<&mut Text, With<MPUConnectionStateText>>,
+    mut activator_text_query: Query<
+        &mut Text,
+        (
+            With<ConnectMPUActivatorText>,
+            Without<MPUConnectionStateText>,
+        ),
+    >,
+) {
+    if !state.is_changed() {
+        return;
+    }
+    let (state_text, color) = connection_state_text(&state);
+    for mut text in state_text_query.iter_mut() {
+        text.sections[1].value = state_text.clone();
+        text.sections[1].style.color = color;
+    }
+    let activator_text = match **state {
+        SerialConnectionState::Disconnected => "连接",
+        _ => "断开",
+    };
+    for mut text in activator_text_query.iter_mut() {
+        text.sections[0].value = activator_text.to_string();
+    }
+}
+
+/// 串口连接状态的显示文字与颜色
+fn connection_state_text(state: &SerialConnectionState) -> (String, Color) {
+    match state {
+        SerialConnectionState::Disconnected => ("未连接".to_string(), Color::RED),
+        SerialConnectionState::Connecting => ("连接中".to_string(), Color::YELLOW),
+        SerialConnectionState::Streaming => ("正常".to_string(), Color::GREEN),
+        SerialConnectionState::Stale => ("无数据".to_string(), Color::ORANGE),
+        SerialConnectionState::Error(error) => (format!("错误，重试中：{error}"), Color::RED),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Component)]
+pub(super) struct LowerStatusText;
+
+fn show_lower_status_system(
+    state: Res<LowerConnectionState>,
+    stats: Res<LowerFrameStats>,
+    mut text_query: Query<&mut Text, With<LowerStatusText>>,
+) {
+    let (state_text, color) = connection_state_text(&state);
+    let status_text = format!(
+        "{state_text} 正常 {} 校验错误 {}",
+        stats.good, stats.bad_checksum
+    );
+    for mut text in text_query.iter_mut() {
+        if text.sections[1].value != status_text {
+            text.sections[1].value = status_text.clone();
+            text.sections[1].style.color = color;
+        }
+    }
+}
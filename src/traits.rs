@@ -2,6 +2,8 @@ use std::path::Path;
 
 use serde::{de::DeserializeOwned, Serialize};
 
+use crate::error::{BigHeroXError, BigHeroXResult};
+
 use std::{
     marker::Send,
     sync::{Arc, Condvar, Mutex},
@@ -9,8 +11,8 @@ use std::{
 
 /// 可快速存取的数据。可以一行代码完成读取和写入操作。
 pub trait FastAccessData<'de>: Serialize + DeserializeOwned {
-    /// 文件路径
-    fn file_path() -> &'static str;
+    /// 文件路径。找不到程序目录时返回错误。
+    fn file_path() -> BigHeroXResult<&'static str>;
 
     /// 添加至文件开头
     fn file_header() -> &'static str {
@@ -23,23 +25,22 @@ pub trait FastAccessData<'de>: Serialize + DeserializeOwned {
     }
 
     fn save(&self) -> Result<(), FastAccessDataError> {
+        let file_path = Self::file_path()?;
         // Create Parent dir
-        let parent_dir = Path::new(Self::file_path())
-            .parent()
-            .ok_or(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                "FastAccessData: Failed to get parent!!!",
-            ))?;
+        let parent_dir = Path::new(file_path).parent().ok_or(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "FastAccessData: Failed to get parent!!!",
+        ))?;
         std::fs::create_dir_all(parent_dir)?;
         // Write config
         let mut toml_string = toml::to_string_pretty(self)?;
         toml_string.insert_str(0, Self::file_header());
         toml_string.push_str(Self::file_trailer());
-        Ok(std::fs::write(Self::file_path(), toml_string)?)
+        Ok(std::fs::write(file_path, toml_string)?)
     }
 
     fn load() -> Result<Self, FastAccessDataError> {
-        let toml_string = std::fs::read_to_string(Self::file_path())?;
+        let toml_string = std::fs::read_to_string(Self::file_path()?)?;
         Ok(toml::from_str(&toml_string)?)
     }
 
@@ -51,12 +52,13 @@ pub trait FastAccessData<'de>: Serialize + DeserializeOwned {
             println!("load_or_default: has error: {:?}, now using default.", err);
             let val = Self::default();
             // Save file
-            Self::save(&val).unwrap_or_else(|_| {
-                panic!(
-                    "load_or_default: Failed to write default on {}",
-                    Self::file_path()
-                )
-            });
+            if let Err(err) = Self::save(&val) {
+                println!(
+                    "load_or_default: Failed to write default on {:?}: {:?}",
+                    Self::file_path(),
+                    err
+                );
+            }
             val
         })
     }
@@ -64,11 +66,18 @@ pub trait FastAccessData<'de>: Serialize + DeserializeOwned {
 
 #[derive(Debug)]
 pub enum FastAccessDataError {
+    Path(BigHeroXError),
     Io(std::io::Error),
     TomlDe(toml::de::Error),
     TomlSer(toml::ser::Error),
 }
 
+impl From<BigHeroXError> for FastAccessDataError {
+    fn from(value: BigHeroXError) -> Self {
+        Self::Path(value)
+    }
+}
+
 impl From<std::io::Error> for FastAccessDataError {
     fn from(value: std::io::Error) -> Self {
         Self::Io(value)